@@ -28,16 +28,15 @@ pub fn add_100_2d(c: &mut Criterion) {
     for size in [100, 1_000, 10_000, 100_000, 1_000_000].iter() {
         group.throughput(Throughput::Elements(100));
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
-            let points_to_add: Vec<([f64; 2], f64)> =
-                (0..100).into_iter().map(|_| rand_data_2d()).collect();
+            let points_to_add: Vec<([f64; 2], f64)> = (0..100).map(|_| rand_data_2d()).collect();
 
             let mut points = vec![];
             let mut kdtree = KdTree::with_capacity(16).unwrap();
             for _ in 0..size {
                 points.push(rand_data_2d());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -55,16 +54,15 @@ pub fn add_100_3d(c: &mut Criterion) {
     for size in [100, 1_000, 10_000, 100_000, 1_000_000].iter() {
         group.throughput(Throughput::Elements(100));
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
-            let points_to_add: Vec<([f64; 3], f64)> =
-                (0..100).into_iter().map(|_| rand_data_3d()).collect();
+            let points_to_add: Vec<([f64; 3], f64)> = (0..100).map(|_| rand_data_3d()).collect();
 
             let mut points = vec![];
             let mut kdtree = KdTree::with_capacity(16).unwrap();
             for _ in 0..size {
                 points.push(rand_data_3d());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -82,16 +80,15 @@ pub fn add_100_4d(c: &mut Criterion) {
     for size in [100, 1_000, 10_000, 100_000, 1_000_000].iter() {
         group.throughput(Throughput::Elements(100));
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
-            let points_to_add: Vec<([f64; 4], f64)> =
-                (0..100).into_iter().map(|_| rand_data_4d()).collect();
+            let points_to_add: Vec<([f64; 4], f64)> = (0..100).map(|_| rand_data_4d()).collect();
 
             let mut points = vec![];
             let mut kdtree = KdTree::with_capacity(16).unwrap();
             for _ in 0..size {
                 points.push(rand_data_4d());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -110,15 +107,15 @@ pub fn add_100_3d_f32(c: &mut Criterion) {
         group.throughput(Throughput::Elements(100));
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
             let points_to_add: Vec<([f32; 3], f32)> =
-                (0..100).into_iter().map(|_| rand_data_3d_f32()).collect();
+                (0..100).map(|_| rand_data_3d_f32()).collect();
 
             let mut points = vec![];
             let mut kdtree = KdTree::with_capacity(16).unwrap();
             for _ in 0..size {
                 points.push(rand_data_3d_f32());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -2,34 +2,29 @@
 #[macro_use]
 extern crate lazy_static;
 
+extern crate aligned;
 extern crate kiddo;
+extern crate num_traits;
 extern crate rand;
 extern crate test;
-extern crate num_traits;
-extern crate aligned;
 
-use rand::distributions::{UnitSphereSurface, Distribution};
-use kiddo::distance::{squared_euclidean, dot_product, dot_product_sse, dot_product_sse_aligned};
+use kiddo::distance::{
+    dot_product, dot_product_sse, dot_product_sse_aligned, squared_euclidean, SquaredEuclidean,
+};
 use kiddo::KdTree;
+use rand::distributions::{Distribution, UnitSphereSurface};
 use test::Bencher;
 
-use num_traits::{FromPrimitive};
+use num_traits::FromPrimitive;
 
-use std::arch::x86_64::*;
 use aligned::{Aligned, A16};
-use std::hint::black_box;
-
-union SimdToArray {
-    array: [f32; 4],
-    simd: __m128
-}
 
 lazy_static! {
     static ref SPHERE: UnitSphereSurface = UnitSphereSurface::new();
 }
 
 //fn rand_data() -> ([f64; 3], f64) {
-    //rand::random()
+//rand::random()
 //}
 
 fn rand_unit_sphere_point_f64() -> [f64; 3] {
@@ -52,7 +47,7 @@ fn rand_unit_sphere_point_f32_qwalign() -> [f32; 4] {
         f32::from_f64(sph64[0]).unwrap(),
         f32::from_f64(sph64[1]).unwrap(),
         f32::from_f64(sph64[2]).unwrap(),
-        0f32
+        0f32,
     ]);
     *res
 }
@@ -82,8 +77,8 @@ fn bench_add_to_kdtree_with_1k_3d_points(b: &mut Bencher) {
     for _ in 0..len {
         points.push(rand_data());
     }
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
     b.iter(|| kdtree.add(&point.0, point.1).unwrap());
 }
@@ -97,8 +92,8 @@ fn bench_nearest_from_kdtree_with_1k_3d_points(b: &mut Bencher) {
     for _ in 0..len {
         points.push(rand_data());
     }
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
     b.iter(|| kdtree.nearest(&point.0, 1000, &squared_euclidean).unwrap());
 }
@@ -114,8 +109,8 @@ fn bench_nearest_from_kdtree_with_150k_3d_points_squared_euclidean(b: &mut Bench
         points.push(rand_sphere_data());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     b.iter(|| kdtree.nearest(&point.0, 50000, &squared_euclidean).unwrap());
@@ -132,8 +127,8 @@ fn bench_nearest_with_1_from_kdtree_with_150k_3d_points_squared_euclidean(b: &mu
         points.push(rand_sphere_data());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     b.iter(|| kdtree.nearest(&point.0, 1, &squared_euclidean).unwrap());
@@ -150,13 +145,31 @@ fn bench_nearest_one_from_kdtree_with_150k_3d_points_squared_euclidean(b: &mut B
         points.push(rand_sphere_data());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     b.iter(|| kdtree.nearest_one(&point.0, &squared_euclidean).unwrap());
 }
 
+#[bench]
+fn bench_nearest_one_from_kdtree_with_150k_3d_points_squared_euclidean_batched(b: &mut Bencher) {
+    let len = 150000usize;
+    let point = rand_sphere_data();
+    let mut points = vec![];
+    let mut kdtree = KdTree::with_capacity(16).unwrap();
+
+    for _ in 0..len {
+        points.push(rand_sphere_data());
+    }
+
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    b.iter(|| kdtree.nearest_one(&point.0, &SquaredEuclidean).unwrap());
+}
+
 #[bench]
 fn bench_best_n_within_150k_3d_squared_euclidean(b: &mut Bencher) {
     let len = 150000usize;
@@ -168,11 +181,37 @@ fn bench_best_n_within_150k_3d_squared_euclidean(b: &mut Bencher) {
         points.push(rand_sphere_data());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
-    b.iter(|| kdtree.best_n_within(&point.0, 0.1, 500, &squared_euclidean).unwrap());
+    b.iter(|| {
+        kdtree
+            .best_n_within(&point.0, 0.1, 500, &squared_euclidean)
+            .unwrap()
+    });
+}
+
+#[bench]
+fn bench_best_n_within_150k_3d_squared_euclidean_batched(b: &mut Bencher) {
+    let len = 150000usize;
+    let point = rand_sphere_data();
+    let mut points = vec![];
+    let mut kdtree = KdTree::with_capacity(16).unwrap();
+
+    for _ in 0..len {
+        points.push(rand_sphere_data());
+    }
+
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    b.iter(|| {
+        kdtree
+            .best_n_within(&point.0, 0.1, 500, &SquaredEuclidean)
+            .unwrap()
+    });
 }
 
 #[bench]
@@ -186,8 +225,8 @@ fn bench_nearest_from_kdtree_with_150k_3d_points_dot_product(b: &mut Bencher) {
         points.push(rand_sphere_data_f32());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     b.iter(|| kdtree.nearest(&point.0, 50000, &dot_product).unwrap());
@@ -205,8 +244,8 @@ fn bench_nearest_from_kdtree_with_150k_3d_points_dot_product_sse(b: &mut Bencher
         points.push(rand_sphere_data_f32());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     println!("calling nearest");
@@ -225,10 +264,14 @@ fn bench_nearest_from_kdtree_with_150k_3d_points_dot_product_sse_aligned(b: &mut
         points.push(rand_sphere_data_f32_qw());
     }
 
-    for i in 0..points.len() {
-        kdtree.add(&points[i].0, points[i].1).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
     }
 
     println!("calling nearest");
-    b.iter(|| kdtree.nearest(&point.0, 50000, &dot_product_sse).unwrap());
+    b.iter(|| {
+        kdtree
+            .nearest(&point.0, 50000, &dot_product_sse_aligned)
+            .unwrap()
+    });
 }
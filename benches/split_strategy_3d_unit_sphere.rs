@@ -0,0 +1,118 @@
+#[macro_use]
+extern crate lazy_static;
+extern crate criterion;
+extern crate kiddo;
+extern crate rand;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use kiddo::distance::SquaredEuclidean;
+use kiddo::{KdTree, SplitStrategy};
+use rand::distributions::{Distribution, UnitSphereSurface};
+
+lazy_static! {
+    static ref SPHERE: UnitSphereSurface = UnitSphereSurface::new();
+}
+
+const STRATEGIES: [SplitStrategy; 4] = [
+    SplitStrategy::Midpoint,
+    SplitStrategy::Median,
+    SplitStrategy::SlidingMidpoint,
+    SplitStrategy::MaxVariance,
+];
+
+fn rand_unit_sphere_point_f64() -> [f64; 3] {
+    SPHERE.sample(&mut rand::thread_rng())
+}
+
+fn rand_sphere_data() -> ([f64; 3], usize) {
+    (rand_unit_sphere_point_f64(), rand::random())
+}
+
+fn sphere_tree(size: usize, strategy: SplitStrategy) -> KdTree<f64, usize, 3> {
+    let mut kdtree = KdTree::with_split_strategy(16, strategy).unwrap();
+    for _ in 0..size {
+        let (point, data) = rand_sphere_data();
+        kdtree.add(&point, data).unwrap();
+    }
+    kdtree
+}
+
+pub fn add_by_strategy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add 100 items by split strategy");
+
+    for size in [1_000, 100_000, 1_000_000].iter() {
+        for strategy in STRATEGIES.iter() {
+            group.throughput(Throughput::Elements(100));
+            let id = BenchmarkId::new(format!("{:?}", strategy), size);
+            group.bench_with_input(id, size, |b, &size| {
+                let points_to_add: Vec<([f64; 3], usize)> =
+                    (0..100).map(|_| rand_sphere_data()).collect();
+                let mut kdtree = sphere_tree(size, *strategy);
+
+                b.iter(|| {
+                    points_to_add.iter().for_each(|point| {
+                        black_box(kdtree.add(black_box(&point.0), point.1)).unwrap()
+                    })
+                });
+            });
+        }
+    }
+}
+
+pub fn nearest_1_by_strategy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearest(1) by split strategy");
+
+    for size in [1_000, 100_000, 1_000_000].iter() {
+        for strategy in STRATEGIES.iter() {
+            let id = BenchmarkId::new(format!("{:?}", strategy), size);
+            group.bench_with_input(id, size, |b, &size| {
+                let point = rand_sphere_data();
+                let kdtree = sphere_tree(size, *strategy);
+
+                b.iter(|| black_box(kdtree.nearest_one(&point.0, &SquaredEuclidean)).unwrap());
+            });
+        }
+    }
+}
+
+pub fn nearest_100_by_strategy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearest(100) by split strategy");
+
+    for size in [1_000, 100_000, 1_000_000].iter() {
+        for strategy in STRATEGIES.iter() {
+            let id = BenchmarkId::new(format!("{:?}", strategy), size);
+            group.bench_with_input(id, size, |b, &size| {
+                let point = rand_sphere_data();
+                let kdtree = sphere_tree(size, *strategy);
+
+                b.iter(|| black_box(kdtree.nearest(&point.0, 100, &SquaredEuclidean)).unwrap());
+            });
+        }
+    }
+}
+
+pub fn within_by_strategy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("within(0.01) by split strategy");
+
+    for size in [1_000, 100_000, 1_000_000].iter() {
+        for strategy in STRATEGIES.iter() {
+            let id = BenchmarkId::new(format!("{:?}", strategy), size);
+            group.bench_with_input(id, size, |b, &size| {
+                let point = rand_sphere_data();
+                let kdtree = sphere_tree(size, *strategy);
+
+                b.iter(|| black_box(kdtree.within(&point.0, 0.01, &SquaredEuclidean)).unwrap());
+            });
+        }
+    }
+}
+
+criterion_group!(
+    benches,
+    add_by_strategy,
+    nearest_1_by_strategy,
+    nearest_100_by_strategy,
+    within_by_strategy
+);
+criterion_main!(benches);
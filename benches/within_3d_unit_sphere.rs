@@ -1,75 +1,27 @@
 #[macro_use]
 extern crate lazy_static;
-extern crate aligned;
 extern crate criterion;
 extern crate kiddo;
-extern crate num_traits;
 extern crate rand;
 
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
-use aligned::{Aligned, A16};
-use kiddo::distance::squared_euclidean;
+use kiddo::distance::{squared_euclidean, SquaredEuclidean};
 use kiddo::KdTree;
-use num_traits::FromPrimitive;
 use rand::distributions::{Distribution, UnitSphereSurface};
 
-use std::arch::x86_64::*;
-
-union SimdToArray {
-    array: [f32; 4],
-    simd: __m128,
-}
-
 lazy_static! {
     static ref SPHERE: UnitSphereSurface = UnitSphereSurface::new();
 }
 
-//fn rand_data() -> ([f64; 3], f64) {
-//rand::random()
-//}
-
 fn rand_unit_sphere_point_f64() -> [f64; 3] {
     SPHERE.sample(&mut rand::thread_rng())
 }
 
-fn rand_unit_sphere_point_f32() -> [f32; 3] {
-    let sph64: [f64; 3] = SPHERE.sample(&mut rand::thread_rng());
-    let res: Aligned<A16, _> = Aligned([
-        f32::from_f64(sph64[0]).unwrap(),
-        f32::from_f64(sph64[1]).unwrap(),
-        f32::from_f64(sph64[2]).unwrap(),
-    ]);
-    *res
-}
-
-fn rand_unit_sphere_point_f32_qwalign() -> [f32; 4] {
-    let sph64: [f64; 3] = SPHERE.sample(&mut rand::thread_rng());
-    let res: Aligned<A16, _> = Aligned([
-        f32::from_f64(sph64[0]).unwrap(),
-        f32::from_f64(sph64[1]).unwrap(),
-        f32::from_f64(sph64[2]).unwrap(),
-        0f32,
-    ]);
-    *res
-}
-
-fn rand_data() -> ([f64; 3], f64) {
-    rand::random()
-}
-
 fn rand_sphere_data() -> ([f64; 3], usize) {
     (rand_unit_sphere_point_f64(), rand::random())
 }
 
-fn rand_sphere_data_f32() -> ([f32; 3], usize) {
-    (rand_unit_sphere_point_f32(), rand::random())
-}
-
-fn rand_sphere_data_f32_qw() -> ([f32; 4], usize) {
-    (rand_unit_sphere_point_f32_qwalign(), rand::random())
-}
-
 pub fn within_small_euclidean2(c: &mut Criterion) {
     let mut group = c.benchmark_group("within(0.01)");
 
@@ -83,8 +35,8 @@ pub fn within_small_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| black_box(kdtree.within(&point.0, 0.01, &squared_euclidean)).unwrap());
@@ -105,8 +57,8 @@ pub fn within_medium_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| black_box(kdtree.within(&point.0, 0.05, &squared_euclidean)).unwrap());
@@ -127,8 +79,8 @@ pub fn within_large_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| black_box(kdtree.within(&point.0, 0.25, &squared_euclidean)).unwrap());
@@ -149,8 +101,8 @@ pub fn within_unsorted_small_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -173,8 +125,8 @@ pub fn within_unsorted_medium_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -197,8 +149,8 @@ pub fn within_unsorted_large_euclidean2(c: &mut Criterion) {
             for _ in 0..size {
                 points.push(rand_sphere_data());
             }
-            for i in 0..points.len() {
-                kdtree.add(&points[i].0, points[i].1).unwrap();
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
             }
 
             b.iter(|| {
@@ -208,6 +160,50 @@ pub fn within_unsorted_large_euclidean2(c: &mut Criterion) {
     }
 }
 
+pub fn within_small_euclidean2_batched(c: &mut Criterion) {
+    let mut group = c.benchmark_group("within(0.01) batched");
+
+    for size in [100, 1_000, 10_000, 100_000, 1_000_000].iter() {
+        //group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
+            let point = rand_sphere_data();
+
+            let mut points = vec![];
+            let mut kdtree = KdTree::with_capacity(16).unwrap();
+            for _ in 0..size {
+                points.push(rand_sphere_data());
+            }
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
+            }
+
+            b.iter(|| black_box(kdtree.within(&point.0, 0.01, &SquaredEuclidean)).unwrap());
+        });
+    }
+}
+
+pub fn within_medium_euclidean2_batched(c: &mut Criterion) {
+    let mut group = c.benchmark_group("within(0.05) batched");
+
+    for size in [100, 1_000, 10_000, 100_000, 1_000_000].iter() {
+        //group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
+            let point = rand_sphere_data();
+
+            let mut points = vec![];
+            let mut kdtree = KdTree::with_capacity(16).unwrap();
+            for _ in 0..size {
+                points.push(rand_sphere_data());
+            }
+            for (point, data) in points.iter() {
+                kdtree.add(point, *data).unwrap();
+            }
+
+            b.iter(|| black_box(kdtree.within(&point.0, 0.05, &SquaredEuclidean)).unwrap());
+        });
+    }
+}
+
 criterion_group!(
     benches,
     within_small_euclidean2,
@@ -215,6 +211,8 @@ criterion_group!(
     within_large_euclidean2,
     within_unsorted_small_euclidean2,
     within_unsorted_medium_euclidean2,
-    within_unsorted_large_euclidean2
+    within_unsorted_large_euclidean2,
+    within_small_euclidean2_batched,
+    within_medium_euclidean2_batched
 );
 criterion_main!(benches);
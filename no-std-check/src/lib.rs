@@ -0,0 +1,65 @@
+//! Builds kiddo without the standard library. This crate is only ever compiled, to prove
+//! that `KdTree` and its queries need nothing more than `core` and `alloc`:
+//!
+//! ```sh
+//! cargo build --manifest-path no-std-check/Cargo.toml --target thumbv7em-none-eabihf
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use kiddo::distance::{squared_euclidean, Manhattan, SquaredEuclidean};
+use kiddo::geo::Haversine;
+use kiddo::{ErrorKind, ImmutableKdTree, KdTree};
+
+pub fn build(points: &[([f32; 3], u32)]) -> Result<KdTree<f32, u32, 3>, ErrorKind> {
+    let mut kdtree = KdTree::with_capacity(16)?;
+    for (point, data) in points {
+        kdtree.add(point, *data)?;
+    }
+    Ok(kdtree)
+}
+
+pub fn nearest(kdtree: &KdTree<f32, u32, 3>, query: &[f32; 3]) -> Result<Vec<u32>, ErrorKind> {
+    Ok(kdtree
+        .nearest(query, 5, &squared_euclidean)?
+        .into_iter()
+        .map(|(_, data)| *data)
+        .collect())
+}
+
+pub fn nearest_one(kdtree: &KdTree<f32, u32, 3>, query: &[f32; 3]) -> Result<u32, ErrorKind> {
+    kdtree
+        .nearest_one(query, &SquaredEuclidean)
+        .map(|(_, data)| *data)
+}
+
+pub fn within(kdtree: &KdTree<f32, u32, 3>, query: &[f32; 3]) -> Result<usize, ErrorKind> {
+    Ok(kdtree.within(query, 0.25, &Manhattan)?.len())
+}
+
+pub fn move_and_remove(
+    kdtree: &mut KdTree<f32, u32, 3>,
+    from: &[f32; 3],
+    to: &[f32; 3],
+    data: &u32,
+) -> Result<usize, ErrorKind> {
+    kdtree.update_position(from, to, data)?;
+    kdtree.remove(to, data)
+}
+
+pub fn freeze(kdtree: KdTree<f32, u32, 3>, query: &[f32; 3]) -> Result<u32, ErrorKind> {
+    let immutable: ImmutableKdTree<f32, u32, 3> = kdtree.into();
+    immutable
+        .nearest_one(query, &SquaredEuclidean)
+        .map(|(_, data)| *data)
+}
+
+pub fn nearest_city(cities: &KdTree<f64, u32, 2>, position: &[f64; 2]) -> Result<u32, ErrorKind> {
+    cities
+        .nearest_one(position, &Haversine)
+        .map(|(_, data)| *data)
+}
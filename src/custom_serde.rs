@@ -1,12 +1,13 @@
 #[cfg(feature = "serialize")]
 pub(crate) mod arrays {
+    use alloc::vec::Vec;
     use core::option::Option::None;
+    use core::{convert::TryInto, marker::PhantomData};
     use serde::{
         de::{SeqAccess, Visitor},
         ser::SerializeTuple,
         Deserialize, Deserializer, Serialize, Serializer,
     };
-    use std::{convert::TryInto, marker::PhantomData};
 
     pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
         data: &[T; N],
@@ -27,8 +28,8 @@ pub(crate) mod arrays {
     {
         type Value = [T; N];
 
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str(&format!("an array of length {}", N))
+        fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+            write!(formatter, "an array of length {}", N)
         }
 
         #[inline]
@@ -61,21 +62,24 @@ pub(crate) mod arrays {
 
 #[cfg(feature = "serialize")]
 pub(crate) mod vec_arrays {
+    use alloc::vec::Vec;
     use core::option::Option::None;
+    use core::{convert::TryInto, marker::PhantomData};
     use serde::ser::SerializeSeq;
     use serde::{
         de::{SeqAccess, Visitor},
         Deserialize, Deserializer, Serialize, Serializer,
     };
-    use std::{convert::TryInto, marker::PhantomData};
 
-    pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
-        data: &[[T; N]],
-        ser: S,
-    ) -> Result<S::Ok, S::Error> {
+    pub fn serialize<S, T, I, const N: usize>(data: I, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+        I: ExactSizeIterator<Item = [T; N]>,
+    {
         let mut s = ser.serialize_seq(Some(data.len() * N))?;
-        for point in data.iter() {
-            for item in point {
+        for point in data {
+            for item in point.iter() {
                 s.serialize_element(item)?;
             }
         }
@@ -91,8 +95,8 @@ pub(crate) mod vec_arrays {
     {
         type Value = Vec<[T; N]>;
 
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            formatter.write_str(&format!("a vector of arrays of length {}", N))
+        fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+            write!(formatter, "a vector of arrays of length {}", N)
         }
 
         #[inline]
@@ -0,0 +1,465 @@
+//! Checks of a tree's internal invariants, and statistics about its shape, for diagnosing
+//! trees that are corrupted or that query slower than expected.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use crate::kiddo::{KdTree, Node};
+use crate::scalar::Scalar;
+
+/// An invariant that `KdTree::validate()` found broken.
+///
+/// Each variant holds the path from the root to the node at fault, as one `L` or `R` per
+/// branch taken, so the root's path is empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A point lies outside the `min_bounds` and `max_bounds` of a node that holds it
+    PointOutOfBounds { path: String },
+    /// A point in the left child of a stem is not below the stem's `split_value`, or one
+    /// in its right child is below it
+    WrongSideOfSplit { path: String },
+    /// A node's `size` differs from the number of elements it holds
+    SizeMismatch {
+        path: String,
+        size: usize,
+        count: usize,
+    },
+    /// A leaf holds a different number of points to items
+    LengthMismatch {
+        path: String,
+        points: usize,
+        items: usize,
+    },
+    /// A leaf's columns are not `stride` coordinates long each, or are shorter than the
+    /// number of points they hold
+    MalformedColumns {
+        path: String,
+        points: usize,
+        stride: usize,
+        coords: usize,
+    },
+}
+
+/// Statistics about the shape of a tree, returned by `KdTree::stats()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeStats {
+    /// The number of elements in the tree
+    pub size: usize,
+    /// The number of stems, which each have two children
+    pub stems: usize,
+    /// The number of leaves, including `Duplicates` nodes
+    pub leaves: usize,
+    /// The number of `Duplicates` nodes, whose elements all share one point
+    pub duplicates: usize,
+    /// The number of leaves at each depth, where the root is at depth zero. The last entry
+    /// is the depth of the deepest leaf.
+    pub depth_histogram: Vec<usize>,
+    /// The smallest number of elements in a leaf, as a fraction of the leaf capacity
+    pub min_fill_ratio: f64,
+    /// The mean number of elements in a leaf, as a fraction of the leaf capacity
+    pub mean_fill_ratio: f64,
+    /// The largest number of elements in a leaf, as a fraction of the leaf capacity. This
+    /// is over one if a leaf is over capacity, because its points could not be split.
+    pub max_fill_ratio: f64,
+    /// The number of leaves over capacity
+    pub oversized_leaves: usize,
+    /// The number of bytes used by the tree's nodes, points and items, not counting
+    /// anything that the items themselves allocate
+    pub memory: usize,
+}
+
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        fn path(path: &str) -> &str {
+            if path.is_empty() {
+                "root"
+            } else {
+                path
+            }
+        }
+
+        match self {
+            ValidationError::PointOutOfBounds { path: p } => {
+                write!(f, "node {} holds a point outside its bounds", path(p))
+            }
+            ValidationError::WrongSideOfSplit { path: p } => {
+                write!(
+                    f,
+                    "stem {} holds a point on the wrong side of its split",
+                    path(p)
+                )
+            }
+            ValidationError::SizeMismatch {
+                path: p,
+                size,
+                count,
+            } => write!(
+                f,
+                "node {} has size {} but holds {} elements",
+                path(p),
+                size,
+                count
+            ),
+            ValidationError::LengthMismatch {
+                path: p,
+                points,
+                items,
+            } => write!(
+                f,
+                "leaf {} holds {} points but {} items",
+                path(p),
+                points,
+                items
+            ),
+            ValidationError::MalformedColumns {
+                path: p,
+                points,
+                stride,
+                coords,
+            } => write!(
+                f,
+                "leaf {} holds {} points in columns {} apart, but has {} coordinates",
+                path(p),
+                points,
+                stride,
+                coords
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// A node on the path from the root to the node being validated, and whether the path
+/// continues into its left child
+type Ancestor<'a, A, T, const K: usize> = (&'a KdTree<A, T, K>, bool);
+
+impl<A: Scalar, T: core::cmp::PartialEq, const K: usize> KdTree<A, T, K> {
+    /// Walks the whole tree checking its invariants: that every point lies within the bounds
+    /// of each node that holds it, that the points in the left child of each stem are below
+    /// its split value and those in the right child are not, that each node's size matches
+    /// the number of elements it holds, and that each leaf holds as many points as items,
+    /// laid out in one full column per axis.
+    ///
+    /// A tree that is only changed through its public methods is always valid, so an error
+    /// means the tree has been corrupted. The walk visits every point once for each of its
+    /// ancestors, so it is intended for tests and debugging rather than routine use.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    /// tree.remove(&[2.0, 3.0, 6.0], &101)?;
+    ///
+    /// assert_eq!(tree.validate(), Ok(()));
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_node(&mut Vec::new())
+    }
+
+    fn validate_node<'a>(
+        &'a self,
+        ancestors: &mut Vec<Ancestor<'a, A, T, K>>,
+    ) -> Result<(), ValidationError> {
+        match &self.content {
+            Node::Leaf { points, bucket, .. } => {
+                if points.len() != bucket.len() {
+                    return Err(ValidationError::LengthMismatch {
+                        path: path_of(ancestors),
+                        points: points.len(),
+                        items: bucket.len(),
+                    });
+                }
+                if points.len > points.stride || points.coords.len() != points.stride * K {
+                    return Err(ValidationError::MalformedColumns {
+                        path: path_of(ancestors),
+                        points: points.len,
+                        stride: points.stride,
+                        coords: points.coords.len(),
+                    });
+                }
+                self.validate_size(bucket.len(), ancestors)?;
+                points
+                    .iter()
+                    .try_for_each(|point| self.validate_point(&point, ancestors))
+            }
+            Node::Duplicates { point, bucket, .. } => {
+                self.validate_size(bucket.len(), ancestors)?;
+                match bucket.is_empty() {
+                    true => Ok(()),
+                    false => self.validate_point(point, ancestors),
+                }
+            }
+            Node::Stem { left, right, .. } => {
+                self.validate_size(left.size + right.size, ancestors)?;
+                for (child, is_left) in [(left, true), (right, false)] {
+                    ancestors.push((self, is_left));
+                    let valid = child.validate_node(ancestors);
+                    ancestors.pop();
+                    valid?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn validate_size(
+        &self,
+        count: usize,
+        ancestors: &[Ancestor<A, T, K>],
+    ) -> Result<(), ValidationError> {
+        if self.size == count {
+            Ok(())
+        } else {
+            Err(ValidationError::SizeMismatch {
+                path: path_of(ancestors),
+                size: self.size,
+                count,
+            })
+        }
+    }
+
+    /// Checks that a point held by this node lies within its bounds and those of each of
+    /// its ancestors, and on the right side of each ancestor's split
+    fn validate_point(
+        &self,
+        point: &[A; K],
+        ancestors: &[Ancestor<A, T, K>],
+    ) -> Result<(), ValidationError> {
+        let within = |node: &KdTree<A, T, K>| {
+            (0..K)
+                .all(|dim| point[dim] >= node.min_bounds[dim] && point[dim] <= node.max_bounds[dim])
+        };
+
+        for (depth, (node, is_left)) in ancestors.iter().enumerate() {
+            if !within(node) {
+                return Err(ValidationError::PointOutOfBounds {
+                    path: path_of(&ancestors[..depth]),
+                });
+            }
+            if let Node::Stem {
+                split_value,
+                split_dimension,
+                ..
+            } = &node.content
+            {
+                if (point[*split_dimension as usize] < *split_value) != *is_left {
+                    return Err(ValidationError::WrongSideOfSplit {
+                        path: path_of(&ancestors[..depth]),
+                    });
+                }
+            }
+        }
+
+        if within(self) {
+            Ok(())
+        } else {
+            Err(ValidationError::PointOutOfBounds {
+                path: path_of(ancestors),
+            })
+        }
+    }
+
+    /// Returns statistics about the shape of the tree: how deep its leaves are, how full
+    /// they are, and how much memory it uses. See `TreeStats`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let points: Vec<([f64; 2], usize)> =
+    ///     (0..100).map(|i| ([i as f64, (i % 10) as f64], i)).collect();
+    /// let tree = KdTree::from_points_with_capacity(points, 10)?;
+    /// let stats = tree.stats();
+    ///
+    /// assert_eq!(stats.size, 100);
+    /// assert_eq!(stats.leaves, stats.stems + 1);
+    /// assert!(stats.max_fill_ratio <= 1.0);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            size: self.size,
+            stems: 0,
+            leaves: 0,
+            duplicates: 0,
+            depth_histogram: vec![],
+            min_fill_ratio: f64::INFINITY,
+            mean_fill_ratio: 0.0,
+            max_fill_ratio: 0.0,
+            oversized_leaves: 0,
+            memory: size_of::<Self>(),
+        };
+        self.collect_stats(0, &mut stats);
+        stats.mean_fill_ratio /= stats.leaves as f64;
+        stats
+    }
+
+    fn collect_stats(&self, depth: usize, stats: &mut TreeStats) {
+        let (len, capacity) = match &self.content {
+            Node::Leaf {
+                points,
+                bucket,
+                capacity,
+                ..
+            } => {
+                stats.memory += points.allocated_bytes() + bucket.capacity() * size_of::<T>();
+                (bucket.len(), *capacity)
+            }
+            Node::Duplicates {
+                bucket, capacity, ..
+            } => {
+                stats.duplicates += 1;
+                stats.memory += bucket.capacity() * size_of::<T>();
+                (bucket.len(), *capacity)
+            }
+            Node::Stem { left, right, .. } => {
+                stats.stems += 1;
+                stats.memory += 2 * size_of::<Self>();
+                left.collect_stats(depth + 1, stats);
+                right.collect_stats(depth + 1, stats);
+                return;
+            }
+        };
+
+        stats.leaves += 1;
+        if stats.depth_histogram.len() <= depth {
+            stats.depth_histogram.resize(depth + 1, 0);
+        }
+        stats.depth_histogram[depth] += 1;
+
+        let fill_ratio = len as f64 / capacity as f64;
+        stats.min_fill_ratio = stats.min_fill_ratio.min(fill_ratio);
+        stats.max_fill_ratio = stats.max_fill_ratio.max(fill_ratio);
+        stats.mean_fill_ratio += fill_ratio;
+        if len > capacity {
+            stats.oversized_leaves += 1;
+        }
+    }
+}
+
+fn path_of<A, T: core::cmp::PartialEq, const K: usize>(ancestors: &[Ancestor<A, T, K>]) -> String {
+    ancestors
+        .iter()
+        .map(|(_, is_left)| if *is_left { 'L' } else { 'R' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationError;
+    use crate::kiddo::{KdTree, Node};
+
+    fn tree() -> KdTree<f64, usize, 2> {
+        let points = (0..40).map(|i| ([i as f64, (i % 7) as f64], i)).collect();
+        KdTree::from_points_with_capacity(points, 4).unwrap()
+    }
+
+    #[test]
+    fn it_finds_a_point_outside_its_bounds() {
+        let mut tree = tree();
+        tree.min_bounds[0] = 1.0;
+
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::PointOutOfBounds { path: "".into() })
+        );
+    }
+
+    #[test]
+    fn it_finds_a_point_on_the_wrong_side_of_a_split() {
+        let mut tree = tree();
+        if let Node::Stem {
+            ref mut split_value,
+            split_dimension,
+            ..
+        } = tree.content
+        {
+            assert_eq!(split_dimension, 0);
+            *split_value -= 1.0;
+        }
+
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::WrongSideOfSplit { path: "".into() })
+        );
+    }
+
+    #[test]
+    fn it_finds_a_size_that_does_not_match_the_elements() {
+        let mut tree = tree();
+        if let Node::Stem { ref mut right, .. } = tree.content {
+            right.size += 1;
+        }
+
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::SizeMismatch {
+                path: "".into(),
+                size: 40,
+                count: 41
+            })
+        );
+    }
+
+    #[test]
+    fn it_finds_a_leaf_with_more_items_than_points() {
+        let mut tree: KdTree<f64, usize, 2> = KdTree::with_capacity(4).unwrap();
+        tree.add(&[1.0, 2.0], 1).unwrap();
+        if let Node::Leaf { ref mut bucket, .. } = tree.content {
+            bucket.push(2);
+        }
+
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::LengthMismatch {
+                path: "".into(),
+                points: 1,
+                items: 2
+            })
+        );
+    }
+
+    #[test]
+    fn it_finds_a_leaf_with_a_short_column() {
+        let mut tree: KdTree<f64, usize, 2> = KdTree::with_capacity(4).unwrap();
+        tree.add(&[1.0, 2.0], 1).unwrap();
+        if let Node::Leaf { ref mut points, .. } = tree.content {
+            points.coords.pop();
+        }
+
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::MalformedColumns {
+                path: "".into(),
+                points: 1,
+                stride: 4,
+                coords: 7
+            })
+        );
+    }
+
+    #[test]
+    fn it_counts_the_nodes_at_each_depth() {
+        let tree = tree();
+        let stats = tree.stats();
+
+        assert_eq!(stats.size, 40);
+        assert_eq!(stats.leaves, 16);
+        assert_eq!(stats.stems, 15);
+        assert_eq!(stats.depth_histogram, vec![0, 0, 0, 0, 16]);
+        assert_eq!(stats.oversized_leaves, 0);
+        assert_eq!(stats.mean_fill_ratio, 40.0 / 64.0);
+        assert!(stats.memory > 40 * (16 + 8));
+    }
+}
@@ -2,9 +2,17 @@
 //! euclidean distance which is no more than the square root of the sum of the
 //! squares of the distances in each dimension.
 
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
 use num_traits::Float;
-use std::arch::x86_64::*;
 
+use crate::scalar::Scalar;
+use crate::simd;
+#[cfg(target_arch = "x86_64")]
+use crate::simd::x86_feature_detected;
+use crate::util;
+
+#[cfg(target_arch = "x86_64")]
 union SimdToArray {
     array: [f32; 4],
     simd: __m128,
@@ -15,6 +23,11 @@ union SimdToArray {
 /// the points, this metric is beneficial because it avoids the expensive square
 /// root computation.
 ///
+/// A query given this function measures each point in a leaf with a separate call.
+/// Only the `SquaredEuclidean` metric measures the points of a leaf several at a time,
+/// with the SIMD kernels, so prefer it for queries on `f32` and `f64` points. Both give
+/// exactly the same distances.
+///
 /// # Examples
 ///
 /// ```rust
@@ -28,17 +41,580 @@ pub fn squared_euclidean<T: Float, const K: usize>(a: &[T; K], b: &[T; K]) -> T
     a.iter()
         .zip(b.iter())
         .map(|(x, y)| ((*x) - (*y)) * ((*x) - (*y)))
-        .fold(T::zero(), ::std::ops::Add::add)
+        .fold(T::zero(), ::core::ops::Add::add)
+}
+
+/// Returns the euclidean distance between two points. Prefer `squared_euclidean` when
+/// only comparing distances, as it avoids the square root.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::distance::euclidean;
+///
+/// assert!(0.0 == euclidean(&[0.0, 0.0], &[0.0, 0.0]));
+/// assert!(5.0 == euclidean(&[0.0, 0.0], &[3.0, 4.0]));
+/// assert!(1.0 == euclidean(&[0.0, 0.0], &[1.0, 0.0]));
+/// ```
+pub fn euclidean<T: Float, const K: usize>(a: &[T; K], b: &[T; K]) -> T {
+    squared_euclidean(a, b).sqrt()
+}
+
+/// Returns the manhattan, or L1, distance between two points: the sum of the absolute
+/// differences along each axis.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::distance::manhattan;
+///
+/// assert!(0.0 == manhattan(&[0.0, 0.0], &[0.0, 0.0]));
+/// assert!(7.0 == manhattan(&[0.0, 0.0], &[3.0, -4.0]));
+/// assert!(1.0 == manhattan(&[0.0, 0.0], &[1.0, 0.0]));
+/// ```
+pub fn manhattan<T: Float, const K: usize>(a: &[T; K], b: &[T; K]) -> T {
+    a.iter()
+        .zip(b.iter())
+        .fold(T::zero(), |acc, (x, y)| acc + (*x - *y).abs())
+}
+
+/// Returns the chebyshev, or L∞, distance between two points: the largest absolute
+/// difference along any axis.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::distance::chebyshev;
+///
+/// assert!(0.0 == chebyshev(&[0.0, 0.0], &[0.0, 0.0]));
+/// assert!(4.0 == chebyshev(&[0.0, 0.0], &[3.0, -4.0]));
+/// assert!(1.0 == chebyshev(&[0.0, 0.0], &[1.0, 0.0]));
+/// ```
+pub fn chebyshev<T: Float, const K: usize>(a: &[T; K], b: &[T; K]) -> T {
+    a.iter()
+        .zip(b.iter())
+        .fold(T::zero(), |acc, (x, y)| acc.max((*x - *y).abs()))
+}
+
+/// Returns the minkowski distance of order `P` between two points: the `P`th root of the
+/// sum of the absolute differences along each axis, each raised to the power `P`.
+/// `minkowski::<1, _, _>` is the same as `manhattan`, and `minkowski::<2, _, _>` is the
+/// same as `euclidean`. When only comparing distances, `Minkowski<P>` avoids the root.
+///
+/// `P` must be at least 1, and using `minkowski::<0, _, _>` is a compile error. Only
+/// integer orders are supported; for a fractional order, such as 0.5, pass a closure that
+/// computes it to a query instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::distance::minkowski;
+///
+/// assert!(0.0 == minkowski::<3, _, _>(&[0.0, 0.0], &[0.0, 0.0]));
+/// assert!(2.0 == minkowski::<3, _, _>(&[0.0, 0.0], &[2.0, 0.0]));
+/// assert!(5.0 == minkowski::<2, _, _>(&[0.0, 0.0], &[3.0, 4.0]));
+/// ```
+pub fn minkowski<const P: u32, T: Float + Scalar, const K: usize>(a: &[T; K], b: &[T; K]) -> T {
+    let () = Minkowski::<P>::ORDER_IS_POSITIVE;
+    Minkowski::<P>.dist(a, b).powf(T::from(P).unwrap().recip())
+}
+
+/// A distance metric that trees can be queried with.
+///
+/// As well as the distance between two points, a metric provides lower bounds on the
+/// distance to any point in a region of space, which queries use to skip subtrees that
+/// cannot contain a result. Any `Fn(&[A; K], &[A; K]) -> D` is also a metric, so plain
+/// functions such as `squared_euclidean` and closures can be used in queries directly.
+///
+/// Distances are of type `D`, which is the coordinate type `A` unless a metric measures
+/// with a wider type, as `SquaredEuclidean`, `Manhattan` and `Chebyshev` do for integer
+/// coordinates.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+/// use kiddo::distance::{squared_euclidean, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+///
+/// tree.add(&[1.0, 2.0, 5.0], 100)?;
+/// tree.add(&[2.0, 3.0, 6.0], 101)?;
+///
+/// let by_struct = tree.nearest_one(&[1.0, 2.0, 5.1], &SquaredEuclidean)?;
+/// let by_fn = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+///
+/// assert_eq!(by_struct, by_fn);
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+pub trait Distance<A: Scalar, const K: usize, D: Scalar = A> {
+    /// Returns the distance between `a` and `b`
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> D;
+
+    /// Returns the distance from `p` to the nearest point in the axis-aligned box with
+    /// corners `min` and `max`. This must never be greater than the distance from `p` to
+    /// any point inside the box.
+    ///
+    /// By default, `p` is clamped to the box and the distance to the clamped point is
+    /// returned, which is correct for any metric that does not decrease as the difference
+    /// along an axis grows.
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> D {
+        util::distance_to_space(p, min, max, &|a, b| self.dist(a, b))
+    }
+
+    /// Returns the distance from `p` to the furthest point in the axis-aligned box with
+    /// corners `min` and `max`. This must never be less than the distance from `p` to any
+    /// point inside the box. Queries use this to take whole subtrees without visiting
+    /// each point.
+    ///
+    /// By default, the distance to the corner of the box furthest from `p` is returned,
+    /// which is correct under the same conditions as `dist_to_box()`.
+    fn max_dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> D {
+        util::distance_to_furthest_corner(p, min, max, self)
+    }
+
+    /// Returns a lower bound on the distance between two points whose coordinates differ
+    /// by `axis_delta` along a single axis. Queries use this to skip the far side of a
+    /// split without calling `dist_to_box()`. `axis_delta` is never negative.
+    fn dist1(&self, axis_delta: A) -> D;
+
+    /// Whether queries should measure the points in a leaf with `dist_batch()` rather than
+    /// calling `dist()` for each of them. Metrics with a batch kernel opt in by setting
+    /// this to `true`.
+    const BATCHED: bool = false;
+
+    /// Computes the distance from `p` to each of a batch of points into `out`, where
+    /// `columns[dim][i]` is coordinate `dim` of point `i`. Every column is at least as
+    /// long as `out`, which holds one distance per point.
+    ///
+    /// By default, each point is gathered from the columns and `dist()` is called for it.
+    /// Metrics with a SIMD kernel override this to measure several points at a time.
+    fn dist_batch(&self, p: &[A; K], columns: &[&[A]; K], out: &mut [D]) {
+        for (idx, dist) in out.iter_mut().enumerate() {
+            let mut point = [A::zero(); K];
+            for (dim, x) in point.iter_mut().enumerate() {
+                *x = columns[dim][idx];
+            }
+            *dist = self.dist(p, &point);
+        }
+    }
+}
+
+/// Any function with the signature of `dist()` can be used as a metric. Queries call it for
+/// one point at a time, as they cannot know of a batch kernel for it.
+impl<A: Scalar, D: Scalar, F, const K: usize> Distance<A, K, D> for F
+where
+    F: Fn(&[A; K], &[A; K]) -> D,
+{
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> D {
+        self(a, b)
+    }
+
+    fn dist1(&self, _axis_delta: A) -> D {
+        // nothing is known about an arbitrary function along a single axis
+        D::zero()
+    }
+}
+
+/// The squared euclidean distance. See `squared_euclidean()`. Queries measure the points
+/// of each leaf several at a time with this metric, which is faster than passing the
+/// function.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SquaredEuclidean;
+
+impl<A: Float + Scalar + 'static, const K: usize> Distance<A, K> for SquaredEuclidean {
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        squared_euclidean(a, b)
+    }
+
+    const BATCHED: bool = true;
+
+    fn dist_batch(&self, p: &[A; K], columns: &[&[A]; K], out: &mut [A]) {
+        simd::squared_euclidean(p, columns, out)
+    }
+
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        (0..K)
+            .map(|dim| axis_distance_to_box(p[dim], min[dim], max[dim]))
+            .fold(A::zero(), |acc, d| acc + d * d)
+    }
+
+    fn dist1(&self, axis_delta: A) -> A {
+        axis_delta * axis_delta
+    }
+}
+
+/// The manhattan, or L1, distance. See `manhattan()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Manhattan;
+
+impl<A: Float + Scalar, const K: usize> Distance<A, K> for Manhattan {
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        manhattan(a, b)
+    }
+
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        (0..K)
+            .map(|dim| axis_distance_to_box(p[dim], min[dim], max[dim]))
+            .fold(A::zero(), |acc, d| acc + d)
+    }
+
+    fn dist1(&self, axis_delta: A) -> A {
+        axis_delta.abs()
+    }
 }
 
+/// The chebyshev, or L∞, distance. See `chebyshev()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Chebyshev;
+
+impl<A: Float + Scalar, const K: usize> Distance<A, K> for Chebyshev {
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        chebyshev(a, b)
+    }
+
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        (0..K)
+            .map(|dim| axis_distance_to_box(p[dim], min[dim], max[dim]))
+            .fold(A::zero(), A::max)
+    }
+
+    fn dist1(&self, axis_delta: A) -> A {
+        axis_delta.abs()
+    }
+}
+
+/// Implements `SquaredEuclidean`, `Manhattan` and `Chebyshev` for integer coordinates,
+/// measured with an unsigned distance type wide enough that the difference along a single
+/// axis, squared, always fits. Sums over several axes saturate rather than overflow.
+macro_rules! integer_metrics {
+    ($($int:ty => $dist:ty),*) => {$(
+        impl<const K: usize> Distance<$int, K, $dist> for SquaredEuclidean {
+            fn dist(&self, a: &[$int; K], b: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| {
+                    let d = a[dim].abs_diff(b[dim]) as $dist;
+                    acc.saturating_add(d * d)
+                })
+            }
+
+            fn dist_to_box(&self, p: &[$int; K], min: &[$int; K], max: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| {
+                    let d = integer_distance_to_box!(p[dim], min[dim], max[dim]) as $dist;
+                    acc.saturating_add(d * d)
+                })
+            }
+
+            fn dist1(&self, axis_delta: $int) -> $dist {
+                let d = axis_delta as $dist;
+                d * d
+            }
+        }
+
+        impl<const K: usize> Distance<$int, K, $dist> for Manhattan {
+            fn dist(&self, a: &[$int; K], b: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| {
+                    acc.saturating_add(a[dim].abs_diff(b[dim]) as $dist)
+                })
+            }
+
+            fn dist_to_box(&self, p: &[$int; K], min: &[$int; K], max: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| {
+                    acc.saturating_add(integer_distance_to_box!(p[dim], min[dim], max[dim]) as $dist)
+                })
+            }
+
+            fn dist1(&self, axis_delta: $int) -> $dist {
+                axis_delta as $dist
+            }
+        }
+
+        impl<const K: usize> Distance<$int, K, $dist> for Chebyshev {
+            fn dist(&self, a: &[$int; K], b: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| acc.max(a[dim].abs_diff(b[dim]) as $dist))
+            }
+
+            fn dist_to_box(&self, p: &[$int; K], min: &[$int; K], max: &[$int; K]) -> $dist {
+                (0..K).fold(0, |acc: $dist, dim| {
+                    acc.max(integer_distance_to_box!(p[dim], min[dim], max[dim]) as $dist)
+                })
+            }
+
+            fn dist1(&self, axis_delta: $int) -> $dist {
+                axis_delta as $dist
+            }
+        }
+    )*};
+}
+
+/// Returns how far the integer `p` lies outside of the range `min..=max`, as an unsigned
+/// integer of the same width, so that it cannot overflow
+macro_rules! integer_distance_to_box {
+    ($p:expr, $min:expr, $max:expr) => {
+        if $p < $min {
+            $min.abs_diff($p)
+        } else if $p > $max {
+            $p.abs_diff($max)
+        } else {
+            0
+        }
+    };
+}
+
+integer_metrics!(i8 => u64, i16 => u64, i32 => u64, u8 => u64, u16 => u64, u32 => u64);
+integer_metrics!(i64 => u128, u64 => u128);
+
+/// The minkowski distance of order `P`, raised to the power `P`: the sum of the absolute
+/// differences along each axis, each raised to the power `P`. As with `SquaredEuclidean`,
+/// which is the same as `Minkowski<2>`, the final root is not taken, as it does not
+/// change the order of results.
+///
+/// `P` must be at least 1, as every pair of distinct points would be the same distance
+/// apart under `Minkowski<0>`, and measuring with it is a compile error. Only integer
+/// orders are supported.
+///
+/// ```compile_fail
+/// use kiddo::distance::{Distance, Minkowski};
+///
+/// let dist = Minkowski::<0>.dist(&[0.0, 0.0], &[1.0, 2.0]);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Minkowski<const P: u32>;
+
+impl<const P: u32> Minkowski<P> {
+    const ORDER_IS_POSITIVE: () = assert!(
+        P > 0,
+        "a minkowski distance must have an order of at least 1"
+    );
+}
+
+impl<A: Float + Scalar, const K: usize, const P: u32> Distance<A, K> for Minkowski<P> {
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        let () = Self::ORDER_IS_POSITIVE;
+        a.iter().zip(b.iter()).fold(A::zero(), |acc, (x, y)| {
+            acc + (*x - *y).abs().powi(P as i32)
+        })
+    }
+
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        let () = Self::ORDER_IS_POSITIVE;
+        (0..K)
+            .map(|dim| axis_distance_to_box(p[dim], min[dim], max[dim]))
+            .fold(A::zero(), |acc, d| acc + d.powi(P as i32))
+    }
+
+    fn dist1(&self, axis_delta: A) -> A {
+        let () = Self::ORDER_IS_POSITIVE;
+        axis_delta.abs().powi(P as i32)
+    }
+}
+
+/// Wraps another metric so that space is periodic along each axis, as in a simulation
+/// with periodic boundary conditions: a point just below `periods[i]` along axis `i`
+/// neighbours one just above zero. Use an infinite period for an axis that does not wrap.
+///
+/// The wrapped metric is given the nearest image of each point, so it must depend only on
+/// the absolute difference along each axis, and must not decrease as that difference grows.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+/// use kiddo::distance::{Periodic, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, usize, 2> = KdTree::new();
+///
+/// tree.add(&[0.5, 5.0], 100)?;
+/// tree.add(&[9.0, 5.0], 101)?;
+///
+/// let periodic = Periodic::new(SquaredEuclidean, [10.0, 10.0]);
+/// let nearest = tree.nearest_one(&[9.9, 5.0], &periodic)?;
+///
+/// assert_eq!(*nearest.1, 100);
+/// assert!((nearest.0 - 0.36).abs() < 1e-9);
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Periodic<D, A, const K: usize> {
+    metric: D,
+    periods: [A; K],
+}
+
+impl<D: Distance<A, K>, A: Float + Scalar, const K: usize> Periodic<D, A, K> {
+    /// Creates a metric that measures distances with `metric`, wrapping around along each
+    /// axis `i` every `periods[i]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a period is not greater than zero.
+    pub fn new(metric: D, periods: [A; K]) -> Self {
+        assert!(
+            periods.iter().all(|&period| period > A::zero()),
+            "periods must be greater than zero"
+        );
+        Periodic { metric, periods }
+    }
+
+    /// Returns the image of `p` offset by the distance returned by `axis_distance` along
+    /// each axis, and measures the distance to it
+    fn dist_to_image<F>(&self, p: &[A; K], mut axis_distance: F) -> A
+    where
+        F: FnMut(usize) -> A,
+    {
+        let mut image = *p;
+        for (dim, coord) in image.iter_mut().enumerate() {
+            *coord = *coord + axis_distance(dim);
+        }
+        self.metric.dist(p, &image)
+    }
+}
+
+impl<D: Distance<A, K>, A: Float + Scalar, const K: usize> Distance<A, K> for Periodic<D, A, K> {
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        self.dist_to_image(a, |dim| {
+            wrapped_distance(b[dim] - a[dim], self.periods[dim])
+        })
+    }
+
+    fn dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        self.dist_to_image(p, |dim| {
+            let period = self.periods[dim];
+            if max[dim] - min[dim] >= period
+                || modulo(p[dim] - min[dim], period) <= max[dim] - min[dim]
+            {
+                A::zero()
+            } else {
+                modulo(min[dim] - p[dim], period).min(modulo(p[dim] - max[dim], period))
+            }
+        })
+    }
+
+    fn max_dist_to_box(&self, p: &[A; K], min: &[A; K], max: &[A; K]) -> A {
+        self.dist_to_image(p, |dim| {
+            let period = self.periods[dim];
+            let half_period = period / (A::one() + A::one());
+            if max[dim] - min[dim] >= period
+                || modulo(p[dim] + half_period - min[dim], period) <= max[dim] - min[dim]
+            {
+                half_period
+            } else {
+                wrapped_distance(min[dim] - p[dim], period)
+                    .max(wrapped_distance(max[dim] - p[dim], period))
+            }
+        })
+    }
+
+    fn dist1(&self, _axis_delta: A) -> A {
+        // the far side of a split can be reached by wrapping around the other way
+        A::zero()
+    }
+}
+
+/// Returns `x` modulo `period`, in the range `0..period`
+pub(crate) fn modulo<A: Float>(x: A, period: A) -> A {
+    let m = x % period;
+    if m < A::zero() {
+        m + period
+    } else {
+        m
+    }
+}
+
+/// Returns the absolute difference `delta` along an axis that wraps around every `period`
+fn wrapped_distance<A: Float>(delta: A, period: A) -> A {
+    let d = delta.abs() % period;
+    d.min(period - d)
+}
+
+/// Returns how far `p` lies outside of the range `min..=max`
+fn axis_distance_to_box<A: Float>(p: A, min: A, max: A) -> A {
+    if p < min {
+        min - p
+    } else if p > max {
+        p - max
+    } else {
+        A::zero()
+    }
+}
+
+/// Returns the dot product, or inner product, of two vectors.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::distance::dot_product;
+///
+/// assert!(0.0 == dot_product(&[1.0, 0.0], &[0.0, 1.0]));
+/// assert!(11.0 == dot_product(&[1.0, 2.0], &[3.0, 4.0]));
+/// ```
 pub fn dot_product<const K: usize>(a: &[f32; K], b: &[f32; K]) -> f32 {
     a.iter()
         .zip(b.iter())
-        .map(|(x, y)| ((*x) * (*y)))
-        .fold(0f32, ::std::ops::Sub::sub)
+        .map(|(x, y)| (*x) * (*y))
+        .fold(0f32, ::core::ops::Add::add)
 }
 
-#[cfg(any(target_arch = "x86_64"))]
+/// The inner product, negated so that the most similar vectors are the nearest: a query
+/// with this metric is a maximum inner product search. For unit vectors, the inner
+/// product is the cosine similarity. See `KdTree::most_similar()`.
+///
+/// The bounds used to prune subtrees hold for any vectors, not just unit vectors.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+/// use kiddo::distance::InnerProduct;
+///
+/// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+///
+/// tree.add(&[1.0, 0.0], 100)?;
+/// tree.add(&[0.6, 0.8], 101)?;
+///
+/// let nearest = tree.nearest_one(&[0.0, 1.0], &InnerProduct)?;
+///
+/// assert_eq!(nearest, (-0.8, &101));
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InnerProduct;
+
+impl<const K: usize> Distance<f32, K> for InnerProduct {
+    fn dist(&self, a: &[f32; K], b: &[f32; K]) -> f32 {
+        -dot_product(a, b)
+    }
+
+    fn dist_to_box(&self, p: &[f32; K], min: &[f32; K], max: &[f32; K]) -> f32 {
+        // each axis contributes the most it can anywhere in the box
+        -(0..K)
+            .map(|dim| (p[dim] * min[dim]).max(p[dim] * max[dim]))
+            .sum::<f32>()
+    }
+
+    fn max_dist_to_box(&self, p: &[f32; K], min: &[f32; K], max: &[f32; K]) -> f32 {
+        -(0..K)
+            .map(|dim| (p[dim] * min[dim]).min(p[dim] * max[dim]))
+            .sum::<f32>()
+    }
+
+    fn dist1(&self, _axis_delta: f32) -> f32 {
+        // the inner product does not depend on differences between coordinates
+        f32::NEG_INFINITY
+    }
+
+    const BATCHED: bool = true;
+
+    fn dist_batch(&self, p: &[f32; K], columns: &[&[f32]; K], out: &mut [f32]) {
+        simd::dot(p, columns, out);
+        for dist in out.iter_mut() {
+            *dist = -*dist;
+        }
+    }
+}
+
+/// # Safety
+///
+/// `a` and `b` must each point to four readable `f32`s, and the CPU must support SSE4.1.
+#[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "sse4.1")]
 pub unsafe fn dot_sse(a: *const f32, b: *const f32) -> f32 {
     let a_mm = _mm_loadu_ps(a);
@@ -50,7 +626,11 @@ pub unsafe fn dot_sse(a: *const f32, b: *const f32) -> f32 {
     res.array[0]
 }
 
-#[cfg(any(target_arch = "x86_64"))]
+/// # Safety
+///
+/// `a` and `b` must each point to four readable `f32`s aligned to 16 bytes, and the CPU
+/// must support SSE4.1.
+#[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "sse4.1")]
 pub unsafe fn dot_sse_aligned(a: *const f32, b: *const f32) -> f32 {
     let a_mm = _mm_load_ps(a);
@@ -62,28 +642,44 @@ pub unsafe fn dot_sse_aligned(a: *const f32, b: *const f32) -> f32 {
     res.array[0]
 }
 
+#[cfg(target_arch = "x86_64")]
 pub fn dot_product_sse<const K: usize>(a: &[f32; K], b: &[f32; K]) -> f32 {
     if K == 3 {
-        dot_product_sse_3(&a[0..3], &a[0..3])
+        dot_product_sse_3(&a[0..3], &b[0..3])
     } else if K == 4 {
-        dot_product_sse_4(&a[0..4], &a[0..4])
+        dot_product_sse_4(&a[0..4], &b[0..4])
     } else {
         dot_product(a, b)
     }
 }
 
+#[cfg(target_arch = "x86_64")]
 pub fn dot_product_sse_3(a: &[f32], b: &[f32]) -> f32 {
-    let ap = [a[0], a[1], a[2], 0f32].as_ptr();
-    let bp = [b[0], b[1], b[2], 0f32].as_ptr();
-    unsafe { dot_sse(ap, bp) }
+    let a = [a[0], a[1], a[2], 0f32];
+    let b = [b[0], b[1], b[2], 0f32];
+    dot_product_sse_4(&a, &b)
 }
 
+#[cfg(target_arch = "x86_64")]
 pub fn dot_product_sse_4(a: &[f32], b: &[f32]) -> f32 {
-    unsafe { dot_sse(a.as_ptr(), b.as_ptr()) }
+    let (a, b) = (&a[..4], &b[..4]);
+    if x86_feature_detected!("sse4.1") {
+        unsafe { dot_sse(a.as_ptr(), b.as_ptr()) }
+    } else {
+        a.iter().zip(b).fold(0f32, |acc, (x, y)| acc + x * y)
+    }
 }
 
+#[cfg(target_arch = "x86_64")]
 pub fn dot_product_sse_aligned(a: &[f32; 4], b: &[f32; 4]) -> f32 {
     let ap = a.as_ptr();
     let bp = b.as_ptr();
-    unsafe { dot_sse_aligned(ap, bp) }
+    if (ap as usize).is_multiple_of(16)
+        && (bp as usize).is_multiple_of(16)
+        && x86_feature_detected!("sse4.1")
+    {
+        unsafe { dot_sse_aligned(ap, bp) }
+    } else {
+        dot_product_sse_4(a, b)
+    }
 }
@@ -0,0 +1,167 @@
+//! Great-circle distances for trees of `[latitude, longitude]` points, in degrees.
+//!
+//! Trees are built on the raw coordinates, so the longitude axis is not wrapped around the
+//! antimeridian when splitting. Instead, `Haversine` wraps longitudes when measuring the
+//! distance to a node's bounds, so that queries near the dateline still find points on the
+//! other side of it.
+
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::distance::{modulo, Distance};
+use crate::kiddo::{ErrorKind, KdTree};
+
+/// The mean radius of the Earth, in kilometres
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// Returns the great-circle distance between two `[latitude, longitude]` points, in
+/// degrees, on a sphere the size of the Earth, in kilometres.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::geo::haversine;
+///
+/// let london = [51.5074, -0.1278];
+/// let paris = [48.8566, 2.3522];
+///
+/// assert!((haversine(&london, &paris) - 343.5).abs() < 0.5);
+/// assert!((haversine(&[0.0, 179.5], &[0.0, -179.5]) - 111.2).abs() < 0.1);
+/// ```
+pub fn haversine(a: &[f64; 2], b: &[f64; 2]) -> f64 {
+    let (lat_a, lat_b) = (a[0].to_radians(), b[0].to_radians());
+    let half_d_lat = (lat_b - lat_a) / 2.0;
+    let half_d_lon = (b[1] - a[1]).to_radians() / 2.0;
+
+    let h = half_d_lat.sin().powi(2) + lat_a.cos() * lat_b.cos() * half_d_lon.sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * h.min(1.0).sqrt().asin()
+}
+
+/// The great-circle distance in kilometres between `[latitude, longitude]` points, in
+/// degrees. See `haversine()`.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+/// use kiddo::geo::Haversine;
+///
+/// let mut tree: KdTree<f64, &str, 2> = KdTree::new();
+///
+/// tree.add(&[51.5074, -0.1278], "London")?;
+/// tree.add(&[48.8566, 2.3522], "Paris")?;
+///
+/// let nearest = tree.nearest_one(&[50.0, 1.0], &Haversine)?;
+///
+/// assert_eq!(*nearest.1, "Paris");
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Haversine;
+
+impl Distance<f64, 2> for Haversine {
+    fn dist(&self, a: &[f64; 2], b: &[f64; 2]) -> f64 {
+        haversine(a, b)
+    }
+
+    fn dist_to_box(&self, p: &[f64; 2], min: &[f64; 2], max: &[f64; 2]) -> f64 {
+        let d_lon = longitude_distance_to_range(p[1], min[1], max[1]);
+        if d_lon == 0.0 {
+            // the nearest point is due north or south
+            return EARTH_RADIUS_KM * (p[0] - p[0].max(min[0]).min(max[0])).abs().to_radians();
+        }
+
+        // The nearest point lies on the nearer of the box's two meridians. Along a meridian,
+        // the distance is smallest at `nearest_lat`, and grows in both directions until it
+        // reaches the antipode, so the minimum over the box is either there or at a corner.
+        let lat = p[0].to_radians();
+        let nearest_lat = lat
+            .sin()
+            .atan2(lat.cos() * d_lon.to_radians().cos())
+            .to_degrees();
+        let meridian = p[1] + d_lon;
+
+        let nearest_lat = nearest_lat.max(min[0]).min(max[0]);
+
+        [min[0], max[0], nearest_lat]
+            .iter()
+            .map(|&lat| haversine(p, &[lat, meridian]))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn max_dist_to_box(&self, p: &[f64; 2], min: &[f64; 2], max: &[f64; 2]) -> f64 {
+        // the furthest point from `p` is the nearest one to its antipode
+        let antipode = [-p[0], p[1] + 180.0];
+        core::f64::consts::PI * EARTH_RADIUS_KM - self.dist_to_box(&antipode, min, max)
+    }
+
+    fn dist1(&self, _axis_delta: f64) -> f64 {
+        // any difference in longitude is no distance at all at the poles
+        0.0
+    }
+}
+
+/// Returns the smallest difference in degrees between `lon` and any longitude in
+/// `min..=max`, wrapping around the antimeridian
+fn longitude_distance_to_range(lon: f64, min: f64, max: f64) -> f64 {
+    if max - min >= 360.0 || (lon >= min && lon <= max) {
+        return 0.0;
+    }
+
+    let east = modulo(min - lon, 360.0);
+    let west = modulo(lon - max, 360.0);
+    east.min(west)
+}
+
+impl<T: PartialEq> KdTree<f64, T, 2> {
+    /// Queries a tree of `[latitude, longitude]` points, in degrees, to find all elements
+    /// within `radius_km` kilometres of `point`. Results are returned sorted nearest-first,
+    /// with their distances in kilometres
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, &str, 2> = KdTree::new();
+    ///
+    /// tree.add(&[51.5074, -0.1278], "London")?;
+    /// tree.add(&[48.8566, 2.3522], "Paris")?;
+    /// tree.add(&[40.7128, -74.0060], "New York")?;
+    ///
+    /// let within = tree.within_km(&[51.5074, -0.1278], 500.0)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_km(&self, point: &[f64; 2], radius_km: f64) -> Result<Vec<(f64, &T)>, ErrorKind> {
+        self.within(point, radius_km, &Haversine)
+    }
+
+    /// Queries a tree of `[latitude, longitude]` points, in degrees, to find the nearest
+    /// `num` elements to `point`. Results are returned sorted nearest-first, with their
+    /// distances in kilometres
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, &str, 2> = KdTree::new();
+    ///
+    /// tree.add(&[-17.7134, 178.0650], "Fiji")?;
+    /// tree.add(&[-13.7590, -172.1046], "Samoa")?;
+    /// tree.add(&[-33.8688, 151.2093], "Sydney")?;
+    ///
+    /// let nearest = tree.nearest_km(&[-14.0, -179.0], 1)?;
+    ///
+    /// assert_eq!(*nearest[0].1, "Fiji");
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_km(&self, point: &[f64; 2], num: usize) -> Result<Vec<(f64, &T)>, ErrorKind> {
+        self.nearest(point, num, &Haversine)
+    }
+}
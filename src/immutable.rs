@@ -0,0 +1,1912 @@
+//! An immutable kd tree with a flat memory layout, optimised for query speed.
+//!
+//! Rather than every node owning its children through a `Box`, the stems and leaves of an
+//! `ImmutableKdTree` are stored contiguously in `Vec`s and refer to each other by `u32` index.
+//! The points in each leaf are stored in structure-of-arrays form, so that every axis of a
+//! leaf is contiguous in memory.
+
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{Ordering, Reverse};
+use core::ops::Range;
+
+use crate::distance::{Distance, InnerProduct};
+use crate::heap_element::HeapElement;
+use crate::kiddo::{check_epsilon, drain_sorted, may_be_closer, ErrorKind, KdTree, Node, Stack};
+use crate::scalar::Scalar;
+use crate::util;
+
+/// Set on a node index to indicate that it refers to a leaf rather than a stem
+pub(crate) const LEAF_FLAG: u32 = 1 << 31;
+
+/// An immutable kd tree. Supports the same queries as `KdTree`, but stores its nodes in
+/// contiguous arrays, which avoids a pointer chase per node and improves cache utilisation.
+///
+/// An `ImmutableKdTree` is created by converting an existing `KdTree`.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::{ImmutableKdTree, KdTree};
+/// use kiddo::distance::squared_euclidean;
+///
+/// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+///
+/// tree.add(&[1.0, 2.0, 5.0], 100)?;
+/// tree.add(&[2.0, 3.0, 6.0], 101)?;
+///
+/// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+///
+/// let nearest = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+///
+/// assert_eq!(*nearest.1, 100);
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImmutableKdTree<A, T, const K: usize> {
+    pub(crate) size: usize,
+    pub(crate) capacity: usize,
+    pub(crate) stems: Vec<StemNode<A, K>>,
+    pub(crate) leaves: Vec<LeafNode<A, K>>,
+    pub(crate) coords: Vec<A>,
+    pub(crate) items: Vec<T>,
+    pub(crate) root: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub(crate) struct StemNode<A, const K: usize> {
+    pub(crate) min_bounds: [A; K],
+    pub(crate) max_bounds: [A; K],
+    pub(crate) split_value: A,
+    pub(crate) split_dimension: u32,
+    pub(crate) left: u32,
+    pub(crate) right: u32,
+}
+
+/// The points of a leaf occupy `coords[start * K..(start + len) * K]`, with all of the
+/// coordinates for the first axis, followed by all of those for the second axis, and so on.
+/// Its items occupy `items[start..start + len]`.
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub(crate) struct LeafNode<A, const K: usize> {
+    pub(crate) min_bounds: [A; K],
+    pub(crate) max_bounds: [A; K],
+    pub(crate) start: u32,
+    pub(crate) len: u32,
+}
+
+/// A borrowed view of the arrays that make up an immutable tree, which implements all of
+/// its queries. Shared by `ImmutableKdTree` and `MappedKdTree`.
+pub(crate) struct TreeRef<'a, A, T, const K: usize> {
+    pub(crate) size: usize,
+    pub(crate) stems: &'a [StemNode<A, K>],
+    pub(crate) leaves: &'a [LeafNode<A, K>],
+    pub(crate) coords: &'a [A],
+    pub(crate) items: &'a [T],
+    pub(crate) root: u32,
+}
+
+impl<'a, A, T, const K: usize> Clone for TreeRef<'a, A, T, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, A, T, const K: usize> Copy for TreeRef<'a, A, T, K> {}
+
+impl<A: Scalar, T, const K: usize> ImmutableKdTree<A, T, K> {
+    /// Returns the number of elements stored in the tree
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[1.1, 2.1, 5.1], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the capacity per leaf node of the tree that this tree was converted from
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let tree: KdTree<f64, usize, 3> = KdTree::with_capacity(8)?;
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// assert_eq!(tree.capacity(), 8);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Queries the tree to find the nearest `num` elements to `point`, using the specified
+    /// distance metric function. Results are returned sorted nearest-first
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest(&[1.0, 2.0, 5.1], 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 1);
+    /// assert!((nearest[0].0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().nearest(point, num, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, using
+    /// the specified distance metric function.
+    ///
+    /// The search skips any node that cannot hold an element closer than the `num`th
+    /// nearest found so far by more than a factor of `1 + epsilon`, so each result is at
+    /// most `1 + epsilon` times as far from `point` as the true neighbour of the same rank.
+    /// Distances are compared as the metric returns them, so with `squared_euclidean` the
+    /// factor applies to the squared distance. Metrics that return negative distances, such
+    /// as `InnerProduct`, get the same bound written without a ratio: no skipped element is
+    /// closer than `d - |d| * epsilon / (1 + epsilon)`, where `d` is the distance of the
+    /// result. An `epsilon` of zero gives the same results as `nearest()`, and larger values
+    /// visit fewer nodes. Returns `ErrorKind::InvalidEpsilon` if `epsilon` is negative or
+    /// not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_approx(&[1.0, 2.0, 5.1], 1, 0.5, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 1);
+    /// assert!(nearest[0].0 <= 1.5 * 0.01);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_approx<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref()
+            .nearest_approx_with_budget(point, num, epsilon, usize::MAX, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, as
+    /// `nearest_approx()` does, but gives up after searching `max_leaves_visited` leaf nodes
+    /// and returns the nearest elements found so far. This caps the time a query can take,
+    /// at the cost of the `1 + epsilon` guarantee: once the budget is spent, the results may
+    /// be further away than that, and there may be fewer than `num` of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_approx_with_budget(&[1.0, 2.0, 5.1], 3, 0.0, 1, &squared_euclidean)?;
+    ///
+    /// assert!(nearest.len() < 3);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().nearest_approx_with_budget(
+            point,
+            num,
+            epsilon,
+            max_leaves_visited,
+            distance,
+        )
+    }
+
+    /// Queries the tree to find the nearest `num` elements to each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling nearest() for each point as allocations
+    /// are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().nearest_batch(points, num, distance)
+    }
+
+    /// Queries the tree to find the nearest element to `point`, using the specified
+    /// distance metric function.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// assert!((nearest.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_one<D, F>(&self, point: &[A; K], distance: &F) -> Result<(D, &T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().nearest_one(point, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest element to `point`, using the
+    /// specified distance metric function. The result is at most `1 + epsilon` times as far
+    /// from `point` as the nearest element. See `nearest_approx()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_one_approx(&[1.0, 2.0, 5.1], 0.5, &squared_euclidean)?;
+    ///
+    /// assert!(nearest.0 <= 1.5 * 0.01);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_one_approx<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        distance: &F,
+    ) -> Result<(D, &T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref()
+            .nearest_one_approx_with_budget(point, epsilon, usize::MAX, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest element to `point`, as
+    /// `nearest_one_approx()` does, but gives up after searching `max_leaves_visited` leaf
+    /// nodes and returns the nearest element found so far. The search always continues
+    /// until it has found at least one element, even if that exceeds the budget.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_one_approx_with_budget(&[1.0, 2.0, 5.1], 0.0, 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_one_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<(D, &T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref()
+            .nearest_one_approx_with_budget(point, epsilon, max_leaves_visited, distance)
+    }
+
+    /// Queries the tree to find the nearest element to each of `points`, using the specified
+    /// distance metric function. Results are returned in the same order as `points`. Faster
+    /// than calling nearest_one() for each point as allocations are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().nearest_one_batch(points, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned sorted nearest-first
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().within(point, radius, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling within() for each point as allocations
+    /// are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let within = tree.within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().within_batch(points, radius, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. Faster than within()
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let within = tree.within_unsorted(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_unsorted<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().within_unsorted(point, radius, distance)
+    }
+
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let best_n_within = tree.best_n_within(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(best_n_within[0], 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn best_n_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> Result<Vec<T>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        self.tree_ref()
+            .best_n_within(point, radius, max_qty, distance)
+    }
+
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt). Returns an iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let mut best_n_within_iter = tree.best_n_within_into_iter(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean);
+    /// let first = best_n_within_iter.next().unwrap();
+    ///
+    /// assert_eq!(first, 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn best_n_within_into_iter<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> impl Iterator<Item = T>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        self.tree_ref()
+            .best_n_within_into_iter(point, radius, max_qty, distance)
+    }
+
+    /// Returns an iterator over all elements in the tree, sorted nearest-first to the query point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let mut nearest_iter = tree.iter_nearest(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// let nearest_first = nearest_iter.next().unwrap();
+    ///
+    /// assert!((nearest_first.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest_first.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn iter_nearest<'a, 'b, D, F>(
+        &'b self,
+        point: &'a [A; K],
+        distance: &'a F,
+    ) -> Result<ImmutableNearestIter<'a, 'b, A, T, F, K, D>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().iter_nearest(point, distance)
+    }
+
+    /// Queries the tree to find all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive. Results are returned in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let within = tree.within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within, vec![([1.0, 2.0, 5.0], &100)]);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_box(&self, min: &[A; K], max: &[A; K]) -> Result<Vec<([A; K], &T)>, ErrorKind> {
+        Ok(self.iter_within_box(min, max)?.collect())
+    }
+
+    /// Returns an iterator over all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive, in arbitrary order.
+    ///
+    /// Subtrees that lie entirely outside the box are skipped, and the elements of subtrees
+    /// that lie entirely inside it are returned without checking each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let mut within = tree.iter_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within.next(), Some(([1.0, 2.0, 5.0], &100)));
+    /// assert_eq!(within.next(), None);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn iter_within_box(
+        &self,
+        min: &[A; K],
+        max: &[A; K],
+    ) -> Result<ImmutableWithinBoxIter<'_, A, T, K>, ErrorKind> {
+        self.tree_ref().iter_within_box(min, max)
+    }
+
+    /// Counts the elements within `radius` of `point`, using the specified distance
+    /// metric function, without collecting them.
+    ///
+    /// The elements of subtrees that lie entirely within `radius` are counted without
+    /// visiting each point, which assumes that the distance metric does not decrease as
+    /// the difference along an axis grows. This holds for all of the metrics in `distance`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let count = tree.count_within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(count, 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn count_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<usize, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree_ref().count_within(point, radius, distance)
+    }
+
+    /// Counts the elements inside the axis-aligned box with corners `min` and `max`,
+    /// inclusive, without collecting them. The elements of subtrees that lie entirely
+    /// inside the box are counted without visiting each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let count = tree.count_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(count, 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn count_within_box(&self, min: &[A; K], max: &[A; K]) -> Result<usize, ErrorKind> {
+        self.tree_ref().count_within_box(min, max)
+    }
+
+    pub(crate) fn tree_ref(&self) -> TreeRef<'_, A, T, K> {
+        TreeRef {
+            size: self.size,
+            stems: &self.stems,
+            leaves: &self.leaves,
+            coords: &self.coords,
+            items: &self.items,
+            root: self.root,
+        }
+    }
+
+    fn push_node(&mut self, tree: KdTree<A, T, K>) -> u32
+    where
+        T: PartialEq,
+    {
+        match tree.content {
+            Node::Stem {
+                left,
+                right,
+                split_value,
+                split_dimension,
+            } => {
+                let idx = self.stems.len();
+                assert!(
+                    idx < LEAF_FLAG as usize,
+                    "too many stems for an ImmutableKdTree"
+                );
+
+                self.stems.push(StemNode {
+                    min_bounds: tree.min_bounds,
+                    max_bounds: tree.max_bounds,
+                    split_value,
+                    split_dimension: split_dimension as u32,
+                    left: 0,
+                    right: 0,
+                });
+
+                let left = self.push_node(*left);
+                let right = self.push_node(*right);
+                self.stems[idx].left = left;
+                self.stems[idx].right = right;
+
+                idx as u32
+            }
+            Node::Leaf { points, bucket, .. } => {
+                let idx = self.leaves.len();
+                assert!(
+                    idx < LEAF_FLAG as usize,
+                    "too many leaves for an ImmutableKdTree"
+                );
+
+                let start = self.items.len();
+                for dim in 0..K {
+                    self.coords.extend_from_slice(points.column(dim));
+                }
+                self.items.extend(bucket);
+
+                self.leaves.push(LeafNode {
+                    min_bounds: tree.min_bounds,
+                    max_bounds: tree.max_bounds,
+                    start: start as u32,
+                    len: points.len() as u32,
+                });
+
+                idx as u32 | LEAF_FLAG
+            }
+            Node::Duplicates { point, bucket, .. } => {
+                let idx = self.leaves.len();
+                assert!(
+                    idx < LEAF_FLAG as usize,
+                    "too many leaves for an ImmutableKdTree"
+                );
+
+                // the flat layout stores the duplicated point once for each element
+                let start = self.items.len();
+                let len = bucket.len();
+                for coord in point.iter() {
+                    self.coords.extend(core::iter::repeat_n(*coord, len));
+                }
+                self.items.extend(bucket);
+
+                self.leaves.push(LeafNode {
+                    min_bounds: tree.min_bounds,
+                    max_bounds: tree.max_bounds,
+                    start: start as u32,
+                    len: len as u32,
+                });
+
+                idx as u32 | LEAF_FLAG
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<A, T, const K: usize> ImmutableKdTree<A, T, K>
+where
+    A: Scalar + Send + Sync,
+    T: Sync,
+{
+    /// Queries the tree to find the nearest element to each of `points` using all available
+    /// threads. Results are returned in the same order as `points`. See `nearest_one()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.par_nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree_ref().par_nearest_one_batch(points, distance)
+    }
+
+    /// Queries the tree to find the nearest `num` elements to each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `nearest()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let nearest = tree.par_nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree_ref().par_nearest_batch(points, num, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `within()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let within = tree.par_within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree_ref().par_within_batch(points, radius, distance)
+    }
+}
+
+impl<T, const K: usize> ImmutableKdTree<f32, T, K> {
+    /// Queries the tree to find the `num` elements with the greatest inner product with
+    /// `query`. For unit vectors, this is their cosine similarity. Results are returned
+    /// sorted most similar first, along with their similarity
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f32, usize, 2> = tree.into();
+    ///
+    /// let similar = tree.most_similar(&[0.0, 1.0], 2)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101), (0.0, &100)]);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn most_similar(&self, query: &[f32; K], num: usize) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        self.tree_ref().most_similar(query, num)
+    }
+
+    /// Queries the tree to find all elements whose inner product with `query` is at least
+    /// `min_similarity`. For unit vectors, this is their cosine similarity. Results are
+    /// returned sorted most similar first, along with their similarity
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f32, usize, 2> = tree.into();
+    ///
+    /// let similar = tree.similar_within(&[0.0, 1.0], 0.5)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101)]);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn similar_within(
+        &self,
+        query: &[f32; K],
+        min_similarity: f32,
+    ) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        self.tree_ref().similar_within(query, min_similarity)
+    }
+}
+
+impl<'a, A: Scalar, T, const K: usize> TreeRef<'a, A, T, K> {
+    pub(crate) fn nearest<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.nearest_approx_with_budget(point, num, 0.0, usize::MAX, distance)
+    }
+
+    pub(crate) fn nearest_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+
+        self.nearest_impl(
+            point,
+            num,
+            epsilon,
+            max_leaves_visited,
+            distance,
+            &mut pending,
+            &mut evaluated,
+        )?;
+
+        Ok(drain_sorted(&mut evaluated))
+    }
+
+    pub(crate) fn nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+
+        points
+            .iter()
+            .map(|point| {
+                self.nearest_impl(
+                    point,
+                    num,
+                    0.0,
+                    usize::MAX,
+                    distance,
+                    &mut pending,
+                    &mut evaluated,
+                )?;
+                Ok(drain_sorted(&mut evaluated))
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn nearest_impl<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, u32>>>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'a T>>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        util::check_point(point)?;
+        check_epsilon(epsilon)?;
+
+        pending.clear();
+        evaluated.clear();
+
+        let num = core::cmp::min(num, self.size);
+        if num == 0 {
+            return Ok(());
+        }
+
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self.root,
+        }));
+
+        let mut leaves_visited = 0;
+        while leaves_visited < max_leaves_visited
+            && !pending.is_empty()
+            && (evaluated.len() < num
+                || may_be_closer(
+                    pending.peek().unwrap().0.distance,
+                    evaluated.peek().unwrap().distance,
+                    epsilon,
+                ))
+        {
+            let node = pending.pop().unwrap().0.element;
+            let leaf = self.populate_pending(point, D::highest(), distance, pending, node);
+
+            self.for_each_leaf_dist(leaf, point, distance, |dist, item| {
+                let element = HeapElement {
+                    distance: dist,
+                    element: item,
+                };
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else {
+                    let mut top = evaluated.peek_mut().unwrap();
+                    if element < *top {
+                        *top = element;
+                    }
+                }
+            });
+            leaves_visited += 1;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn nearest_one<D, F>(
+        &self,
+        point: &[A; K],
+        distance: &F,
+    ) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.nearest_one_impl(
+            point,
+            0.0,
+            usize::MAX,
+            distance,
+            &mut Vec::with_capacity(16),
+        )
+    }
+
+    pub(crate) fn nearest_one_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.nearest_one_impl(
+            point,
+            epsilon,
+            max_leaves_visited,
+            distance,
+            &mut Vec::with_capacity(16),
+        )
+    }
+
+    pub(crate) fn nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = Vec::with_capacity(16);
+
+        points
+            .iter()
+            .map(|point| self.nearest_one_impl(point, 0.0, usize::MAX, distance, &mut pending))
+            .collect()
+    }
+
+    fn nearest_one_impl<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, u32>>>,
+    ) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        if self.size == 0 {
+            return Err(ErrorKind::Empty);
+        }
+        util::check_point(point)?;
+        check_epsilon(epsilon)?;
+
+        pending.clear();
+
+        let mut best_dist = D::highest();
+        let mut best_elem: Option<&T> = None;
+        let mut leaves_visited = 0;
+
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self.root,
+        }));
+
+        // the budget only applies once something has been found, so that there is always
+        // an element to return
+        while let Some(next) = pending.pop() {
+            if best_elem.is_some() && !may_be_closer(next.0.distance, best_dist, epsilon) {
+                continue;
+            }
+
+            let leaf = self.populate_pending(point, best_dist, distance, pending, next.0.element);
+
+            self.for_each_leaf_dist(leaf, point, distance, |dist, item| {
+                if best_elem.is_none() || dist < best_dist {
+                    best_elem = Some(item);
+                    best_dist = dist;
+                }
+            });
+
+            leaves_visited += 1;
+            if leaves_visited >= max_leaves_visited && best_elem.is_some() {
+                break;
+            }
+        }
+
+        Ok((best_dist, best_elem.unwrap()))
+    }
+
+    pub(crate) fn within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.within_impl(point, radius, distance, &mut Vec::with_capacity(16))
+    }
+
+    pub(crate) fn within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = Vec::with_capacity(16);
+
+        points
+            .iter()
+            .map(|point| {
+                util::check_point(point)?;
+                self.within_impl(point, radius, distance, &mut pending)
+            })
+            .collect()
+    }
+
+    fn within_impl<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, u32>>>,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut evaluated = self.within_unsorted_impl(point, radius, distance, pending)?;
+        // sorted as KdTree::within() sorts, treating incomparable distances as equal
+        evaluated.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        Ok(evaluated)
+    }
+
+    pub(crate) fn within_unsorted<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.within_unsorted_impl(point, radius, distance, &mut Vec::with_capacity(16))
+    }
+
+    fn within_unsorted_impl<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, u32>>>,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
+        util::check_point(point)?;
+
+        pending.clear();
+        let mut evaluated = Vec::new();
+
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self.root,
+        }));
+
+        while let Some(next) = pending.pop() {
+            let leaf = self.populate_pending(point, radius, distance, pending, next.0.element);
+
+            self.for_each_leaf_dist(leaf, point, distance, |dist, item| {
+                if dist <= radius {
+                    evaluated.push((dist, item));
+                }
+            });
+        }
+
+        Ok(evaluated)
+    }
+
+    pub(crate) fn best_n_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> Result<Vec<T>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        if self.size == 0 || max_qty == 0 {
+            return Ok(vec![]);
+        }
+        util::check_point(point)?;
+
+        let mut pending = Vec::with_capacity(16);
+        let mut evaluated = BinaryHeap::<T>::new();
+
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self.root,
+        }));
+
+        while let Some(next) = pending.pop() {
+            let leaf = self.populate_pending(point, radius, distance, &mut pending, next.0.element);
+
+            self.for_each_leaf_dist(leaf, point, distance, |dist, item| {
+                if dist <= radius {
+                    if evaluated.len() < max_qty {
+                        evaluated.push(*item);
+                    } else {
+                        let mut top = evaluated.peek_mut().unwrap();
+                        if *item < *top {
+                            *top = *item;
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(evaluated.into_vec())
+    }
+
+    pub(crate) fn best_n_within_into_iter<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> impl Iterator<Item = T>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        // as with KdTree::best_n_within_into_iter(), there is no way to return an error,
+        // so an invalid point yields nothing
+        self.best_n_within(point, radius, max_qty, distance)
+            .unwrap_or_default()
+            .into_iter()
+    }
+
+    pub(crate) fn iter_nearest<'p, D, F>(
+        self,
+        point: &'p [A; K],
+        distance: &'p F,
+    ) -> Result<ImmutableNearestIter<'p, 'a, A, T, F, K, D>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        util::check_point(point)?;
+
+        let mut pending = BinaryHeap::new();
+        if self.size > 0 {
+            pending.push(Reverse(HeapElement {
+                distance: D::zero(),
+                element: self.root,
+            }));
+        }
+
+        Ok(ImmutableNearestIter {
+            tree: self,
+            point,
+            pending,
+            evaluated: BinaryHeap::new(),
+            distance,
+        })
+    }
+
+    pub(crate) fn iter_within_box(
+        self,
+        min: &[A; K],
+        max: &[A; K],
+    ) -> Result<ImmutableWithinBoxIter<'a, A, T, K>, ErrorKind> {
+        util::check_point(min)?;
+        util::check_point(max)?;
+
+        Ok(ImmutableWithinBoxIter {
+            tree: self,
+            min: *min,
+            max: *max,
+            pending: vec![(self.root, false)],
+            leaf: None,
+        })
+    }
+
+    pub(crate) fn count_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<usize, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        util::check_point(point)?;
+        if self.size == 0 {
+            return Ok(0);
+        }
+
+        // stems do not record how many elements they hold, so a subtree that lies entirely
+        // within `radius` is still walked, but its leaves are counted without measuring
+        let mut count = 0;
+        let mut pending = vec![(self.root, false)];
+        while let Some((node, parent_inside)) = pending.pop() {
+            let mut inside = parent_inside;
+            if !inside {
+                let (min_bounds, max_bounds) = self.bounds(node);
+                if distance.dist_to_box(point, min_bounds, max_bounds) > radius {
+                    continue;
+                }
+                inside = distance.max_dist_to_box(point, min_bounds, max_bounds) <= radius;
+            }
+
+            if node & LEAF_FLAG == 0 {
+                let stem = &self.stems[node as usize];
+                pending.push((stem.right, inside));
+                pending.push((stem.left, inside));
+            } else if inside {
+                count += self.leaves[(node & !LEAF_FLAG) as usize].len as usize;
+            } else {
+                let leaf = (node & !LEAF_FLAG) as usize;
+                self.for_each_leaf_dist(leaf, point, distance, |dist, _| {
+                    if dist <= radius {
+                        count += 1;
+                    }
+                });
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub(crate) fn count_within_box(&self, min: &[A; K], max: &[A; K]) -> Result<usize, ErrorKind> {
+        util::check_point(min)?;
+        util::check_point(max)?;
+        if self.size == 0 {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        let mut pending = vec![(self.root, false)];
+        while let Some((node, parent_inside)) = pending.pop() {
+            let (min_bounds, max_bounds) = self.bounds(node);
+            let inside = parent_inside || util::bounds_within_box(min_bounds, max_bounds, min, max);
+            if !inside && !util::bounds_intersect_box(min_bounds, max_bounds, min, max) {
+                continue;
+            }
+
+            if node & LEAF_FLAG == 0 {
+                let stem = &self.stems[node as usize];
+                pending.push((stem.right, inside));
+                pending.push((stem.left, inside));
+            } else {
+                let leaf = (node & !LEAF_FLAG) as usize;
+                let len = self.leaves[leaf].len as usize;
+                count += if inside {
+                    len
+                } else {
+                    (0..len)
+                        .map(|idx| self.leaf_entry(leaf, idx).0)
+                        .filter(|p| (0..K).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim]))
+                        .count()
+                };
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Walks from `node` down to the leaf that `point` falls within, pushing each sibling
+    /// that could contain an element within `max_dist` of `point` onto `pending`.
+    /// Returns the index of the leaf.
+    fn populate_pending<D, F>(
+        &self,
+        point: &[A; K],
+        max_dist: D,
+        distance: &F,
+        pending: &mut impl Stack<Reverse<HeapElement<D, u32>>>,
+        mut node: u32,
+    ) -> usize
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        while node & LEAF_FLAG == 0 {
+            let stem = &self.stems[node as usize];
+            let candidate;
+            (candidate, node) = if point[stem.split_dimension as usize] < stem.split_value {
+                (stem.right, stem.left)
+            } else {
+                (stem.left, stem.right)
+            };
+
+            // everything in the candidate is at least as far away as the split plane
+            if distance.dist1(A::abs_diff(
+                point[stem.split_dimension as usize],
+                stem.split_value,
+            )) > max_dist
+            {
+                continue;
+            }
+
+            let (min_bounds, max_bounds) = self.bounds(candidate);
+            let candidate_to_space = distance.dist_to_box(point, min_bounds, max_bounds);
+
+            if candidate_to_space <= max_dist {
+                pending.stack_push(Reverse(HeapElement {
+                    distance: candidate_to_space,
+                    element: candidate,
+                }));
+            }
+        }
+
+        (node & !LEAF_FLAG) as usize
+    }
+
+    fn bounds(&self, node: u32) -> (&'a [A; K], &'a [A; K]) {
+        if node & LEAF_FLAG == 0 {
+            let stem = &self.stems[node as usize];
+            (&stem.min_bounds, &stem.max_bounds)
+        } else {
+            let leaf = &self.leaves[(node & !LEAF_FLAG) as usize];
+            (&leaf.min_bounds, &leaf.max_bounds)
+        }
+    }
+
+    /// Measures the distance from `point` to each point in a leaf, passing the distance
+    /// and item of each to `visit`
+    fn for_each_leaf_dist<D, F, V>(self, leaf: usize, point: &[A; K], distance: &F, mut visit: V)
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        V: FnMut(D, &'a T),
+    {
+        let leaf = &self.leaves[leaf];
+        let start = leaf.start as usize;
+        let len = leaf.len as usize;
+        let coords = &self.coords[start * K..(start + len) * K];
+        let columns: [&[A]; K] = core::array::from_fn(|dim| &coords[dim * len..(dim + 1) * len]);
+        let items = &self.items[start..start + len];
+
+        util::for_each_dist(point, &columns, len, distance, |idx, dist| {
+            visit(dist, &items[idx])
+        });
+    }
+
+    /// Returns the point and item at position `idx` within a leaf
+    fn leaf_entry(self, leaf: usize, idx: usize) -> ([A; K], &'a T) {
+        let leaf = &self.leaves[leaf];
+        let start = leaf.start as usize;
+        let len = leaf.len as usize;
+        let coords = &self.coords[start * K..(start + len) * K];
+
+        (
+            core::array::from_fn(|dim| coords[dim * len + idx]),
+            &self.items[start + idx],
+        )
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, A, T, const K: usize> TreeRef<'a, A, T, K>
+where
+    A: Scalar + Send + Sync,
+    T: Sync,
+{
+    pub(crate) fn par_nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || Vec::with_capacity(16),
+                |pending, point| self.nearest_one_impl(point, 0.0, usize::MAX, distance, pending),
+            )
+            .collect()
+    }
+
+    pub(crate) fn par_nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || (BinaryHeap::new(), BinaryHeap::new()),
+                |(pending, evaluated), point| {
+                    self.nearest_impl(point, num, 0.0, usize::MAX, distance, pending, evaluated)?;
+                    Ok(drain_sorted(evaluated))
+                },
+            )
+            .collect()
+    }
+
+    pub(crate) fn par_within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || Vec::with_capacity(16),
+                |pending, point| {
+                    util::check_point(point)?;
+                    self.within_impl(point, radius, distance, pending)
+                },
+            )
+            .collect()
+    }
+}
+
+impl<'a, T, const K: usize> TreeRef<'a, f32, T, K> {
+    pub(crate) fn most_similar(
+        &self,
+        query: &[f32; K],
+        num: usize,
+    ) -> Result<Vec<(f32, &'a T)>, ErrorKind> {
+        let nearest = self.nearest(query, num, &InnerProduct)?;
+        Ok(nearest
+            .into_iter()
+            .map(|(dist, item)| (-dist, item))
+            .collect())
+    }
+
+    pub(crate) fn similar_within(
+        &self,
+        query: &[f32; K],
+        min_similarity: f32,
+    ) -> Result<Vec<(f32, &'a T)>, ErrorKind> {
+        let within = self.within(query, -min_similarity, &InnerProduct)?;
+        Ok(within
+            .into_iter()
+            .map(|(dist, item)| (-dist, item))
+            .collect())
+    }
+}
+
+impl<A: Scalar, T: PartialEq, const K: usize> From<KdTree<A, T, K>> for ImmutableKdTree<A, T, K> {
+    fn from(tree: KdTree<A, T, K>) -> Self {
+        assert!(
+            tree.size() <= u32::MAX as usize,
+            "too many elements for an ImmutableKdTree"
+        );
+
+        let mut result = ImmutableKdTree {
+            size: tree.size(),
+            capacity: tree.capacity(),
+            stems: Vec::new(),
+            leaves: Vec::new(),
+            coords: Vec::with_capacity(tree.size() * K),
+            items: Vec::with_capacity(tree.size()),
+            root: 0,
+        };
+        result.root = result.push_node(tree);
+
+        result
+    }
+}
+
+impl<A: Scalar, T: PartialEq + Clone, const K: usize> From<&KdTree<A, T, K>>
+    for ImmutableKdTree<A, T, K>
+{
+    fn from(tree: &KdTree<A, T, K>) -> Self {
+        tree.clone().into()
+    }
+}
+
+pub struct ImmutableNearestIter<
+    'a,
+    'b,
+    A: 'a + 'b + Scalar,
+    T: 'b,
+    F: 'a + Distance<A, K, D>,
+    const K: usize,
+    D: Scalar = A,
+> {
+    tree: TreeRef<'b, A, T, K>,
+    point: &'a [A; K],
+    pending: BinaryHeap<Reverse<HeapElement<D, u32>>>,
+    evaluated: BinaryHeap<Reverse<HeapElement<D, &'b T>>>,
+    distance: &'a F,
+}
+
+impl<'a, 'b, A: Scalar, T: 'b, F: 'a, const K: usize, D> Iterator
+    for ImmutableNearestIter<'a, 'b, A, T, F, K, D>
+where
+    D: Scalar,
+    F: Distance<A, K, D>,
+{
+    type Item = (D, &'b T);
+    fn next(&mut self) -> Option<(D, &'b T)> {
+        let distance = self.distance;
+        let point = self.point;
+        let tree = self.tree;
+        while !self.pending.is_empty()
+            && (self.evaluated.peek().map_or(D::highest(), |x| x.0.distance)
+                >= self.pending.peek().unwrap().0.distance)
+        {
+            let node = self.pending.pop().unwrap().0.element;
+            let leaf =
+                tree.populate_pending(point, D::highest(), distance, &mut self.pending, node);
+
+            let evaluated = &mut self.evaluated;
+            tree.for_each_leaf_dist(leaf, point, distance, |dist, item| {
+                evaluated.push(Reverse(HeapElement {
+                    distance: dist,
+                    element: item,
+                }))
+            });
+        }
+        self.evaluated.pop().map(|x| x.0.into())
+    }
+}
+
+pub struct ImmutableWithinBoxIter<'a, A, T, const K: usize> {
+    tree: TreeRef<'a, A, T, K>,
+    min: [A; K],
+    max: [A; K],
+    pending: Vec<(u32, bool)>,
+    leaf: Option<(usize, Range<usize>, bool)>,
+}
+
+impl<'a, A: Scalar, T, const K: usize> Iterator for ImmutableWithinBoxIter<'a, A, T, K> {
+    type Item = ([A; K], &'a T);
+    fn next(&mut self) -> Option<([A; K], &'a T)> {
+        let tree = self.tree;
+        loop {
+            if let Some((leaf, positions, inside)) = &mut self.leaf {
+                let (min, max) = (&self.min, &self.max);
+                let next = positions
+                    .map(|idx| tree.leaf_entry(*leaf, idx))
+                    .find(|(p, _)| {
+                        *inside || (0..K).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim])
+                    });
+                if next.is_some() {
+                    return next;
+                }
+                self.leaf = None;
+            }
+
+            let (node, parent_inside) = self.pending.pop()?;
+            // everything in a subtree is inside the box if its bounds are
+            let (min, max) = (&self.min, &self.max);
+            let (min_bounds, max_bounds) = tree.bounds(node);
+            let inside = parent_inside || util::bounds_within_box(min_bounds, max_bounds, min, max);
+            if !inside && !util::bounds_intersect_box(min_bounds, max_bounds, min, max) {
+                continue;
+            }
+
+            if node & LEAF_FLAG == 0 {
+                let stem = &tree.stems[node as usize];
+                self.pending.push((stem.right, inside));
+                self.pending.push((stem.left, inside));
+            } else {
+                let leaf = (node & !LEAF_FLAG) as usize;
+                let len = tree.leaves[leaf].len as usize;
+                self.leaf = Some((leaf, 0..len, inside));
+            }
+        }
+    }
+}
@@ -1,18 +1,24 @@
-use std::collections::BinaryHeap;
-
-use num_traits::{Float, One, Zero};
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::iter::{FromIterator, Zip};
+use core::slice;
 
 #[cfg(feature = "serialize")]
 use crate::custom_serde::*;
+use crate::distance::{Distance, InnerProduct};
 use crate::heap_element::HeapElement;
+use crate::scalar::Scalar;
+use crate::split::{widest_dimension, SplitStrategy};
 use crate::util;
 
-trait Stack<T>
+pub(crate) trait Stack<T>
 where
     T: Ord,
 {
     fn stack_push(&mut self, _: T);
-    fn stack_pop(&mut self) -> Option<T>;
 }
 
 impl<T> Stack<T> for Vec<T>
@@ -23,10 +29,6 @@ where
     fn stack_push(&mut self, element: T) {
         Vec::<T>::push(self, element)
     }
-    #[inline(always)]
-    fn stack_pop(&mut self) -> Option<T> {
-        Vec::<T>::pop(self)
-    }
 }
 
 impl<T> Stack<T> for BinaryHeap<T>
@@ -37,27 +39,30 @@ where
     fn stack_push(&mut self, element: T) {
         BinaryHeap::<T>::push(self, element)
     }
-    #[inline(always)]
-    fn stack_pop(&mut self) -> Option<T> {
-        BinaryHeap::<T>::pop(self)
-    }
 }
 
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
-pub struct KdTree<A, T: std::cmp::PartialEq, const K: usize> {
-    size: usize,
+pub struct KdTree<A, T: core::cmp::PartialEq, const K: usize> {
+    pub(crate) size: usize,
 
     #[cfg_attr(feature = "serialize", serde(with = "arrays"))]
-    min_bounds: [A; K],
+    pub(crate) min_bounds: [A; K],
     #[cfg_attr(feature = "serialize", serde(with = "arrays"))]
-    max_bounds: [A; K],
-    content: Node<A, T, K>,
+    pub(crate) max_bounds: [A; K],
+    #[cfg_attr(
+        feature = "serialize",
+        serde(bound(
+            serialize = "A: serde::Serialize + Copy, T: serde::Serialize",
+            deserialize = "A: serde::Deserialize<'de> + Copy, T: serde::Deserialize<'de>"
+        ))
+    )]
+    pub(crate) content: Node<A, T, K>,
 }
 
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug)]
-pub enum Node<A, T: std::cmp::PartialEq, const K: usize> {
+pub enum Node<A, T: core::cmp::PartialEq, const K: usize> {
     Stem {
         left: Box<KdTree<A, T, K>>,
         right: Box<KdTree<A, T, K>>,
@@ -65,21 +70,246 @@ pub enum Node<A, T: std::cmp::PartialEq, const K: usize> {
         split_dimension: u8,
     },
     Leaf {
-        #[cfg_attr(feature = "serialize", serde(with = "vec_arrays"))]
-        points: Vec<[A; K]>,
+        #[cfg_attr(
+            feature = "serialize",
+            serde(bound(
+                serialize = "A: serde::Serialize + Copy",
+                deserialize = "A: serde::Deserialize<'de> + Copy"
+            ))
+        )]
+        points: LeafPoints<A, K>,
+        bucket: Vec<T>,
+        capacity: usize,
+        #[cfg_attr(feature = "serialize", serde(default))]
+        split_strategy: SplitStrategy,
+    },
+    /// A leaf whose elements all share one point, which is stored once. A leaf becomes a
+    /// `Duplicates` node when it overflows with nothing to split on, and turns back into an
+    /// ordinary leaf when a different point is added to it, or when its elements fit in one.
+    Duplicates {
+        #[cfg_attr(feature = "serialize", serde(with = "arrays"))]
+        point: [A; K],
         bucket: Vec<T>,
         capacity: usize,
+        split_strategy: SplitStrategy,
     },
 }
 
+/// The points of a leaf, stored as one list of coordinates per axis. The per-axis layout
+/// lets metrics that opt in with `Distance::BATCHED` measure many points at once. Points
+/// are gathered from each axis when they are read one at a time.
+#[derive(Clone)]
+pub struct LeafPoints<A, const K: usize> {
+    /// The coordinates along each axis, `stride` apart, in a single allocation so that a
+    /// leaf stays close together in memory. Only the first `len` coordinates of each axis
+    /// are meaningful.
+    pub(crate) coords: Vec<A>,
+    pub(crate) len: usize,
+    pub(crate) stride: usize,
+}
+
+impl<A: Copy, const K: usize> LeafPoints<A, K> {
+    fn with_capacity(capacity: usize) -> Self {
+        LeafPoints {
+            coords: Vec::with_capacity(capacity * K),
+            len: 0,
+            stride: 0,
+        }
+    }
+
+    /// Returns the number of points
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no points
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the point at `idx`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> [A; K] {
+        assert!(idx < self.len, "point index out of bounds");
+        core::array::from_fn(|dim| self.coords[dim * self.stride + idx])
+    }
+
+    /// Returns an iterator over the points
+    pub fn iter(&self) -> LeafPointsIter<'_, A, K> {
+        LeafPointsIter {
+            points: self,
+            idx: 0,
+        }
+    }
+
+    fn push(&mut self, point: [A; K]) {
+        if self.len == self.stride {
+            // the first relayout uses the capacity the leaf was created with
+            let stride = (self.stride * 2)
+                .max(self.coords.capacity() / K.max(1))
+                .max(1);
+            self.relayout(stride, &point);
+        }
+        self.len += 1;
+        self.set(self.len - 1, point);
+    }
+
+    /// Moves the coordinates of each axis `stride` apart, filling the gaps with the
+    /// coordinates of `fill`
+    fn relayout(&mut self, stride: usize, fill: &[A; K]) {
+        let mut coords = Vec::with_capacity(stride * K);
+        for (dim, x) in fill.iter().enumerate() {
+            coords.extend_from_slice(self.column(dim));
+            coords.resize((dim + 1) * stride, *x);
+        }
+        self.coords = coords;
+        self.stride = stride;
+    }
+
+    fn swap_remove(&mut self, idx: usize) -> [A; K] {
+        let point = self.get(idx);
+        let last = self.len - 1;
+        for dim in 0..K {
+            self.coords[dim * self.stride + idx] = self.coords[dim * self.stride + last];
+        }
+        self.len = last;
+        point
+    }
+
+    fn set(&mut self, idx: usize, point: [A; K]) {
+        assert!(idx < self.len, "point index out of bounds");
+        for (dim, x) in point.iter().enumerate() {
+            self.coords[dim * self.stride + idx] = *x;
+        }
+    }
+
+    /// Removes every point, returning an iterator over them. The columns keep their
+    /// allocation.
+    fn drain(&mut self) -> impl Iterator<Item = [A; K]> + '_ {
+        let len = core::mem::replace(&mut self.len, 0);
+        let (coords, stride) = (&self.coords, self.stride);
+        (0..len).map(move |idx| core::array::from_fn(|dim| coords[dim * stride + idx]))
+    }
+
+    /// Returns the coordinates of every point along `dim`
+    pub fn column(&self, dim: usize) -> &[A] {
+        let start = dim * self.stride;
+        &self.coords[start..start + self.len]
+    }
+
+    fn columns(&self) -> [&[A]; K] {
+        core::array::from_fn(|dim| self.column(dim))
+    }
+
+    /// Returns the number of bytes allocated for the coordinates
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        self.coords.capacity() * core::mem::size_of::<A>()
+    }
+}
+
+impl<A: Scalar, const K: usize> LeafPoints<A, K> {
+    /// Measures the distance from `point` to each point, passing its index and distance
+    /// to `visit`
+    fn for_each_dist<D, F, V>(&self, point: &[A; K], distance: &F, visit: V)
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        V: FnMut(usize, D),
+    {
+        util::for_each_dist(point, &self.columns(), self.len, distance, visit);
+    }
+}
+
+impl<A: core::fmt::Debug, const K: usize> core::fmt::Debug for LeafPoints<A, K> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let point =
+            |idx| -> [&A; K] { core::array::from_fn(|dim| &self.coords[dim * self.stride + idx]) };
+        f.debug_list().entries((0..self.len).map(point)).finish()
+    }
+}
+
+impl<A: Copy, const K: usize> Default for LeafPoints<A, K> {
+    fn default() -> Self {
+        LeafPoints::with_capacity(0)
+    }
+}
+
+impl<A: Copy, const K: usize> Extend<[A; K]> for LeafPoints<A, K> {
+    fn extend<I: IntoIterator<Item = [A; K]>>(&mut self, iter: I) {
+        for point in iter {
+            self.push(point);
+        }
+    }
+}
+
+impl<A: Copy, const K: usize> From<Vec<[A; K]>> for LeafPoints<A, K> {
+    fn from(points: Vec<[A; K]>) -> Self {
+        let mut coords = Vec::with_capacity(points.len() * K);
+        for dim in 0..K {
+            coords.extend(points.iter().map(|p| p[dim]));
+        }
+        LeafPoints {
+            coords,
+            len: points.len(),
+            stride: points.len(),
+        }
+    }
+}
+
+/// An iterator over the points of a leaf, gathered from each axis
+pub struct LeafPointsIter<'a, A, const K: usize> {
+    points: &'a LeafPoints<A, K>,
+    idx: usize,
+}
+
+impl<'a, A: Copy, const K: usize> Iterator for LeafPointsIter<'a, A, K> {
+    type Item = [A; K];
+    fn next(&mut self) -> Option<[A; K]> {
+        if self.idx < self.points.len {
+            self.idx += 1;
+            Some(self.points.get(self.idx - 1))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.points.len - self.idx;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, A: Copy, const K: usize> ExactSizeIterator for LeafPointsIter<'a, A, K> {}
+
+// serialized as a list of points, as leaves were before they were stored by axis
+#[cfg(feature = "serialize")]
+impl<A: serde::Serialize + Copy, const K: usize> serde::Serialize for LeafPoints<A, K> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        vec_arrays::serialize(self.iter(), serializer)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'de, A: serde::Deserialize<'de> + Copy, const K: usize> serde::Deserialize<'de>
+    for LeafPoints<A, K>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        vec_arrays::deserialize(deserializer).map(LeafPoints::from)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ErrorKind {
     NonFiniteCoordinate,
     ZeroCapacity,
     Empty,
+    InvalidEpsilon,
 }
 
-impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T, K> {
+impl<A: Scalar, T: core::cmp::PartialEq, const K: usize> KdTree<A, T, K> {
     /// Creates a new KdTree with default capacity per node of 16
     ///
     /// # Examples
@@ -109,22 +339,187 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
     pub fn with_capacity(capacity: usize) -> Result<Self, ErrorKind> {
+        KdTree::with_split_strategy(capacity, SplitStrategy::default())
+    }
+
+    /// Creates a new KdTree with a specific capacity per node, whose leaves are split by
+    /// `split_strategy` when they overflow. See `SplitStrategy`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SplitStrategy};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> =
+    ///     KdTree::with_split_strategy(8, SplitStrategy::SlidingMidpoint)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn with_split_strategy(
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Result<Self, ErrorKind> {
         if capacity == 0 {
             return Err(ErrorKind::ZeroCapacity);
         }
 
         Ok(KdTree {
             size: 0,
-            min_bounds: [A::infinity(); K],
-            max_bounds: [A::neg_infinity(); K],
+            min_bounds: [A::highest(); K],
+            max_bounds: [A::lowest(); K],
             content: Node::Leaf {
-                points: Vec::with_capacity(capacity),
+                points: LeafPoints::with_capacity(capacity),
                 bucket: Vec::with_capacity(capacity),
                 capacity,
+                split_strategy,
             },
         })
     }
 
+    /// Creates a balanced KdTree from a set of points, with default capacity per node of 16.
+    ///
+    /// Unlike repeatedly calling `add()`, which by default splits leaves at the midpoint of
+    /// their widest dimension, the tree is built in one pass by splitting each node at the
+    /// median of its widest dimension. This keeps the tree shallow even on skewed data.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> = KdTree::from_points(points)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn from_points(points: Vec<([A; K], T)>) -> Result<Self, ErrorKind> {
+        KdTree::from_points_with_capacity(points, 16)
+    }
+
+    /// Creates a balanced KdTree from a set of points, with a specific capacity per node
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> = KdTree::from_points_with_capacity(points, 1)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.is_leaf(), false);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn from_points_with_capacity(
+        points: Vec<([A; K], T)>,
+        capacity: usize,
+    ) -> Result<Self, ErrorKind> {
+        KdTree::from_points_with_split_strategy(points, capacity, SplitStrategy::default())
+    }
+
+    /// Creates a balanced KdTree from a set of points, with a specific capacity per node,
+    /// whose leaves are split by `split_strategy` when they overflow as more points are
+    /// added. The tree is built by splitting at the median, along the dimension that
+    /// `split_strategy` chooses.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SplitStrategy};
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> =
+    ///     KdTree::from_points_with_split_strategy(points, 1, SplitStrategy::MaxVariance)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.split_strategy(), SplitStrategy::MaxVariance);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn from_points_with_split_strategy(
+        points: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Result<Self, ErrorKind> {
+        check_bulk_input(&points, capacity)?;
+
+        Ok(KdTree::build_balanced(points, capacity, split_strategy))
+    }
+
+    fn build_balanced(
+        entries: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Self {
+        KdTree::build_balanced_with(entries, capacity, split_strategy, |left, right| {
+            (
+                KdTree::build_balanced(left, capacity, split_strategy),
+                KdTree::build_balanced(right, capacity, split_strategy),
+            )
+        })
+    }
+
+    fn build_balanced_with<B>(
+        mut entries: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+        build_children: B,
+    ) -> Self
+    where
+        B: FnOnce(Vec<([A; K], T)>, Vec<([A; K], T)>) -> (Self, Self),
+    {
+        let (min_bounds, max_bounds) = bounds_of(entries.iter().map(|(point, _)| *point));
+        let size = entries.len();
+
+        let content = if size <= capacity {
+            KdTree::leaf_from_entries(entries, capacity, split_strategy)
+        } else if let Some((split_dimension, split_value, mid)) =
+            split_strategy.balanced_partition(&mut entries, &min_bounds, &max_bounds)
+        {
+            let right_entries = entries.split_off(mid);
+            let (left, right) = build_children(entries, right_entries);
+            Node::Stem {
+                left: Box::new(left),
+                right: Box::new(right),
+                split_value,
+                split_dimension: split_dimension as u8,
+            }
+        } else if widest_dimension(&min_bounds, &max_bounds).is_none() {
+            // every point is identical, so there is nothing to split on
+            let point = entries[0].0;
+            Node::Duplicates {
+                point,
+                bucket: entries.into_iter().map(|(_, item)| item).collect(),
+                capacity,
+                split_strategy,
+            }
+        } else {
+            KdTree::leaf_from_entries(entries, capacity, split_strategy)
+        };
+
+        KdTree {
+            size,
+            min_bounds,
+            max_bounds,
+            content,
+        }
+    }
+
+    fn leaf_from_entries(
+        entries: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Node<A, T, K> {
+        let (points, bucket) = entries.into_iter().unzip();
+        Node::Leaf {
+            points,
+            bucket,
+            capacity,
+            split_strategy,
+        }
+    }
+
     /// Returns the current number of elements stored in the tree
     ///
     /// # Examples
@@ -144,6 +539,46 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
         self.size
     }
 
+    /// Returns the number of elements each leaf node can hold before it is split
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let tree: KdTree<f64, usize, 3> = KdTree::with_capacity(8)?;
+    ///
+    /// assert_eq!(tree.capacity(), 8);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn capacity(&self) -> usize {
+        match &self.content {
+            Node::Leaf { capacity, .. } | Node::Duplicates { capacity, .. } => *capacity,
+            Node::Stem { left, .. } => left.capacity(),
+        }
+    }
+
+    /// Returns the strategy by which leaf nodes are split when they overflow
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SplitStrategy};
+    ///
+    /// let tree: KdTree<f64, usize, 3> = KdTree::with_capacity(8)?;
+    ///
+    /// assert_eq!(tree.split_strategy(), SplitStrategy::Midpoint);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn split_strategy(&self) -> SplitStrategy {
+        match &self.content {
+            Node::Leaf { split_strategy, .. } | Node::Duplicates { split_strategy, .. } => {
+                *split_strategy
+            }
+            Node::Stem { left, .. } => left.split_strategy(),
+        }
+    }
+
     /// Returns true if the node is a leaf node
     ///
     /// # Examples
@@ -168,7 +603,7 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// ```
     pub fn is_leaf(&self) -> bool {
         match &self.content {
-            Node::Leaf { .. } => true,
+            Node::Leaf { .. } | Node::Duplicates { .. } => true,
             Node::Stem { .. } => false,
         }
     }
@@ -194,55 +629,37 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// assert_eq!(*nearest[0].1, 100);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn nearest<F>(
+    pub fn nearest<D, F>(
         &self,
-        point: &[A],
+        point: &[A; K],
         num: usize,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        self.check_point_mod(point)?;
-
-        let num = std::cmp::min(num, self.size);
-        if num == 0 {
-            return Ok(vec![]);
-        }
-
         let mut pending = BinaryHeap::new();
-        let mut evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
+        let mut evaluated = BinaryHeap::new();
 
-        pending.push(HeapElement {
-            distance: A::zero(),
-            element: self,
-        });
-
-        while !pending.is_empty()
-            && (evaluated.len() < num
-                || (-pending.peek().unwrap().distance <= evaluated.peek().unwrap().distance))
-        {
-            self.nearest_step_no_dim(
-                point,
-                num,
-                A::infinity(),
-                distance,
-                &mut pending,
-                &mut evaluated,
-            );
-        }
+        self.nearest_impl(point, num, distance, &mut pending, &mut evaluated)?;
 
-        Ok(evaluated
-            .into_sorted_vec()
-            .into_iter()
-            .take(num)
-            .map(Into::into)
-            .collect())
+        Ok(drain_sorted(&mut evaluated))
     }
 
-    /// Queries the tree to find the nearest element to `point`, using the specified
-    /// distance metric function. Faster than querying for nearest(point, 1, ...) due
-    /// to not needing to allocate a Vec for the result
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, using
+    /// the specified distance metric function.
+    ///
+    /// The search skips any node that cannot hold an element closer than the `num`th
+    /// nearest found so far by more than a factor of `1 + epsilon`, so each result is at
+    /// most `1 + epsilon` times as far from `point` as the true neighbour of the same rank.
+    /// Distances are compared as the metric returns them, so with `squared_euclidean` the
+    /// factor applies to the squared distance. Metrics that return negative distances, such
+    /// as `InnerProduct`, get the same bound written without a ratio: no skipped element is
+    /// closer than `d - |d| * epsilon / (1 + epsilon)`, where `d` is the distance of the
+    /// result. An `epsilon` of zero gives the same results as `nearest()`, and larger values
+    /// visit fewer nodes. Returns `ErrorKind::InvalidEpsilon` if `epsilon` is negative or
+    /// not finite.
     ///
     /// # Examples
     ///
@@ -255,118 +672,112 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
     /// tree.add(&[2.0, 3.0, 6.0], 101)?;
     ///
-    /// let nearest = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    /// let nearest = tree.nearest_approx(&[1.0, 2.0, 5.1], 1, 0.5, &squared_euclidean)?;
     ///
-    /// assert!((nearest.0 - 0.01f64).abs() < f64::EPSILON);
-    /// assert_eq!(*nearest.1, 100);
+    /// assert_eq!(nearest.len(), 1);
+    /// assert!(nearest[0].0 <= 1.5 * 0.01);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    // TODO: pending only ever gets to about 7 items max. try doing this
-    //       recursively to avoid the alloc/dealloc of the vec
-    pub fn nearest_one<F>(&self, point: &[A; K], distance: &F) -> Result<(A, &T), ErrorKind>
-    where
-        F: Fn(&[A; K], &[A; K]) -> A,
-    {
-        if self.size == 0 {
-            return Err(ErrorKind::Empty);
-        }
-        self.check_point(point)?;
-
-        let mut pending = Vec::with_capacity(16);
-
-        let mut best_dist: A = A::infinity();
-        let mut best_elem: Option<&T> = None;
-
-        pending.push(HeapElement {
-            distance: A::zero(),
-            element: self,
-        });
-
-        while !pending.is_empty() && (best_elem.is_none() || (pending[0].distance > best_dist)) {
-            self.nearest_one_step(
-                point,
-                distance,
-                &mut pending,
-                &mut best_dist,
-                &mut best_elem,
-            );
-        }
-
-        Ok((best_dist, best_elem.unwrap()))
-    }
-
-    fn within_impl<F>(
+    pub fn nearest_approx<D, F>(
         &self,
         point: &[A; K],
-        radius: A,
+        num: usize,
+        epsilon: f64,
         distance: &F,
-    ) -> Result<BinaryHeap<HeapElement<A, &T>>, ErrorKind>
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A; K], &[A; K]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        self.check_point(point)?;
-
-        let mut pending = BinaryHeap::new();
-        let mut evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
-
-        pending.push(HeapElement {
-            distance: A::zero(),
-            element: self,
-        });
+        self.nearest_approx_with_budget(point, num, epsilon, usize::MAX, distance)
+    }
 
-        while !pending.is_empty() && (-pending.peek().unwrap().distance <= radius) {
-            self.nearest_step(
-                point,
-                self.size,
-                radius,
-                distance,
-                &mut pending,
-                &mut evaluated,
-            );
-        }
-
-        Ok(evaluated)
-    }
-
-    ////////////////////////////////////////////////////
-    fn within_unsorted_impl<F>(
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, as
+    /// `nearest_approx()` does, but gives up after searching `max_leaves_visited` leaf nodes
+    /// and returns the nearest elements found so far. This caps the time a query can take,
+    /// at the cost of the `1 + epsilon` guarantee: once the budget is spent, the results may
+    /// be further away than that, and there may be fewer than `num` of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    ///
+    /// let nearest = tree.nearest_approx_with_budget(&[1.0, 2.0, 5.1], 3, 0.0, 1, &squared_euclidean)?;
+    ///
+    /// assert!(nearest.len() < 3);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn nearest_approx_with_budget<D, F>(
         &self,
-        point: &[A],
-        radius: A,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        max_leaves_visited: usize,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>//Result<BinaryHeap<HeapElement<A, &T>>, ErrorKind>
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        self.check_point_mod(point)?;
-
         let mut pending = BinaryHeap::new();
-        let mut evaluated = Vec::with_capacity(100);
-
-        pending.push(HeapElement {
-            distance: A::zero(),
-            element: self,
-        });
+        let mut evaluated = BinaryHeap::new();
 
-        //println!("pending binary heap size: {}", pending.len());
+        self.nearest_approx_impl(
+            point,
+            num,
+            epsilon,
+            max_leaves_visited,
+            distance,
+            &mut pending,
+            &mut evaluated,
+        )?;
 
-        while !pending.is_empty() && (-pending.peek().unwrap().distance <= radius) {
-            self.nearest_step_mod(
-                point,
-                self.size,
-                radius,
-                distance,
-                &mut pending,
-                &mut evaluated,
-            );
-        }
+        Ok(drain_sorted(&mut evaluated))
+    }
 
-        Ok(evaluated)
+    /// Queries the tree to find the nearest element to `point`, using the specified
+    /// distance metric function. Faster than querying for nearest(point, 1, ...) due
+    /// to not needing to allocate a Vec for the result
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let nearest = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// assert!((nearest.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    // TODO: pending only ever gets to about 7 items max. try doing this
+    //       recursively to avoid the alloc/dealloc of the vec
+    pub fn nearest_one<D, F>(&self, point: &[A; K], distance: &F) -> Result<(D, &T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.nearest_one_impl(point, distance, &mut Vec::with_capacity(16))
     }
-    ////////////////////////////////////////////////////////////
-    /// 
-    /// Queries the tree to find all elements within `radius` of `point`, using the specified
-    /// distance metric function. Results are returned sorted nearest-first
+
+    /// Queries the tree to find approximately the nearest element to `point`, using the
+    /// specified distance metric function. The result is at most `1 + epsilon` times as far
+    /// from `point` as the nearest element. See `nearest_approx()`.
     ///
     /// # Examples
     ///
@@ -378,37 +789,29 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     ///
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
     /// tree.add(&[2.0, 3.0, 6.0], 101)?;
-    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
     ///
-    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    /// let nearest = tree.nearest_one_approx(&[1.0, 2.0, 5.1], 0.5, &squared_euclidean)?;
     ///
-    /// assert_eq!(within.len(), 2);
+    /// assert!(nearest.0 <= 1.5 * 0.01);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn within<F>(
+    pub fn nearest_one_approx<D, F>(
         &self,
         point: &[A; K],
-        radius: A,
+        epsilon: f64,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>
+    ) -> Result<(D, &T), ErrorKind>
     where
-        F: Fn(&[A; K], &[A; K]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        if self.size == 0 {
-            return Ok(vec![]);
-        }
-
-        self.within_impl(point, radius, distance).map(|evaluated| {
-            evaluated
-                .into_sorted_vec()
-                .into_iter()
-                .map(Into::into)
-                .collect()
-        })
+        self.nearest_one_approx_with_budget(point, epsilon, usize::MAX, distance)
     }
 
-    /// Queries the tree to find all elements within `radius` of `point`, using the specified
-    /// distance metric function. Results are returned in arbitrary order. Faster than within()
+    /// Queries the tree to find approximately the nearest element to `point`, as
+    /// `nearest_one_approx()` does, but gives up after searching `max_leaves_visited` leaf
+    /// nodes and returns the nearest element found so far. The search always continues
+    /// until it has found at least one element, even if that exceeds the budget.
     ///
     /// # Examples
     ///
@@ -416,37 +819,40 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// use kiddo::KdTree;
     /// use kiddo::distance::squared_euclidean;
     ///
-    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
     ///
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
     /// tree.add(&[2.0, 3.0, 6.0], 101)?;
-    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
     ///
-    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    /// let nearest = tree.nearest_one_approx_with_budget(&[1.0, 2.0, 5.1], 0.0, 1, &squared_euclidean)?;
     ///
-    /// assert_eq!(within.len(), 2);
+    /// assert_eq!(*nearest.1, 100);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn within_unsorted<F>(
+    pub fn nearest_one_approx_with_budget<D, F>(
         &self,
-        point: &[A],
-        radius: A,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
         distance: &F,
-    ) -> Result<Vec<(A, &T)>, ErrorKind>
+    ) -> Result<(D, &T), ErrorKind>
     where
-        F: Fn(&[A], &[A]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        if self.size == 0 {
-            return Ok(vec![]);
-        }
-
-        self.within_unsorted_impl(point, radius, distance)
-            //.map(|evaluated| evaluated.into_vec().into_iter().map(Into::into).collect())
+        self.nearest_one_approx_impl(
+            point,
+            epsilon,
+            max_leaves_visited,
+            distance,
+            &mut Vec::with_capacity(16),
+        )
     }
 
-    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
-    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
-    /// performing a comparison of the elements using < (ie, std::ord::lt)
+    /// Queries the tree to find the nearest element to each of `points`, using the specified
+    /// distance metric function. Results are returned in the same order as `points`. Faster
+    /// than calling nearest_one() for each point as allocations are reused between queries
     ///
     /// # Examples
     ///
@@ -457,57 +863,96 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
     ///
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
-    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
-    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
     ///
-    /// let best_n_within = tree.best_n_within(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean)?;
+    /// let nearest = tree.nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
     ///
-    /// assert_eq!(best_n_within[0], 1);
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn best_n_within<F>(
+    pub fn nearest_one_batch<D, F>(
         &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = Vec::with_capacity(16);
+
+        points
+            .iter()
+            .map(|point| self.nearest_one_impl(point, distance, &mut pending))
+            .collect()
+    }
+
+    fn nearest_one_impl<'b, D, F>(
+        &'b self,
         point: &[A; K],
-        radius: A,
-        max_qty: usize,
         distance: &F,
-    ) -> Result<Vec<T>, ErrorKind>
+        pending: &mut Vec<Reverse<HeapElement<D, &'b Self>>>,
+    ) -> Result<(D, &'b T), ErrorKind>
     where
-        F: Fn(&[A; K], &[A; K]) -> A,
-        T: Copy + Ord,
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.nearest_one_approx_impl(point, 0.0, usize::MAX, distance, pending)
+    }
+
+    fn nearest_one_approx_impl<'b, D, F>(
+        &'b self,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, &'b Self>>>,
+    ) -> Result<(D, &'b T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
         if self.size == 0 {
-            return Ok(vec![]);
+            return Err(ErrorKind::Empty);
         }
-
         self.check_point(point)?;
+        check_epsilon(epsilon)?;
 
-        let mut pending = Vec::with_capacity(max_qty);
-        let mut evaluated = BinaryHeap::<T>::new();
+        pending.clear();
+
+        let mut best_dist = D::highest();
+        let mut best_elem: Option<&T> = None;
+        let mut leaves_visited = 0;
 
-        pending.push(HeapElement {
-            distance: A::zero(),
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
             element: self,
-        });
+        }));
 
-        while !pending.is_empty() {
-            self.best_n_within_step(
+        // the budget only applies once something has been found, so that there is always
+        // an element to return
+        while !pending.is_empty() && (leaves_visited < max_leaves_visited || best_elem.is_none()) {
+            if self.nearest_one_step(
                 point,
-                self.size,
-                max_qty,
-                radius,
+                epsilon,
                 distance,
-                &mut pending,
-                &mut evaluated,
-            );
+                pending,
+                &mut best_dist,
+                &mut best_elem,
+            ) {
+                leaves_visited += 1;
+            }
         }
 
-        Ok(evaluated.into_vec().into_iter().collect())
+        Ok((best_dist, best_elem.unwrap()))
     }
 
-    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
-    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
-    /// performing a comparison of the elements using < (ie, std::ord::lt). Returns an iterator.
+    /// Queries the tree to find the nearest `num` elements to each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling nearest() for each point as allocations
+    /// are reused between queries
     ///
     /// # Examples
     ///
@@ -518,325 +963,353 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
     ///
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
-    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
-    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
     ///
-    /// let mut best_n_within_iter = tree.best_n_within_into_iter(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean);
-    /// let first = best_n_within_iter.next().unwrap();
+    /// let nearest = tree.nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
     ///
-    /// assert_eq!(first, 1);
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn best_n_within_into_iter<F>(
+    pub fn nearest_batch<D, F>(
         &self,
-        point: &[A; K],
-        radius: A,
-        max_qty: usize,
+        points: &[[A; K]],
+        num: usize,
         distance: &F,
-    ) -> impl Iterator<Item = T>
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
     where
-        F: Fn(&[A; K], &[A; K]) -> A,
-        T: Copy + Ord,
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        // if let Err(err) = self.check_point(point) {
-        //     return Err(err);
-        // }
-        // if self.size == 0 {
-        //     return std::iter::empty::<T>();
-        // }
-
-        let mut pending = Vec::with_capacity(max_qty);
-        let mut evaluated = BinaryHeap::<T>::new();
-
-        pending.push(HeapElement {
-            distance: A::zero(),
-            element: self,
-        });
-
-        while !pending.is_empty() {
-            self.best_n_within_step(
-                point,
-                self.size,
-                max_qty,
-                radius,
-                distance,
-                &mut pending,
-                &mut evaluated,
-            );
-        }
-
-        evaluated.into_iter()
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+
+        points
+            .iter()
+            .map(|point| {
+                self.nearest_impl(point, num, distance, &mut pending, &mut evaluated)?;
+                Ok(drain_sorted(&mut evaluated))
+            })
+            .collect()
     }
 
-    fn best_n_within_step<'b, F>(
-        &self,
+    fn nearest_impl<'b, D, F>(
+        &'b self,
         point: &[A; K],
-        _num: usize,
-        max_qty: usize,
-        max_dist: A,
+        num: usize,
         distance: &F,
-        pending: &mut Vec<HeapElement<A, &'b Self>>,
-        evaluated: &mut BinaryHeap<T>,
-    ) where
-        F: Fn(&[A; K], &[A; K]) -> A,
-        T: Copy + Ord,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, &'b Self>>>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T>>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        let curr = &mut &*pending.pop().unwrap().element;
-        <KdTree<A, T, K>>::populate_pending(point, max_dist, distance, pending, curr);
-
-        match &curr.content {
-            Node::Leaf { points, bucket, .. } => {
-                let points = points.iter();
-                let bucket = bucket.iter();
-                let iter = points.zip(bucket).map(|(p, d)| HeapElement {
-                    distance: distance(point, p),
-                    element: d,
-                });
-
-                for element in iter {
-                    if element <= max_dist {
-                        if evaluated.len() < max_qty {
-                            evaluated.push(*element.element);
-                        } else {
-                            let mut top = evaluated.peek_mut().unwrap();
-                            if element.element < &top {
-                                *top = *element.element;
-                            }
-                        }
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
-        }
+        self.nearest_approx_impl(point, num, 0.0, usize::MAX, distance, pending, evaluated)
     }
 
-    fn nearest_step<'b, F>(
-        &self,
+    #[allow(clippy::too_many_arguments)]
+    fn nearest_approx_impl<'b, D, F>(
+        &'b self,
         point: &[A; K],
         num: usize,
-        max_dist: A,
+        epsilon: f64,
+        max_leaves_visited: usize,
         distance: &F,
-        pending: &mut BinaryHeap<HeapElement<A, &'b Self>>,
-        evaluated: &mut BinaryHeap<HeapElement<A, &'b T>>,
-    ) where
-        F: Fn(&[A; K], &[A; K]) -> A,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, &'b Self>>>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T>>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        let curr = &mut &*pending.pop().unwrap().element;
-        <KdTree<A, T, K>>::populate_pending(point, max_dist, distance, pending, curr);
+        self.check_point(point)?;
+        check_epsilon(epsilon)?;
 
-        match &curr.content {
-            Node::Leaf { points, bucket, .. } => {
-                let points = points.iter();
-                let bucket = bucket.iter();
-                let iter = points.zip(bucket).map(|(p, d)| HeapElement {
-                    distance: distance(point, p),
-                    element: d,
-                });
+        pending.clear();
+        evaluated.clear();
 
-                for element in iter {
-                    if element <= max_dist {
-                        if evaluated.len() < num {
-                            evaluated.push(element);
-                        } else {
-                            //println!("else in nearest_step was triggerred");
-                            let mut top = evaluated.peek_mut().unwrap();
-                            if element < *top {
-                                *top = element;
-                            }
-                        }
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
+        let num = core::cmp::min(num, self.size);
+        if num == 0 {
+            return Ok(());
         }
-    }
-
-    fn nearest_step_no_dim<'b, F>(
-        &self,
-        point: &[A],
-        num: usize,
-        max_dist: A,
-        distance: &F,
-        pending: &mut BinaryHeap<HeapElement<A, &'b Self>>,
-        evaluated: &mut BinaryHeap<HeapElement<A, &'b T>>,
-    ) where
-        F: Fn(&[A], &[A]) -> A,
-    {
-        let curr = &mut &*pending.pop().unwrap().element;
-        <KdTree<A, T, K>>::populate_pending_mod(point, max_dist, distance, pending, curr);
 
-        match &curr.content {
-            Node::Leaf { points, bucket, .. } => {
-                let points = points.iter();
-                let bucket = bucket.iter();
-                let iter = points.zip(bucket).map(|(p, d)| HeapElement {
-                    distance: distance(point, p),
-                    element: d,
-                });
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self,
+        }));
 
-                for element in iter {
-                    if element <= max_dist {
-                        if evaluated.len() < num {
-                            evaluated.push(element);
-                        } else {
-                            //println!("else in nearest_step was triggerred");
-                            let mut top = evaluated.peek_mut().unwrap();
-                            if element < *top {
-                                *top = element;
-                            }
-                        }
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
+        let mut leaves_visited = 0;
+        while leaves_visited < max_leaves_visited
+            && !pending.is_empty()
+            && (evaluated.len() < num
+                || may_be_closer(
+                    pending.peek().unwrap().0.distance,
+                    evaluated.peek().unwrap().distance,
+                    epsilon,
+                ))
+        {
+            self.nearest_step(point, num, D::highest(), distance, pending, evaluated);
+            leaves_visited += 1;
         }
+
+        Ok(())
     }
 
-    fn nearest_step_mod<'b, F>(
-        &self,
-        point: &[A],
-        num: usize,
-        max_dist: A,
+    fn within_impl<'b, D, F>(
+        &'b self,
+        point: &[A; K],
+        radius: D,
         distance: &F,
-        pending: &mut BinaryHeap<HeapElement<A, &'b Self>>,
-        evaluated: &mut Vec<(A, &'b T)>,
-    ) where
-        F: Fn(&[A], &[A]) -> A,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, &'b Self>>>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T>>,
+    ) -> Result<(), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        let curr = &mut &*pending.pop().unwrap().element;
-        <KdTree<A, T, K>>::populate_pending_mod(point, max_dist, distance, pending, curr);
+        self.check_point(point)?;
 
-        match &curr.content {
-            Node::Leaf { points, bucket, .. } => {
-                let points = points.iter();
-                let bucket = bucket.iter();
-                let iter = points.zip(bucket).map(|(p, d)| (
-                    distance(point, p),
-                    d,
-                ));
-
-                for element in iter {
-                    // evaluated.push((element.0, element.1));
-                    if element.0 <= max_dist {
-                    //     if evaluated.len() < num {
-                    //         evaluated.push(*element.element);
-                    //     } else {
-                    //         //println!("else in nearest_step was triggerred");
-                    //         let mut top = evaluated.peek_mut().unwrap();
-                    //         if element < *top {
-                    //             *top = element;
-                    //         }
-                    //     }
-                        evaluated.push((element.0, element.1));
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
+        pending.clear();
+        evaluated.clear();
+
+        // the root is always visited, even by metrics that can be negative
+        pending.push(Reverse(HeapElement {
+            distance: D::lowest(),
+            element: self,
+        }));
+
+        while !pending.is_empty() && (pending.peek().unwrap().0.distance <= radius) {
+            self.nearest_step(point, self.size, radius, distance, pending, evaluated);
         }
+
+        Ok(())
     }
 
-    fn nearest_one_step<'b, F>(
+    fn within_unsorted_impl<D, F>(
         &self,
         point: &[A; K],
+        radius: D,
         distance: &F,
-        pending: &mut Vec<HeapElement<A, &'b Self>>,
-        best_dist: &mut A,
-        best_elem: &mut Option<&'b T>,
-    ) where
-        F: Fn(&[A; K], &[A; K]) -> A,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        let curr = &mut &*pending.pop().unwrap().element;
-        let evaluated_dist = *best_dist;
-        <KdTree<A, T, K>>::populate_pending(point, evaluated_dist, distance, pending, curr);
-
-        match &curr.content {
-            Node::Leaf { points, bucket, .. } => {
-                let points = points.iter();
-                let bucket = bucket.iter();
-                let iter = points.zip(bucket).map(|(p, d)| HeapElement {
-                    distance: distance(point, p),
-                    element: d,
-                });
+        self.check_point(point)?;
 
-                for element in iter {
-                    if best_elem.is_none() || element < *best_dist {
-                        *best_elem = Some(element.element);
-                        *best_dist = element.distance;
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = Vec::with_capacity(100);
+
+        // the root is always visited, even by metrics that can be negative
+        pending.push(Reverse(HeapElement {
+            distance: D::lowest(),
+            element: self,
+        }));
+
+        while !pending.is_empty() && (pending.peek().unwrap().0.distance <= radius) {
+            self.within_unsorted_step(point, radius, distance, &mut pending, &mut evaluated);
         }
+
+        Ok(evaluated)
     }
 
-    fn populate_pending_mod<'a, F>(
-        point: &[A],
-        max_dist: A,
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned sorted nearest-first
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
         distance: &F,
-        pending: &mut impl Stack<HeapElement<A, &'a Self>>,
-        curr: &mut &'a Self,
-    ) where
-        F: Fn(&[A], &[A]) -> A,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
     {
-        while let Node::Stem { left, right, .. } = &curr.content {
-            let candidate;
-            (candidate, *curr) = if curr.belongs_in_left_mod(point) {
-                (right, left)
-            } else {
-                (left, right)
-            };
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
 
-            let candidate_to_space = util::distance_to_space_mod(
-                point,
-                &candidate.min_bounds,
-                &candidate.max_bounds,
-                distance,
-            );
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
 
-            if candidate_to_space <= max_dist {
-                pending.stack_push(HeapElement {
-                    distance: candidate_to_space * -A::one(),
-                    element: &**candidate,
-                });
-            }
+        self.within_impl(point, radius, distance, &mut pending, &mut evaluated)?;
+
+        Ok(evaluated
+            .into_sorted_vec()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling within() for each point as allocations
+    /// are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let within = tree.within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let mut pending = BinaryHeap::new();
+        let mut evaluated = BinaryHeap::new();
+
+        points
+            .iter()
+            .map(|point| {
+                if self.size == 0 {
+                    return self.check_point(point).map(|_| vec![]);
+                }
+                self.within_impl(point, radius, distance, &mut pending, &mut evaluated)?;
+                Ok(drain_sorted(&mut evaluated))
+            })
+            .collect()
+    }
+
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. Faster than within()
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_unsorted<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        if self.size == 0 {
+            return Ok(vec![]);
         }
+
+        self.within_unsorted_impl(point, radius, distance)
     }
 
-    fn populate_pending<'a, F>(
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let best_n_within = tree.best_n_within(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(best_n_within[0], 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn best_n_within<D, F>(
+        &self,
         point: &[A; K],
-        max_dist: A,
+        radius: D,
+        max_qty: usize,
         distance: &F,
-        pending: &mut impl Stack<HeapElement<A, &'a Self>>,
-        curr: &mut &'a Self,
-    ) where
-        F: Fn(&[A; K], &[A; K]) -> A,
+    ) -> Result<Vec<T>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
     {
-        while let Node::Stem { left, right, .. } = &curr.content {
-            let candidate;
-            (candidate, *curr) = if curr.belongs_in_left(point) {
-                (right, left)
-            } else {
-                (left, right)
-            };
+        if self.size == 0 {
+            return Ok(vec![]);
+        }
+
+        self.check_point(point)?;
+
+        let mut pending = Vec::with_capacity(max_qty);
+        let mut evaluated = BinaryHeap::<T>::new();
 
-            let candidate_to_space = util::distance_to_space(
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self,
+        }));
+
+        while !pending.is_empty() {
+            self.best_n_within_step(
                 point,
-                &candidate.min_bounds,
-                &candidate.max_bounds,
+                max_qty,
+                radius,
                 distance,
+                &mut pending,
+                &mut evaluated,
             );
-
-            if candidate_to_space <= max_dist {
-                pending.stack_push(HeapElement {
-                    distance: candidate_to_space * -A::one(),
-                    element: &**candidate,
-                });
-            }
         }
+
+        Ok(evaluated.into_vec().into_iter().collect())
     }
-    
 
-    /// Returns an iterator over all elements in the tree, sorted nearest-first to the query point.
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt). Returns an iterator.
     ///
     /// # Examples
     ///
@@ -847,287 +1320,1586 @@ impl<A: Float + Zero + One, T: std::cmp::PartialEq, const K: usize> KdTree<A, T,
     /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
     ///
     /// tree.add(&[1.0, 2.0, 5.0], 100)?;
-    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
-    ///
-    /// let mut nearest_iter = tree.iter_nearest(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
     ///
-    /// let nearest_first = nearest_iter.next().unwrap();
+    /// let mut best_n_within_iter = tree.best_n_within_into_iter(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean);
+    /// let first = best_n_within_iter.next().unwrap();
     ///
-    /// assert!((nearest_first.0 - 0.01f64).abs() < f64::EPSILON);
-    /// assert_eq!(*nearest_first.1, 100);
+    /// assert_eq!(first, 1);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn iter_nearest<'a, 'b, F>(
-        &'b self,
-        point: &'a [A; K],
-        distance: &'a F,
-    ) -> Result<NearestIter<'a, 'b, A, T, F, K>, ErrorKind>
+    pub fn best_n_within_into_iter<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> impl Iterator<Item = T>
     where
-        F: Fn(&[A; K], &[A; K]) -> A,
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
     {
-        self.check_point(point)?;
+        // if let Err(err) = self.check_point(point) {
+        //     return Err(err);
+        // }
+        // if self.size == 0 {
+        //     return std::iter::empty::<T>();
+        // }
 
-        let mut pending = BinaryHeap::new();
-        let evaluated = BinaryHeap::<HeapElement<A, &T>>::new();
+        let mut pending = Vec::with_capacity(max_qty);
+        let mut evaluated = BinaryHeap::<T>::new();
 
-        pending.push(HeapElement {
-            distance: A::zero(),
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
             element: self,
+        }));
+
+        while !pending.is_empty() {
+            self.best_n_within_step(
+                point,
+                max_qty,
+                radius,
+                distance,
+                &mut pending,
+                &mut evaluated,
+            );
+        }
+
+        evaluated.into_iter()
+    }
+
+    fn best_n_within_step<D, F>(
+        &self,
+        point: &[A; K],
+        max_qty: usize,
+        max_dist: D,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, &Self>>>,
+        evaluated: &mut BinaryHeap<T>,
+    ) where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        let curr = &mut &*pending.pop().unwrap().0.element;
+        <KdTree<A, T, K>>::populate_pending(point, max_dist, distance, pending, curr);
+
+        curr.for_each_item_dist(point, distance, |dist, item| {
+            if dist <= max_dist {
+                if evaluated.len() < max_qty {
+                    evaluated.push(*item);
+                } else {
+                    let mut top = evaluated.peek_mut().unwrap();
+                    if *item < *top {
+                        *top = *item;
+                    }
+                }
+            }
         });
+    }
 
-        Ok(NearestIter {
-            point,
-            pending,
-            evaluated,
-            distance,
-        })
+    fn nearest_step<'b, D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        max_dist: D,
+        distance: &F,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, &'b Self>>>,
+        evaluated: &mut BinaryHeap<HeapElement<D, &'b T>>,
+    ) where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let curr = &mut &*pending.pop().unwrap().0.element;
+        <KdTree<A, T, K>>::populate_pending(point, max_dist, distance, pending, curr);
+
+        curr.for_each_item_dist(point, distance, |dist, item| {
+            let element = HeapElement {
+                distance: dist,
+                element: item,
+            };
+
+            if element <= max_dist {
+                if evaluated.len() < num {
+                    evaluated.push(element);
+                } else {
+                    let mut top = evaluated.peek_mut().unwrap();
+                    if element < *top {
+                        *top = element;
+                    }
+                }
+            }
+        });
+    }
+
+    fn within_unsorted_step<'b, D, F>(
+        &self,
+        point: &[A; K],
+        max_dist: D,
+        distance: &F,
+        pending: &mut BinaryHeap<Reverse<HeapElement<D, &'b Self>>>,
+        evaluated: &mut Vec<(D, &'b T)>,
+    ) where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let curr = &mut &*pending.pop().unwrap().0.element;
+        <KdTree<A, T, K>>::populate_pending(point, max_dist, distance, pending, curr);
+
+        curr.for_each_item_dist(point, distance, |dist, item| {
+            if dist <= max_dist {
+                evaluated.push((dist, item));
+            }
+        });
+    }
+
+    /// Visits the leaf nearest to `point` under the next pending node, returning false if
+    /// the node was skipped because it cannot hold anything closer than `best_dist`
+    fn nearest_one_step<'b, D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        distance: &F,
+        pending: &mut Vec<Reverse<HeapElement<D, &'b Self>>>,
+        best_dist: &mut D,
+        best_elem: &mut Option<&'b T>,
+    ) -> bool
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        let next = pending.pop().unwrap();
+        if best_elem.is_some() && !may_be_closer(next.0.distance, *best_dist, epsilon) {
+            // pending is a stack rather than a heap, so nodes found before best_dist
+            // shrank may now be too far away to contain anything closer
+            return false;
+        }
+        let curr = &mut &*next.0.element;
+        let evaluated_dist = *best_dist;
+        <KdTree<A, T, K>>::populate_pending(point, evaluated_dist, distance, pending, curr);
+
+        curr.for_each_item_dist(point, distance, |dist, item| {
+            if best_elem.is_none() || dist < *best_dist {
+                *best_elem = Some(item);
+                *best_dist = dist;
+            }
+        });
+        true
+    }
+
+    /// Measures the distance from `point` to each element of a leaf node, passing the
+    /// distance and the element to `visit`. The elements of a `Duplicates` node share one
+    /// point, so it is only measured once.
+    fn for_each_item_dist<'a, D, F, V>(&'a self, point: &[A; K], distance: &F, mut visit: V)
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        V: FnMut(D, &'a T),
+    {
+        match &self.content {
+            Node::Leaf { points, bucket, .. } => {
+                points.for_each_dist(point, distance, |idx, dist| visit(dist, &bucket[idx]));
+            }
+            Node::Duplicates {
+                point: duplicate,
+                bucket,
+                ..
+            } => {
+                let dist = distance.dist(point, duplicate);
+                for item in bucket {
+                    visit(dist, item);
+                }
+            }
+            Node::Stem { .. } => unreachable!(),
+        }
+    }
+
+    fn populate_pending<'a, D, F>(
+        point: &[A; K],
+        max_dist: D,
+        distance: &F,
+        pending: &mut impl Stack<Reverse<HeapElement<D, &'a Self>>>,
+        curr: &mut &'a Self,
+    ) where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        while let Node::Stem {
+            left,
+            right,
+            split_value,
+            split_dimension,
+        } = &curr.content
+        {
+            let candidate;
+            (candidate, *curr) = if curr.belongs_in_left(point) {
+                (right, left)
+            } else {
+                (left, right)
+            };
+
+            // everything in the candidate is at least as far away as the split plane
+            if distance.dist1(A::abs_diff(point[*split_dimension as usize], *split_value))
+                > max_dist
+            {
+                continue;
+            }
+
+            let candidate_to_space =
+                distance.dist_to_box(point, &candidate.min_bounds, &candidate.max_bounds);
+
+            if candidate_to_space <= max_dist {
+                pending.stack_push(Reverse(HeapElement {
+                    distance: candidate_to_space,
+                    element: &**candidate,
+                }));
+            }
+        }
+    }
+
+    /// Returns an iterator over all elements in the tree, sorted nearest-first to the query point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let mut nearest_iter = tree.iter_nearest(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// let nearest_first = nearest_iter.next().unwrap();
+    ///
+    /// assert!((nearest_first.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest_first.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn iter_nearest<'a, 'b, D, F>(
+        &'b self,
+        point: &'a [A; K],
+        distance: &'a F,
+    ) -> Result<NearestIter<'a, 'b, A, T, F, K, D>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.check_point(point)?;
+
+        let mut pending = BinaryHeap::new();
+        let evaluated = BinaryHeap::new();
+
+        pending.push(Reverse(HeapElement {
+            distance: D::zero(),
+            element: self,
+        }));
+
+        Ok(NearestIter {
+            point,
+            pending,
+            evaluated,
+            distance,
+        })
+    }
+
+    /// Queries the tree to find all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive. Results are returned in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let within = tree.within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within, vec![([1.0, 2.0, 5.0], &100)]);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn within_box(&self, min: &[A; K], max: &[A; K]) -> Result<Vec<([A; K], &T)>, ErrorKind> {
+        Ok(self.iter_within_box(min, max)?.collect())
+    }
+
+    /// Returns an iterator over all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive, in arbitrary order.
+    ///
+    /// Subtrees that lie entirely outside the box are skipped, and the elements of subtrees
+    /// that lie entirely inside it are returned without checking each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let mut within = tree.iter_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within.next(), Some(([1.0, 2.0, 5.0], &100)));
+    /// assert_eq!(within.next(), None);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn iter_within_box(
+        &self,
+        min: &[A; K],
+        max: &[A; K],
+    ) -> Result<WithinBoxIter<'_, A, T, K>, ErrorKind> {
+        self.check_point(min)?;
+        self.check_point(max)?;
+
+        Ok(WithinBoxIter {
+            min: *min,
+            max: *max,
+            pending: vec![(self, false)],
+            leaf: None,
+        })
+    }
+
+    /// Counts the elements within `radius` of `point`, using the specified distance
+    /// metric function, without collecting them.
+    ///
+    /// The elements of subtrees that lie entirely within `radius` are counted without
+    /// visiting each point, which assumes that the distance metric does not decrease as
+    /// the difference along an axis grows. This holds for all of the metrics in `distance`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let count = tree.count_within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(count, 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn count_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<usize, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.check_point(point)?;
+
+        let mut count = 0;
+        let mut pending = vec![self];
+        while let Some(node) = pending.pop() {
+            if node.size == 0
+                || distance.dist_to_box(point, &node.min_bounds, &node.max_bounds) > radius
+            {
+                continue;
+            }
+
+            if distance.max_dist_to_box(point, &node.min_bounds, &node.max_bounds) <= radius {
+                count += node.size;
+                continue;
+            }
+
+            match &node.content {
+                Node::Leaf { .. } | Node::Duplicates { .. } => {
+                    node.for_each_item_dist(point, distance, |dist, _| {
+                        if dist <= radius {
+                            count += 1;
+                        }
+                    });
+                }
+                Node::Stem { left, right, .. } => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Counts the elements inside the axis-aligned box with corners `min` and `max`,
+    /// inclusive, without collecting them. The elements of subtrees that lie entirely
+    /// inside the box are counted without visiting each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let count = tree.count_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(count, 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn count_within_box(&self, min: &[A; K], max: &[A; K]) -> Result<usize, ErrorKind> {
+        self.check_point(min)?;
+        self.check_point(max)?;
+
+        let mut count = 0;
+        let mut pending = vec![self];
+        while let Some(node) = pending.pop() {
+            if node.size == 0
+                || !util::bounds_intersect_box(&node.min_bounds, &node.max_bounds, min, max)
+            {
+                continue;
+            }
+
+            if util::bounds_within_box(&node.min_bounds, &node.max_bounds, min, max) {
+                count += node.size;
+                continue;
+            }
+
+            match &node.content {
+                Node::Leaf { points, .. } => {
+                    count += points
+                        .iter()
+                        .filter(|p| (0..K).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim]))
+                        .count();
+                }
+                Node::Duplicates { .. } => {
+                    // the bounds are the duplicated point, which intersects the box
+                    count += node.size;
+                }
+                Node::Stem { left, right, .. } => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Add an element to the tree. The first argument specifies the location in kd space
+    /// at which the element is located. The second argument is the data associated with
+    /// that point in space.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[1.1, 2.1, 5.1], 101)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn add(&mut self, point: &[A; K], data: T) -> Result<(), ErrorKind> {
+        self.check_point(point)?;
+        self.add_unchecked(point, data)
+    }
+
+    fn add_unchecked(&mut self, point: &[A; K], data: T) -> Result<(), ErrorKind> {
+        let res = match &mut self.content {
+            Node::Leaf { .. } | Node::Duplicates { .. } => {
+                self.add_to_bucket(point, data);
+                return Ok(());
+            }
+
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                split_dimension,
+                split_value,
+            } => {
+                if point[*split_dimension as usize] < *split_value {
+                    // belongs_in_left
+                    left.add_unchecked(point, data)
+                } else {
+                    right.add_unchecked(point, data)
+                }
+            }
+        };
+
+        self.extend(point);
+        self.size += 1;
+
+        res
+    }
+
+    fn add_to_bucket(&mut self, point: &[A; K], data: T) {
+        if let Node::Duplicates {
+            point: duplicate, ..
+        } = &self.content
+        {
+            if duplicate != point {
+                self.expand_duplicates();
+            }
+        }
+
+        self.extend(point);
+        let cap = match &mut self.content {
+            Node::Leaf {
+                ref mut points,
+                ref mut bucket,
+                capacity,
+                ..
+            } => {
+                points.push(*point);
+                bucket.push(data);
+                Some(*capacity)
+            }
+            Node::Duplicates { ref mut bucket, .. } => {
+                bucket.push(data);
+                None
+            }
+            Node::Stem { .. } => unreachable!(),
+        };
+
+        self.size += 1;
+        if cap.is_some_and(|cap| self.size > cap) {
+            self.split();
+        }
+    }
+
+    /// Turns a `Duplicates` node back into an ordinary leaf, with the duplicated point
+    /// stored once for each of its elements
+    fn expand_duplicates(&mut self) {
+        let empty = KdTree::leaf_from_entries(Vec::new(), 1, SplitStrategy::default());
+        if let Node::Duplicates {
+            point,
+            bucket,
+            capacity,
+            split_strategy,
+        } = core::mem::replace(&mut self.content, empty)
+        {
+            self.content = Node::Leaf {
+                points: vec![point; bucket.len()].into(),
+                bucket,
+                capacity,
+                split_strategy,
+            };
+        }
+    }
+
+    /// Removes all elements at `point` whose data is equal to `data`, returning the
+    /// number of elements removed.
+    ///
+    /// Bounds are shrunk to fit the remaining elements, and any stem whose elements
+    /// would fit in a single leaf is collapsed back into one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(1)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[1.1, 2.1, 5.1], 101)?;
+    ///
+    /// assert_eq!(tree.remove(&[1.1, 2.1, 5.1], &101)?, 1);
+    /// assert_eq!(tree.size(), 1);
+    /// assert_eq!(tree.is_leaf(), true);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn remove(&mut self, point: &[A; K], data: &T) -> Result<usize, ErrorKind> {
+        Ok(self.remove_where(point, |item| item == data)?.len())
+    }
+
+    /// Removes all elements at `point` for which `pred` returns true, returning
+    /// the removed items in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[1.0, 2.0, 5.0], 101)?;
+    /// tree.add(&[1.0, 2.0, 5.0], 102)?;
+    ///
+    /// let mut removed = tree.remove_where(&[1.0, 2.0, 5.0], |item| *item > 100)?;
+    /// removed.sort();
+    ///
+    /// assert_eq!(removed, vec![101, 102]);
+    /// assert_eq!(tree.size(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn remove_where<P>(&mut self, point: &[A; K], mut pred: P) -> Result<Vec<T>, ErrorKind>
+    where
+        P: FnMut(&T) -> bool,
+    {
+        self.check_point(point)?;
+
+        let mut removed = Vec::new();
+        self.remove_matching(
+            Some(point),
+            usize::MAX,
+            &mut |_, item| pred(item),
+            &mut removed,
+        );
+
+        Ok(removed.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Removes a single element at `point` whose data is equal to `data`, returning it,
+    /// or `None` if there is no such element.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    ///
+    /// assert_eq!(tree.remove_one(&[1.0, 2.0, 5.0], &100)?, Some(100));
+    /// assert_eq!(tree.remove_one(&[1.0, 2.0, 5.0], &101)?, None);
+    /// assert_eq!(tree.size(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn remove_one(&mut self, point: &[A; K], data: &T) -> Result<Option<T>, ErrorKind> {
+        self.check_point(point)?;
+
+        let mut removed = Vec::with_capacity(1);
+        self.remove_matching(Some(point), 1, &mut |_, item| item == data, &mut removed);
+
+        Ok(removed.pop().map(|(_, item)| item))
+    }
+
+    /// Retains only the elements for which `pred` returns true, returning the
+    /// removed elements in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let removed = tree.retain(|point, _| point[0] < 1.5);
+    ///
+    /// assert_eq!(removed, vec![([2.0, 3.0, 6.0], 101)]);
+    /// assert_eq!(tree.size(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn retain<P>(&mut self, mut pred: P) -> Vec<([A; K], T)>
+    where
+        P: FnMut(&[A; K], &T) -> bool,
+    {
+        let mut removed = Vec::new();
+        self.remove_matching(
+            None,
+            usize::MAX,
+            &mut |p, item| !pred(p, item),
+            &mut removed,
+        );
+
+        removed
+    }
+
+    /// Moves up to `max_qty` elements for which `pred` returns true into `removed`. If `point`
+    /// is given, only elements at exactly that point are considered, and only the one path
+    /// through the tree that could contain it is visited.
+    fn remove_matching<P>(
+        &mut self,
+        point: Option<&[A; K]>,
+        max_qty: usize,
+        pred: &mut P,
+        removed: &mut Vec<([A; K], T)>,
+    ) where
+        P: FnMut(&[A; K], &T) -> bool,
+    {
+        let removed_before = removed.len();
+
+        match &mut self.content {
+            Node::Leaf {
+                ref mut points,
+                ref mut bucket,
+                ..
+            } => {
+                let mut idx = 0;
+                while idx < points.len() && removed.len() < max_qty {
+                    let candidate = points.get(idx);
+                    if point.is_none_or(|p| candidate == *p) && pred(&candidate, &bucket[idx]) {
+                        removed.push((points.swap_remove(idx), bucket.swap_remove(idx)));
+                    } else {
+                        idx += 1;
+                    }
+                }
+
+                if removed.len() > removed_before {
+                    (self.min_bounds, self.max_bounds) = bounds_of(points.iter());
+                }
+            }
+            Node::Duplicates {
+                point: duplicate,
+                ref mut bucket,
+                ..
+            } => {
+                if point.is_none_or(|p| duplicate == p) {
+                    let mut idx = 0;
+                    while idx < bucket.len() && removed.len() < max_qty {
+                        if pred(duplicate, &bucket[idx]) {
+                            removed.push((*duplicate, bucket.swap_remove(idx)));
+                        } else {
+                            idx += 1;
+                        }
+                    }
+                }
+            }
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                split_value,
+                split_dimension,
+            } => match point {
+                Some(p) if p[*split_dimension as usize] < *split_value => {
+                    // belongs_in_left
+                    left.remove_matching(point, max_qty, pred, removed)
+                }
+                Some(_) => right.remove_matching(point, max_qty, pred, removed),
+                None => {
+                    left.remove_matching(point, max_qty, pred, removed);
+                    right.remove_matching(point, max_qty, pred, removed);
+                }
+            },
+        }
+
+        let removed_here = removed.len() - removed_before;
+        if removed_here > 0 {
+            self.size -= removed_here;
+            self.shrink();
+        }
+    }
+
+    /// Moves the element at `old_point` whose data is equal to `data` to `new_point`,
+    /// returning false if there is no such element.
+    ///
+    /// If `new_point` belongs in the same leaf as `old_point`, the element is updated in
+    /// place without restructuring the tree. Otherwise, it is removed and added again.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// assert!(tree.update_position(&[1.0, 2.0, 5.0], &[3.0, 4.0, 7.0], &100)?);
+    ///
+    /// let nearest = tree.nearest_one(&[3.0, 4.0, 7.0], &squared_euclidean)?;
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn update_position(
+        &mut self,
+        old_point: &[A; K],
+        new_point: &[A; K],
+        data: &T,
+    ) -> Result<bool, ErrorKind> {
+        self.check_point(old_point)?;
+        self.check_point(new_point)?;
+
+        match self.move_within_leaf(old_point, new_point, data) {
+            Some(moved) => Ok(moved),
+            None => match self.remove_one(old_point, data)? {
+                Some(item) => {
+                    self.add_unchecked(new_point, item)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+        }
+    }
+
+    /// Moves the element in place if `old_point` and `new_point` belong in the same leaf,
+    /// returning whether it was found. Returns `None` if they belong in different leaves.
+    fn move_within_leaf(
+        &mut self,
+        old_point: &[A; K],
+        new_point: &[A; K],
+        data: &T,
+    ) -> Option<bool> {
+        let moved = match &mut self.content {
+            Node::Leaf {
+                ref mut points,
+                ref bucket,
+                ..
+            } => match points
+                .iter()
+                .zip(bucket.iter())
+                .position(|(p, item)| p == *old_point && item == data)
+            {
+                Some(idx) => {
+                    points.set(idx, *new_point);
+                    true
+                }
+                None => false,
+            },
+            Node::Duplicates {
+                point: duplicate,
+                bucket,
+                ..
+            } => {
+                if duplicate != old_point || !bucket.contains(data) {
+                    false
+                } else if new_point == old_point {
+                    true
+                } else {
+                    // the element no longer shares the duplicated point
+                    return None;
+                }
+            }
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                split_value,
+                split_dimension,
+            } => {
+                let dim = *split_dimension as usize;
+                // belongs_in_left
+                match (old_point[dim] < *split_value, new_point[dim] < *split_value) {
+                    (true, true) => left.move_within_leaf(old_point, new_point, data)?,
+                    (false, false) => right.move_within_leaf(old_point, new_point, data)?,
+                    _ => return None,
+                }
+            }
+        };
+
+        if moved {
+            self.fit_bounds();
+        }
+
+        Some(moved)
+    }
+
+    /// Returns a mutable reference to the data of the element at `point` that is equal
+    /// to `data`, or `None` if there is no such element.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, (usize, u32), 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], (100, 0))?;
+    ///
+    /// if let Some(item) = tree.get_mut(&[1.0, 2.0, 5.0], &(100, 0)) {
+    ///     item.1 += 1;
+    /// }
+    ///
+    /// assert!(tree.get_mut(&[1.0, 2.0, 5.0], &(100, 1)).is_some());
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn get_mut(&mut self, point: &[A; K], data: &T) -> Option<&mut T> {
+        match &mut self.content {
+            Node::Leaf {
+                ref points,
+                ref mut bucket,
+                ..
+            } => points
+                .iter()
+                .zip(bucket.iter_mut())
+                .find(|(p, item)| p == point && *item == data)
+                .map(|(_, item)| item),
+            Node::Duplicates {
+                point: duplicate,
+                ref mut bucket,
+                ..
+            } => {
+                if duplicate == point {
+                    bucket.iter_mut().find(|item| *item == data)
+                } else {
+                    None
+                }
+            }
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                split_value,
+                split_dimension,
+            } => {
+                if point[*split_dimension as usize] < *split_value {
+                    // belongs_in_left
+                    left.get_mut(point, data)
+                } else {
+                    right.get_mut(point, data)
+                }
+            }
+        }
+    }
+
+    /// Rebuilds any regions of the tree that have become unbalanced, for example after
+    /// adding points in sorted order or removing most of the points on one side of a split.
+    /// Unbalanced regions are rebuilt by splitting at the median, as with `from_points()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 1> = KdTree::with_capacity(2)?;
+    ///
+    /// for i in 0..100 {
+    ///     tree.add(&[2f64.powi(-i)], i as usize)?;
+    /// }
+    ///
+    /// tree.rebalance();
+    ///
+    /// assert_eq!(tree.size(), 100);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn rebalance(&mut self) {
+        let capacity = self.capacity();
+        let split_strategy = self.split_strategy();
+        self.rebalance_with(capacity, split_strategy);
+    }
+
+    fn rebalance_with(&mut self, capacity: usize, split_strategy: SplitStrategy) {
+        let balanced = match &mut self.content {
+            Node::Leaf { .. } | Node::Duplicates { .. } => return,
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                ..
+            } => {
+                // a subtree is rebuilt when one side holds over three quarters of its points
+                let larger = left.size.max(right.size);
+                if self.size > capacity && larger * 4 <= self.size * 3 {
+                    left.rebalance_with(capacity, split_strategy);
+                    right.rebalance_with(capacity, split_strategy);
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if !balanced {
+            self.rebuild(capacity, split_strategy);
+        }
+    }
+
+    /// Shrinks the bounds of a stem to fit its children after elements have been removed
+    /// from them. A stem or `Duplicates` node whose elements fit in a single leaf is
+    /// collapsed into one, and a stem with an empty child is replaced by its other child.
+    fn shrink(&mut self) {
+        let capacity = self.capacity();
+        match &self.content {
+            Node::Leaf { .. } => return,
+            Node::Duplicates { .. } if self.size > capacity => return,
+            _ => {}
+        }
+        let split_strategy = self.split_strategy();
+        if self.size <= capacity {
+            self.rebuild(capacity, split_strategy);
+            return;
+        }
+
+        let empty = KdTree::leaf_from_entries(Vec::new(), capacity, split_strategy);
+        match core::mem::replace(&mut self.content, empty) {
+            Node::Stem { left, right, .. } if left.size == 0 => *self = *right,
+            Node::Stem { left, right, .. } if right.size == 0 => *self = *left,
+            content => {
+                self.content = content;
+                self.fit_bounds();
+            }
+        }
+    }
+
+    /// Shrinks the bounds of a node to fit its points, or the bounds of its children
+    fn fit_bounds(&mut self) {
+        (self.min_bounds, self.max_bounds) = match &self.content {
+            Node::Leaf { points, .. } => bounds_of(points.iter()),
+            Node::Duplicates { point, bucket, .. } => {
+                bounds_of(bucket.iter().take(1).map(|_| *point))
+            }
+            Node::Stem { left, right, .. } => bounds_of(
+                [left, right]
+                    .iter()
+                    .filter(|child| child.size > 0)
+                    .flat_map(|child| [child.min_bounds, child.max_bounds]),
+            ),
+        };
+    }
+
+    fn rebuild(&mut self, capacity: usize, split_strategy: SplitStrategy) {
+        let mut entries = Vec::with_capacity(self.size);
+        self.drain_into(&mut entries);
+        *self = KdTree::build_balanced(entries, capacity, split_strategy);
+    }
+
+    fn drain_into(&mut self, entries: &mut Vec<([A; K], T)>) {
+        match &mut self.content {
+            Node::Leaf {
+                ref mut points,
+                ref mut bucket,
+                ..
+            } => entries.extend(points.drain().zip(bucket.drain(..))),
+            Node::Duplicates {
+                point,
+                ref mut bucket,
+                ..
+            } => entries.extend(bucket.drain(..).map(|item| (*point, item))),
+            Node::Stem {
+                ref mut left,
+                ref mut right,
+                ..
+            } => {
+                left.drain_into(entries);
+                right.drain_into(entries);
+            }
+        }
+    }
+
+    /// Splits an overflowing leaf into a stem with two non-empty leaves. A leaf whose points
+    /// are all identical becomes a `Duplicates` node instead, and a leaf whose points the
+    /// split strategy cannot separate is left over capacity.
+    fn split(&mut self) {
+        if widest_dimension(&self.min_bounds, &self.max_bounds).is_none() {
+            // every point is identical, so there is nothing to split on
+            self.collapse_duplicates();
+            return;
+        }
+        if !self
+            .split_strategy()
+            .can_split(&self.min_bounds, &self.max_bounds)
+        {
+            return;
+        }
+
+        match &mut self.content {
+            Node::Leaf {
+                ref mut bucket,
+                ref mut points,
+                capacity,
+                split_strategy,
+            } => {
+                let mut entries: Vec<_> = points.drain().zip(bucket.drain(..)).collect();
+                let split =
+                    split_strategy.partition(&mut entries, &self.min_bounds, &self.max_bounds);
+
+                if let Some((split_dimension, split_value, left_len)) = split {
+                    let mut left =
+                        Box::new(KdTree::with_split_strategy(*capacity, *split_strategy).unwrap());
+                    let mut right =
+                        Box::new(KdTree::with_split_strategy(*capacity, *split_strategy).unwrap());
+
+                    let right_entries = entries.split_off(left_len);
+                    for (point, data) in entries {
+                        left.add_to_bucket(&point, data);
+                    }
+                    for (point, data) in right_entries {
+                        right.add_to_bucket(&point, data);
+                    }
+
+                    self.content = Node::Stem {
+                        left,
+                        right,
+                        split_value,
+                        split_dimension: split_dimension as u8,
+                    }
+                } else {
+                    for (point, data) in entries {
+                        points.push(point);
+                        bucket.push(data);
+                    }
+                }
+            }
+            Node::Duplicates { .. } | Node::Stem { .. } => unreachable!(),
+        }
+    }
+
+    /// Turns a leaf whose points are all identical into a `Duplicates` node, so that the
+    /// point is stored once and adding more of it does not try to split the leaf again
+    fn collapse_duplicates(&mut self) {
+        let empty = KdTree::leaf_from_entries(Vec::new(), 1, SplitStrategy::default());
+        if let Node::Leaf {
+            points,
+            bucket,
+            capacity,
+            split_strategy,
+        } = core::mem::replace(&mut self.content, empty)
+        {
+            self.content = Node::Duplicates {
+                point: points.get(0),
+                bucket,
+                capacity,
+                split_strategy,
+            };
+        }
+    }
+
+    fn belongs_in_left(&self, point: &[A; K]) -> bool {
+        match &self.content {
+            Node::Stem {
+                ref split_dimension,
+                ref split_value,
+                ..
+            } => point[*split_dimension as usize] < *split_value,
+            Node::Leaf { .. } | Node::Duplicates { .. } => unreachable!(),
+        }
+    }
+
+    fn extend(&mut self, point: &[A; K]) {
+        let min = self.min_bounds.iter_mut();
+        let max = self.max_bounds.iter_mut();
+        for ((l, h), v) in min.zip(max).zip(point.iter()) {
+            if v < l {
+                *l = *v
+            }
+            if v > h {
+                *h = *v
+            }
+        }
+    }
+
+    fn check_point(&self, point: &[A; K]) -> Result<(), ErrorKind> {
+        util::check_point(point)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<A, T, const K: usize> KdTree<A, T, K>
+where
+    A: Scalar + Send,
+    T: core::cmp::PartialEq + Send,
+{
+    /// Creates a balanced KdTree from a set of points using all available threads, with default
+    /// capacity per node of 16. The resulting tree is identical to the one built by `from_points()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> = KdTree::par_from_points(points)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_from_points(points: Vec<([A; K], T)>) -> Result<Self, ErrorKind> {
+        KdTree::par_from_points_with_capacity(points, 16)
+    }
+
+    /// Creates a balanced KdTree from a set of points using all available threads, with a specific
+    /// capacity per node. The left and right subtrees of each stem are built concurrently.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> = KdTree::par_from_points_with_capacity(points, 1)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.is_leaf(), false);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_from_points_with_capacity(
+        points: Vec<([A; K], T)>,
+        capacity: usize,
+    ) -> Result<Self, ErrorKind> {
+        KdTree::par_from_points_with_split_strategy(points, capacity, SplitStrategy::default())
+    }
+
+    /// Creates a balanced KdTree from a set of points using all available threads, with a
+    /// specific capacity per node, whose leaves are split by `split_strategy` when they
+    /// overflow as more points are added. The resulting tree is identical to the one built by
+    /// `from_points_with_split_strategy()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SplitStrategy};
+    ///
+    /// let points = vec![([1.0, 2.0, 5.0], 100), ([1.1, 2.1, 5.1], 101)];
+    /// let tree: KdTree<f64, usize, 3> =
+    ///     KdTree::par_from_points_with_split_strategy(points, 1, SplitStrategy::MaxVariance)?;
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.split_strategy(), SplitStrategy::MaxVariance);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_from_points_with_split_strategy(
+        points: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Result<Self, ErrorKind> {
+        check_bulk_input(&points, capacity)?;
+
+        Ok(KdTree::par_build_balanced(points, capacity, split_strategy))
+    }
+
+    fn par_build_balanced(
+        entries: Vec<([A; K], T)>,
+        capacity: usize,
+        split_strategy: SplitStrategy,
+    ) -> Self {
+        // below this size, the cost of handing work to another thread outweighs the gain
+        const SERIAL_THRESHOLD: usize = 4096;
+
+        if entries.len() <= SERIAL_THRESHOLD {
+            return KdTree::build_balanced(entries, capacity, split_strategy);
+        }
+
+        KdTree::build_balanced_with(entries, capacity, split_strategy, |left, right| {
+            rayon::join(
+                || KdTree::par_build_balanced(left, capacity, split_strategy),
+                || KdTree::par_build_balanced(right, capacity, split_strategy),
+            )
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<A, T, const K: usize> KdTree<A, T, K>
+where
+    A: Scalar + Send + Sync,
+    T: core::cmp::PartialEq + Sync,
+{
+    /// Queries the tree to find the nearest element to each of `points` using all available
+    /// threads. Results are returned in the same order as `points`. See `nearest_one()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let nearest = tree.par_nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &T)>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || Vec::with_capacity(16),
+                |pending, point| self.nearest_one_impl(point, distance, pending),
+            )
+            .collect()
+    }
+
+    /// Queries the tree to find the nearest `num` elements to each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `nearest()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let nearest = tree.par_nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || (BinaryHeap::new(), BinaryHeap::new()),
+                |(pending, evaluated), point| {
+                    self.nearest_impl(point, num, distance, pending, evaluated)?;
+                    Ok(drain_sorted(evaluated))
+                },
+            )
+            .collect()
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `within()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::distance::squared_euclidean;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let within = tree.par_within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn par_within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        use rayon::prelude::*;
+
+        points
+            .par_iter()
+            .map_init(
+                || (BinaryHeap::new(), BinaryHeap::new()),
+                |(pending, evaluated), point| {
+                    if self.size == 0 {
+                        return self.check_point(point).map(|_| vec![]);
+                    }
+                    self.within_impl(point, radius, distance, pending, evaluated)?;
+                    Ok(drain_sorted(evaluated))
+                },
+            )
+            .collect()
     }
+}
 
-    /// Add an element to the tree. The first argument specifies the location in kd space
-    /// at which the element is located. The second argument is the data associated with
-    /// that point in space.
+impl<T: core::cmp::PartialEq, const K: usize> KdTree<f32, T, K> {
+    /// Queries the tree to find the `num` elements with the greatest inner product with
+    /// `query`. For unit vectors, this is their cosine similarity. Results are returned
+    /// sorted most similar first, along with their similarity
     ///
     /// # Examples
     ///
     /// ```rust
     /// use kiddo::KdTree;
     ///
-    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
     ///
-    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
-    /// tree.add(&[1.1, 2.1, 5.1], 101)?;
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
     ///
-    /// assert_eq!(tree.size(), 2);
+    /// let similar = tree.most_similar(&[0.0, 1.0], 2)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101), (0.0, &100)]);
     /// # Ok::<(), kiddo::ErrorKind>(())
     /// ```
-    pub fn add(&mut self, point: &[A; K], data: T) -> Result<(), ErrorKind> {
-        self.check_point(&point)?;
-        self.add_unchecked(point, data)
+    pub fn most_similar(&self, query: &[f32; K], num: usize) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        let nearest = self.nearest(query, num, &InnerProduct)?;
+        Ok(nearest
+            .into_iter()
+            .map(|(dist, item)| (-dist, item))
+            .collect())
     }
 
-    fn add_unchecked(&mut self, point: &[A; K], data: T) -> Result<(), ErrorKind> {
-        let res = match &mut self.content {
-            Node::Leaf { .. } => {
-                self.add_to_bucket(&point, data);
-                return Ok(());
-            }
-
-            Node::Stem {
-                ref mut left,
-                ref mut right,
-                split_dimension,
-                split_value,
-            } => {
-                if point[*split_dimension as usize] < *split_value {
-                    // belongs_in_left
-                    left.add_unchecked(point, data)
-                } else {
-                    right.add_unchecked(point, data)
-                }
-            }
-        };
-
-        self.extend(&point);
-        self.size += 1;
-
-        res
+    /// Queries the tree to find all elements whose inner product with `query` is at least
+    /// `min_similarity`. For unit vectors, this is their cosine similarity. Results are
+    /// returned sorted most similar first, along with their similarity
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
+    ///
+    /// let similar = tree.similar_within(&[0.0, 1.0], 0.5)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101)]);
+    /// # Ok::<(), kiddo::ErrorKind>(())
+    /// ```
+    pub fn similar_within(
+        &self,
+        query: &[f32; K],
+        min_similarity: f32,
+    ) -> Result<Vec<(f32, &T)>, ErrorKind> {
+        let within = self.within(query, -min_similarity, &InnerProduct)?;
+        Ok(within
+            .into_iter()
+            .map(|(dist, item)| (-dist, item))
+            .collect())
     }
+}
 
-    fn add_to_bucket(&mut self, point: &[A; K], data: T) {
-        self.extend(&point);
-        let cap;
-        match &mut self.content {
-            Node::Leaf {
-                ref mut points,
-                ref mut bucket,
-                capacity,
-            } => {
-                points.push(*point);
-                bucket.push(data);
-                cap = *capacity;
-            }
-            Node::Stem { .. } => unreachable!(),
-        }
-
-        self.size += 1;
-        if self.size > cap {
-            self.split();
-        }
+impl<A: Scalar, T: core::cmp::PartialEq, const K: usize> Default for KdTree<A, T, K> {
+    /// Creates a new KdTree with default capacity per node of 16. See `KdTree::new`.
+    fn default() -> Self {
+        KdTree::new()
     }
+}
 
-    pub fn remove(&mut self, point: &[A; K], data: &T) -> Result<usize, ErrorKind> {
-        let mut removed = 0;
-        self.check_point(point)?;
-
-        match &mut self.content {
-            Node::Leaf {
-                ref mut points,
-                ref mut bucket,
-                ..
-            } => {
-                while let Some(p_index) = points.iter().position(|x| x == point) {
-                    if &bucket[p_index] == data {
-                        points.remove(p_index);
-                        bucket.remove(p_index);
-                        removed += 1;
-                        self.size -= 1;
-                    }
-                }
-            }
-            Node::Stem {
-                ref mut left,
-                ref mut right,
-                ..
-            } => {
-                let right_removed = right.remove(point, data)?;
-                if right_removed > 0 {
-                    self.size -= right_removed;
-                    removed += right_removed;
-                }
-
-                let left_removed = left.remove(point, data)?;
-                if left_removed > 0 {
-                    self.size -= left_removed;
-                    removed += left_removed;
-                }
-            }
-        }
-
-        Ok(removed)
+impl<A: Scalar, T: core::cmp::PartialEq, const K: usize> FromIterator<([A; K], T)>
+    for KdTree<A, T, K>
+{
+    /// Builds a balanced tree with the default capacity per node of 16. See `KdTree::from_points`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the points has a non-finite coordinate.
+    fn from_iter<I: IntoIterator<Item = ([A; K], T)>>(iter: I) -> Self {
+        KdTree::from_points(iter.into_iter().collect())
+            .expect("cannot build a KdTree from points with non-finite coordinates")
     }
+}
 
-    fn split(&mut self) {
-        match &mut self.content {
-            Node::Leaf {
-                ref mut bucket,
-                ref mut points,
-                capacity,
-                ..
-            } => {
-                let mut split_dimension: Option<usize> = None;
-                let mut max = A::zero();
-                for dim in 0..K {
-                    let diff = self.max_bounds[dim] - self.min_bounds[dim];
-                    if !diff.is_nan() && diff > max {
-                        max = diff;
-                        split_dimension = Some(dim);
-                    }
-                }
-
-                if let Some(split_dimension) = split_dimension {
-                    let min = self.min_bounds[split_dimension];
-                    let max = self.max_bounds[split_dimension];
-                    let split_value = min + (max - min) / A::from(2.0).unwrap();
-
-                    let mut left = Box::new(KdTree::with_capacity(*capacity).unwrap());
-                    let mut right = Box::new(KdTree::with_capacity(*capacity).unwrap());
-
-                    while !points.is_empty() {
-                        let point = points.swap_remove(0);
-                        let data = bucket.swap_remove(0);
-                        if point[split_dimension] < split_value {
-                            // belongs_in_left
-                            left.add_to_bucket(&point, data);
-                        } else {
-                            right.add_to_bucket(&point, data);
-                        }
-                    }
-
-                    self.content = Node::Stem {
-                        left,
-                        right,
-                        split_value,
-                        split_dimension: split_dimension as u8,
-                    }
-                }
-            }
-            Node::Stem { .. } => unreachable!(),
-        }
+/// Checks that an approximate query's `epsilon` is finite and non-negative
+pub(crate) fn check_epsilon(epsilon: f64) -> Result<(), ErrorKind> {
+    if epsilon.is_finite() && epsilon >= 0.0 {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidEpsilon)
     }
+}
 
-    fn belongs_in_left(&self, point: &[A; K]) -> bool {
-        match &self.content {
-            Node::Stem {
-                ref split_dimension,
-                ref split_value,
-                ..
-            } => point[*split_dimension as usize] < *split_value,
-            Node::Leaf { .. } => unreachable!(),
-        }
+/// Returns true if a node `node_dist` away may hold an element enough closer than
+/// `best_dist` to be worth visiting: any closer for an exact query, or closer by more than
+/// `|best_dist| * epsilon / (1 + epsilon)` for an approximate one. For a positive
+/// `best_dist` that is the same as dividing it by `1 + epsilon`, but unlike a division it
+/// still tightens the threshold when distances are negative, as with `InnerProduct`.
+/// The threshold is computed in the distance type, so integer distances too large for an
+/// `f64` to hold exactly are still compared exactly.
+pub(crate) fn may_be_closer<D: Scalar>(node_dist: D, best_dist: D, epsilon: f64) -> bool {
+    if epsilon == 0.0 {
+        return node_dist <= best_dist;
     }
+    node_dist <= D::sub_fraction(best_dist, epsilon / (1.0 + epsilon))
+}
 
-    fn belongs_in_left_mod(&self, point: &[A]) -> bool {
-        match &self.content {
-            Node::Stem {
-                ref split_dimension,
-                ref split_value,
-                ..
-            } => point[*split_dimension as usize] < *split_value,
-            Node::Leaf { .. } => unreachable!(),
-        }
+/// Empties `evaluated` into a Vec, sorted nearest-first, leaving its allocation to be reused
+pub(crate) fn drain_sorted<D: PartialOrd, T>(
+    evaluated: &mut BinaryHeap<HeapElement<D, T>>,
+) -> Vec<(D, T)> {
+    let mut sorted: Vec<_> = evaluated.drain().collect();
+    sorted.sort();
+    sorted.into_iter().map(Into::into).collect()
+}
+
+/// Checks the input to a bulk build: the capacity must be non-zero, and every point valid
+fn check_bulk_input<A: Scalar, T, const K: usize>(
+    points: &[([A; K], T)],
+    capacity: usize,
+) -> Result<(), ErrorKind> {
+    if capacity == 0 {
+        return Err(ErrorKind::ZeroCapacity);
     }
+    points
+        .iter()
+        .try_for_each(|(point, _)| util::check_point(point))
+}
 
-    fn extend(&mut self, point: &[A; K]) {
-        let min = self.min_bounds.iter_mut();
-        let max = self.max_bounds.iter_mut();
-        for ((l, h), v) in min.zip(max).zip(point.iter()) {
-            if v < l {
-                *l = *v
+fn bounds_of<A: Scalar, const K: usize>(points: impl Iterator<Item = [A; K]>) -> ([A; K], [A; K]) {
+    let mut min_bounds = [A::highest(); K];
+    let mut max_bounds = [A::lowest(); K];
+    for point in points {
+        for dim in 0..K {
+            if point[dim] < min_bounds[dim] {
+                min_bounds[dim] = point[dim];
             }
-            if v > h {
-                *h = *v
+            if point[dim] > max_bounds[dim] {
+                max_bounds[dim] = point[dim];
             }
         }
     }
-
-    fn check_point(&self, point: &[A; K]) -> Result<(), ErrorKind> {
-        if point.iter().all(|n| n.is_finite()) {
-            Ok(())
-        } else {
-            Err(ErrorKind::NonFiniteCoordinate)
-        }
-    }
-
-    fn check_point_mod(&self, point: &[A]) -> Result<(), ErrorKind> {
-        if point.iter().all(|n| n.is_finite()) {
-            Ok(())
-        } else {
-            Err(ErrorKind::NonFiniteCoordinate)
-        }
-    }
+    (min_bounds, max_bounds)
 }
 
 pub struct NearestIter<
     'a,
     'b,
-    A: 'a + 'b + Float,
+    A: 'a + 'b + Scalar,
     T: 'b + PartialEq,
-    F: 'a + Fn(&[A; K], &[A; K]) -> A,
+    F: 'a + Distance<A, K, D>,
     const K: usize,
+    D: Scalar = A,
 > {
     point: &'a [A; K],
-    pending: BinaryHeap<HeapElement<A, &'b KdTree<A, T, K>>>,
-    evaluated: BinaryHeap<HeapElement<A, &'b T>>,
+    pending: BinaryHeap<Reverse<HeapElement<D, &'b KdTree<A, T, K>>>>,
+    evaluated: BinaryHeap<Reverse<HeapElement<D, &'b T>>>,
     distance: &'a F,
 }
 
-impl<'a, 'b, A: Float + Zero + One, T: 'b, F: 'a, const K: usize> Iterator
-    for NearestIter<'a, 'b, A, T, F, K>
+impl<'a, 'b, A: Scalar, T: 'b, F: 'a, const K: usize, D> Iterator
+    for NearestIter<'a, 'b, A, T, F, K, D>
 where
-    F: Fn(&[A; K], &[A; K]) -> A,
+    D: Scalar,
+    F: Distance<A, K, D>,
     T: PartialEq,
 {
-    type Item = (A, &'b T);
-    fn next(&mut self) -> Option<(A, &'b T)> {
-        use util::distance_to_space;
-
+    type Item = (D, &'b T);
+    fn next(&mut self) -> Option<(D, &'b T)> {
         let distance = self.distance;
         let point = self.point;
         while !self.pending.is_empty()
-            && (self.evaluated.peek().map_or(A::infinity(), |x| -x.distance)
-                >= -self.pending.peek().unwrap().distance)
+            && (self.evaluated.peek().map_or(D::highest(), |x| x.0.distance)
+                >= self.pending.peek().unwrap().0.distance)
         {
-            let mut curr = &*self.pending.pop().unwrap().element;
+            let mut curr = self.pending.pop().unwrap().0.element;
             while let Node::Stem { left, right, .. } = &curr.content {
                 let candidate;
                 (candidate, curr) = if curr.belongs_in_left(point) {
@@ -1136,48 +2908,108 @@ where
                     (left, right)
                 };
 
-                self.pending.push(HeapElement {
-                    distance: -distance_to_space(
+                self.pending.push(Reverse(HeapElement {
+                    distance: distance.dist_to_box(
                         point,
                         &candidate.min_bounds,
                         &candidate.max_bounds,
-                        distance,
                     ),
                     element: &**candidate,
+                }));
+            }
+
+            let evaluated = &mut self.evaluated;
+            curr.for_each_item_dist(point, distance, |dist, item| {
+                evaluated.push(Reverse(HeapElement {
+                    distance: dist,
+                    element: item,
+                }))
+            });
+        }
+        self.evaluated.pop().map(|x| x.0.into())
+    }
+}
+
+pub struct WithinBoxIter<'a, A, T: PartialEq, const K: usize> {
+    min: [A; K],
+    max: [A; K],
+    pending: Vec<(&'a KdTree<A, T, K>, bool)>,
+    leaf: Option<(LeafIter<'a, A, T, K>, bool)>,
+}
+
+enum LeafIter<'a, A, T, const K: usize> {
+    Points(Zip<LeafPointsIter<'a, A, K>, slice::Iter<'a, T>>),
+    Duplicates(&'a [A; K], slice::Iter<'a, T>),
+}
+
+impl<'a, A: Copy, T, const K: usize> Iterator for LeafIter<'a, A, T, K> {
+    type Item = ([A; K], &'a T);
+    fn next(&mut self) -> Option<([A; K], &'a T)> {
+        match self {
+            LeafIter::Points(entries) => entries.next(),
+            LeafIter::Duplicates(point, items) => items.next().map(|item| (**point, item)),
+        }
+    }
+}
+
+impl<'a, A: Copy + PartialOrd, T: PartialEq, const K: usize> Iterator
+    for WithinBoxIter<'a, A, T, K>
+{
+    type Item = ([A; K], &'a T);
+    fn next(&mut self) -> Option<([A; K], &'a T)> {
+        loop {
+            if let Some((entries, inside)) = &mut self.leaf {
+                let (min, max) = (&self.min, &self.max);
+                let next = entries.find(|(p, _)| {
+                    *inside || (0..K).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim])
                 });
+                if next.is_some() {
+                    return next;
+                }
+                self.leaf = None;
             }
 
-            match &curr.content {
+            let (node, parent_inside) = self.pending.pop()?;
+            if node.size == 0 {
+                continue;
+            }
+            // everything in a subtree is inside the box if its bounds are
+            let (min, max) = (&self.min, &self.max);
+            let inside = parent_inside
+                || util::bounds_within_box(&node.min_bounds, &node.max_bounds, min, max);
+            if !inside && !util::bounds_intersect_box(&node.min_bounds, &node.max_bounds, min, max)
+            {
+                continue;
+            }
+
+            match &node.content {
                 Node::Leaf { points, bucket, .. } => {
-                    let points = points.iter();
-                    let bucket = bucket.iter();
-
-                    self.evaluated
-                        .extend(points.zip(bucket).map(|(p, d)| HeapElement {
-                            distance: -distance(point, &p),
-                            element: d,
-                        }));
+                    let entries = LeafIter::Points(points.iter().zip(bucket.iter()));
+                    self.leaf = Some((entries, inside));
+                }
+                Node::Duplicates { point, bucket, .. } => {
+                    self.leaf = Some((LeafIter::Duplicates(point, bucket.iter()), inside));
+                }
+                Node::Stem { left, right, .. } => {
+                    self.pending.push((right, inside));
+                    self.pending.push((left, inside));
                 }
-                Node::Stem { .. } => unreachable!(),
             }
         }
-        self.evaluated.pop().map(|x| (-x.distance, x.element))
     }
 }
 
-impl std::error::Error for ErrorKind {
-    fn description(&self) -> &str {
-        match *self {
+impl core::error::Error for ErrorKind {}
+
+impl core::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let description = match *self {
             ErrorKind::NonFiniteCoordinate => "non-finite coordinate",
             ErrorKind::ZeroCapacity => "zero capacity",
             ErrorKind::Empty => "invalid operation on empty tree",
-        }
-    }
-}
-
-impl std::fmt::Display for ErrorKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "KdTree error: {}", self)
+            ErrorKind::InvalidEpsilon => "epsilon must be finite and non-negative",
+        };
+        write!(f, "KdTree error: {}", description)
     }
 }
 
@@ -1198,7 +3030,7 @@ mod tests {
             Node::Leaf { capacity, .. } => {
                 assert_eq!(*capacity, 2_usize.pow(4));
             }
-            Node::Stem { .. } => unreachable!(),
+            _ => unreachable!(),
         }
     }
 
@@ -1232,4 +3064,155 @@ mod tests {
         assert_eq!(tree.size(), capacity + 1);
         assert!(!tree.is_leaf());
     }
+
+    fn depth(tree: &KdTree<f64, i32, 2>) -> usize {
+        match &tree.content {
+            Node::Leaf { .. } | Node::Duplicates { .. } => 1,
+            Node::Stem { left, right, .. } => 1 + core::cmp::max(depth(left), depth(right)),
+        }
+    }
+
+    #[test]
+    fn it_builds_a_balanced_tree_from_skewed_points() {
+        let points: Vec<([f64; 2], i32)> = (0..4096)
+            .map(|i| ([(i as f64 / 64.0).exp(), 0.0], i))
+            .collect();
+
+        let tree = KdTree::from_points_with_capacity(points, 16).unwrap();
+
+        assert_eq!(tree.size(), 4096);
+        assert_eq!(depth(&tree), 9);
+    }
+
+    #[test]
+    fn it_rebalances_a_tree_built_from_skewed_points() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(16).unwrap();
+        for i in 0..4096 {
+            tree.add(&[(i as f64 / 64.0).exp(), 0.0], i).unwrap();
+        }
+        assert!(depth(&tree) > 9);
+
+        tree.rebalance();
+
+        assert_eq!(tree.size(), 4096);
+        assert_eq!(depth(&tree), 9);
+    }
+
+    #[test]
+    fn it_shrinks_its_bounds_when_points_are_removed() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(2).unwrap();
+        for i in 0..10 {
+            tree.add(&[i as f64, -i as f64], i).unwrap();
+        }
+
+        tree.remove(&[9.0, -9.0], &9).unwrap();
+        tree.remove(&[0.0, 0.0], &0).unwrap();
+
+        assert_eq!(tree.min_bounds, [1.0, -8.0]);
+        assert_eq!(tree.max_bounds, [8.0, -1.0]);
+    }
+
+    #[test]
+    fn it_collapses_stems_when_points_are_removed() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(4).unwrap();
+        for i in 0..10 {
+            tree.add(&[i as f64, 0.0], i).unwrap();
+        }
+        for i in 0..6 {
+            tree.remove(&[i as f64, 0.0], &i).unwrap();
+        }
+
+        assert_eq!(tree.size(), 4);
+        assert!(tree.is_leaf());
+        assert_eq!(tree.min_bounds, [6.0, 0.0]);
+    }
+
+    #[test]
+    fn it_moves_a_point_within_its_leaf_without_restructuring() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(4).unwrap();
+        for i in 0..8 {
+            tree.add(&[i as f64, 0.0], i).unwrap();
+        }
+        let depth_before = depth(&tree);
+
+        assert!(tree.update_position(&[0.0, 0.0], &[0.5, 0.25], &0).unwrap());
+
+        assert_eq!(depth(&tree), depth_before);
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.min_bounds, [0.5, 0.0]);
+        assert_eq!(tree.max_bounds, [7.0, 0.25]);
+        match &tree.content {
+            Node::Stem { left, .. } => assert_eq!(left.min_bounds, [0.5, 0.0]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn it_collapses_an_overflowing_leaf_of_duplicates() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(4).unwrap();
+        for i in 0..100 {
+            tree.add(&[1.0, 2.0], i).unwrap();
+        }
+
+        assert_eq!(tree.size(), 100);
+        match &tree.content {
+            Node::Duplicates { point, bucket, .. } => {
+                assert_eq!(*point, [1.0, 2.0]);
+                assert_eq!(bucket.len(), 100);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn it_splits_duplicates_from_a_different_point() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(4).unwrap();
+        for i in 0..100 {
+            tree.add(&[1.0, 2.0], i).unwrap();
+        }
+        tree.add(&[3.0, 2.0], 100).unwrap();
+
+        assert_eq!(tree.size(), 101);
+        match &tree.content {
+            Node::Stem { left, right, .. } => {
+                assert!(matches!(left.content, Node::Duplicates { .. }));
+                assert_eq!(left.size(), 100);
+                assert!(matches!(right.content, Node::Leaf { .. }));
+                assert_eq!(right.size(), 1);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn it_turns_duplicates_back_into_a_leaf_once_they_fit() {
+        let mut tree: KdTree<f64, i32, 2> = KdTree::with_capacity(4).unwrap();
+        for i in 0..10 {
+            tree.add(&[1.0, 2.0], i).unwrap();
+        }
+        for i in 0..6 {
+            assert_eq!(tree.remove(&[1.0, 2.0], &i).unwrap(), 1);
+        }
+
+        assert_eq!(tree.size(), 4);
+        assert!(matches!(tree.content, Node::Leaf { .. }));
+        assert_eq!(tree.min_bounds, [1.0, 2.0]);
+    }
+
+    #[test]
+    fn it_builds_duplicates_from_points() {
+        let points: Vec<([f64; 2], i32)> = (0..100)
+            .map(|i| {
+                if i < 90 {
+                    ([0.0, 0.0], i)
+                } else {
+                    ([i as f64, 0.0], i)
+                }
+            })
+            .collect();
+        let tree = KdTree::from_points_with_capacity(points, 8).unwrap();
+
+        assert_eq!(tree.size(), 100);
+        assert!(depth(&tree) <= 4);
+    }
 }
@@ -1,10 +1,6 @@
-#![feature(allocator_api)]
-// #![feature(const_generics)]
-#![feature(adt_const_params)]
-#![feature(generic_const_exprs)]
-#![feature(destructuring_assignment)]
 #![doc(html_root_url = "https://docs.rs/kiddo/0.1.4")]
 #![doc(issue_tracker_base_url = "https://github.com/sdd/kiddo/issues/")]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 //! # kiddo
 //!
@@ -26,7 +22,7 @@
 //! ```rust
 //! use kiddo::KdTree;
 //! use kiddo::ErrorKind;
-//! use kiddo::distance::squared_euclidean;
+//! use kiddo::distance::SquaredEuclidean;
 //!
 //! let a: ([f64; 2], usize) = ([0f64, 0f64], 0);
 //! let b: ([f64; 2], usize) = ([1f64, 1f64], 1);
@@ -42,36 +38,38 @@
 //!
 //! assert_eq!(kdtree.size(), 4);
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 0, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 0, &SquaredEuclidean)?,
 //!     vec![]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 1, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 1, &SquaredEuclidean)?,
 //!     vec![(0f64, &0)]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 2, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 2, &SquaredEuclidean)?,
 //!     vec![(0f64, &0), (2f64, &1)]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 3, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 3, &SquaredEuclidean)?,
 //!     vec![(0f64, &0), (2f64, &1), (8f64, &2)]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 4, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 4, &SquaredEuclidean)?,
 //!     vec![(0f64, &0), (2f64, &1), (8f64, &2), (18f64, &3)]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&a.0, 5, &squared_euclidean)?,
+//!     kdtree.nearest(&a.0, 5, &SquaredEuclidean)?,
 //!     vec![(0f64, &0), (2f64, &1), (8f64, &2), (18f64, &3)]
 //! );
 //! assert_eq!(
-//!     kdtree.nearest(&b.0, 4, &squared_euclidean)?,
+//!     kdtree.nearest(&b.0, 4, &SquaredEuclidean)?,
 //!     vec![(0f64, &1), (2f64, &0), (2f64, &2), (8f64, &3)]
 //! );
 //! # Ok::<(), kiddo::ErrorKind>(())
 //! ```
 
+extern crate alloc;
+
 #[cfg(feature = "serialize")]
 extern crate serde;
 #[cfg(feature = "serialize")]
@@ -79,10 +77,24 @@ extern crate serde;
 extern crate serde_derive;
 
 mod custom_serde;
+pub mod diagnostics;
 pub mod distance;
+pub mod geo;
 mod heap_element;
+pub mod immutable;
 pub mod kiddo;
+#[cfg(feature = "std")]
+pub mod mapped;
+pub mod scalar;
+mod simd;
+pub mod split;
 mod util;
 
+pub use crate::diagnostics::{TreeStats, ValidationError};
+pub use crate::immutable::ImmutableKdTree;
 pub use crate::kiddo::ErrorKind;
 pub use crate::kiddo::KdTree;
+#[cfg(feature = "std")]
+pub use crate::mapped::MappedKdTree;
+pub use crate::scalar::Scalar;
+pub use crate::split::SplitStrategy;
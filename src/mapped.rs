@@ -0,0 +1,1528 @@
+//! A versioned binary format for immutable trees, which can be queried in place without
+//! deserialising or allocating anything per node, for example straight from a memory-mapped file.
+//!
+//! A file consists of a fixed-size header followed by the stems, leaves, coordinates and items
+//! of an `ImmutableKdTree`. Each of these is stored as a contiguous array, aligned to 64 bytes
+//! and laid out exactly as it is in memory. Files can therefore only be read on a platform with
+//! the same endianness and type layouts as the one that wrote them. The header records enough
+//! information to detect when this is not the case.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::mem::{align_of, size_of, MaybeUninit};
+use std::ptr::addr_of_mut;
+
+use crate::distance::Distance;
+use crate::immutable::{
+    ImmutableKdTree, ImmutableNearestIter, ImmutableWithinBoxIter, LeafNode, StemNode, TreeRef,
+    LEAF_FLAG,
+};
+use crate::kiddo::ErrorKind;
+use crate::scalar::Scalar;
+use crate::split::SplitStrategy;
+
+/// The version of the format written by `ImmutableKdTree::write_to`
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: [u8; 8] = *b"KIDDOMAP";
+const ENDIANNESS_MARKER: u32 = 0x0102_0304;
+const HEADER_LEN: usize = 128;
+const SECTION_ALIGN: usize = 64;
+
+/// Marker for types that can be stored in, and read directly from, a mapped tree.
+///
+/// # Safety
+///
+/// Implementors must be valid for any bit pattern, and must not contain any padding bytes.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<P: Pod, const N: usize> Pod for [P; N] {}
+
+/// A coordinate type that can be stored in a mapped tree. `TYPE_ID` identifies the type
+/// in the file header.
+pub trait MappedCoordinate: Scalar + Pod {
+    const TYPE_ID: u32;
+}
+
+impl MappedCoordinate for f32 {
+    const TYPE_ID: u32 = 1;
+}
+
+impl MappedCoordinate for f64 {
+    const TYPE_ID: u32 = 2;
+}
+
+impl MappedCoordinate for i8 {
+    const TYPE_ID: u32 = 3;
+}
+
+impl MappedCoordinate for i16 {
+    const TYPE_ID: u32 = 4;
+}
+
+impl MappedCoordinate for i32 {
+    const TYPE_ID: u32 = 5;
+}
+
+impl MappedCoordinate for i64 {
+    const TYPE_ID: u32 = 6;
+}
+
+impl MappedCoordinate for u8 {
+    const TYPE_ID: u32 = 7;
+}
+
+impl MappedCoordinate for u16 {
+    const TYPE_ID: u32 = 8;
+}
+
+impl MappedCoordinate for u32 {
+    const TYPE_ID: u32 = 9;
+}
+
+impl MappedCoordinate for u64 {
+    const TYPE_ID: u32 = 10;
+}
+
+/// The reasons why a buffer could not be read as a `MappedKdTree`
+#[derive(Debug, PartialEq)]
+pub enum FormatError {
+    /// The buffer does not start with the expected magic bytes
+    InvalidMagic,
+    /// The buffer was written with a version of the format that is not supported
+    UnsupportedVersion(u32),
+    /// The buffer was written on a platform with a different endianness or type layout
+    IncompatibleLayout,
+    /// The buffer holds a tree with a different number of dimensions
+    DimensionMismatch,
+    /// The buffer holds a tree with a different coordinate type
+    CoordinateTypeMismatch,
+    /// The buffer holds a tree with an item type of a different size or alignment
+    ItemTypeMismatch,
+    /// The buffer is shorter than its header says it should be
+    Truncated,
+    /// The buffer is not sufficiently aligned for the types it contains
+    Misaligned,
+    /// The buffer contains an invalid tree structure
+    Corrupt,
+}
+
+/// A read-only tree that borrows all of its data from a byte buffer in the format
+/// written by `ImmutableKdTree::write_to`. Supports the same queries as `ImmutableKdTree`.
+///
+/// The buffer must be aligned to at least 64 bytes, which is always the case for a
+/// memory-mapped file.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::{ImmutableKdTree, KdTree};
+/// use kiddo::distance::squared_euclidean;
+/// use kiddo::mapped::MappedKdTree;
+///
+/// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+///
+/// tree.add(&[1.0, 2.0, 5.0], 100)?;
+/// tree.add(&[2.0, 3.0, 6.0], 101)?;
+///
+/// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+///
+/// let path = std::env::temp_dir().join("kiddo_mapped_doc_example.bin");
+/// tree.write_to(std::io::BufWriter::new(std::fs::File::create(&path)?))?;
+///
+/// let file = std::fs::File::open(&path)?;
+/// let mmap = unsafe { memmap2::Mmap::map(&file)? };
+/// let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+///
+/// let nearest = mapped.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+///
+/// assert_eq!(*nearest.1, 100);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct MappedKdTree<'a, A, T, const K: usize> {
+    capacity: usize,
+    tree: TreeRef<'a, A, T, K>,
+}
+
+impl<'a, A: MappedCoordinate, T: Pod, const K: usize> MappedKdTree<'a, A, T, K> {
+    /// Reads a tree from a buffer in the format written by `ImmutableKdTree::write_to`,
+    /// without copying any of its contents.
+    ///
+    /// The header is checked against `A`, `T` and `K`, and the structure of the tree is
+    /// validated, so an incompatible or corrupt buffer results in an error rather than
+    /// undefined behaviour.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let header = Header::read(bytes)?;
+        header.check::<A, T, K>()?;
+
+        let stems: &[StemNode<A, K>] = section(bytes, header.stems_offset, header.stem_count)?;
+        let leaves: &[LeafNode<A, K>] = section(bytes, header.leaves_offset, header.leaf_count)?;
+        let coord_count = header
+            .size
+            .checked_mul(K as u64)
+            .ok_or(FormatError::Truncated)?;
+        let coords: &[A] = section(bytes, header.coords_offset, coord_count)?;
+        let items: &[T] = section(bytes, header.items_offset, header.size)?;
+
+        let tree = TreeRef {
+            size: items.len(),
+            stems,
+            leaves,
+            coords,
+            items,
+            root: header.root,
+        };
+        let capacity = usize::try_from(header.capacity).map_err(|_| FormatError::Corrupt)?;
+        validate(&tree, capacity)?;
+
+        Ok(MappedKdTree { capacity, tree })
+    }
+
+    /// Returns the number of elements stored in the tree
+    pub fn size(&self) -> usize {
+        self.tree.size
+    }
+
+    /// Returns the capacity per leaf node of the tree that this tree was written from
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Queries the tree to find the nearest `num` elements to `point`, using the specified
+    /// distance metric function.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest(&[1.0, 2.0, 5.1], 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 1);
+    /// assert!((nearest[0].0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.nearest(point, num, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, using
+    /// the specified distance metric function.
+    ///
+    /// The search skips any node that cannot hold an element closer than the `num`th
+    /// nearest found so far by more than a factor of `1 + epsilon`, so each result is at
+    /// most `1 + epsilon` times as far from `point` as the true neighbour of the same rank.
+    /// Distances are compared as the metric returns them, so with `squared_euclidean` the
+    /// factor applies to the squared distance. Metrics that return negative distances, such
+    /// as `InnerProduct`, get the same bound written without a ratio: no skipped element is
+    /// closer than `d - |d| * epsilon / (1 + epsilon)`, where `d` is the distance of the
+    /// result. An `epsilon` of zero gives the same results as `nearest()`, and larger values
+    /// visit fewer nodes. Returns `ErrorKind::InvalidEpsilon` if `epsilon` is negative or
+    /// not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_approx(&[1.0, 2.0, 5.1], 1, 0.5, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 1);
+    /// assert!(nearest[0].0 <= 1.5 * 0.01);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_approx<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree
+            .nearest_approx_with_budget(point, num, epsilon, usize::MAX, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest `num` elements to `point`, as
+    /// `nearest_approx()` does, but gives up after searching `max_leaves_visited` leaf nodes
+    /// and returns the nearest elements found so far. This caps the time a query can take,
+    /// at the cost of the `1 + epsilon` guarantee: once the budget is spent, the results may
+    /// be further away than that, and there may be fewer than `num` of them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_approx_with_budget(&[1.0, 2.0, 5.1], 3, 0.0, 1, &squared_euclidean)?;
+    ///
+    /// assert!(nearest.len() < 3);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        num: usize,
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree
+            .nearest_approx_with_budget(point, num, epsilon, max_leaves_visited, distance)
+    }
+
+    /// Queries the tree to find the nearest `num` elements to each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling nearest() for each point as allocations
+    /// are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.nearest_batch(points, num, distance)
+    }
+
+    /// Queries the tree to find the nearest element to `point`, using the specified
+    /// distance metric function. Faster than querying for nearest(point, 1, ...) due
+    /// to not needing to allocate a Vec for the result
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_one(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// assert!((nearest.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_one<D, F>(&self, point: &[A; K], distance: &F) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.nearest_one(point, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest element to `point`, using the
+    /// specified distance metric function. The result is at most `1 + epsilon` times as far
+    /// from `point` as the nearest element. See `nearest_approx()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_one_approx(&[1.0, 2.0, 5.1], 0.5, &squared_euclidean)?;
+    ///
+    /// assert!(nearest.0 <= 1.5 * 0.01);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_one_approx<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        distance: &F,
+    ) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree
+            .nearest_one_approx_with_budget(point, epsilon, usize::MAX, distance)
+    }
+
+    /// Queries the tree to find approximately the nearest element to `point`, as
+    /// `nearest_one_approx()` does, but gives up after searching `max_leaves_visited` leaf
+    /// nodes and returns the nearest element found so far. The search always continues
+    /// until it has found at least one element, even if that exceeds the budget.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(2)?;
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[3.0, 4.0, 7.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_one_approx_with_budget(&[1.0, 2.0, 5.1], 0.0, 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest.1, 100);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_one_approx_with_budget<D, F>(
+        &self,
+        point: &[A; K],
+        epsilon: f64,
+        max_leaves_visited: usize,
+        distance: &F,
+    ) -> Result<(D, &'a T), ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree
+            .nearest_one_approx_with_budget(point, epsilon, max_leaves_visited, distance)
+    }
+
+    /// Queries the tree to find the nearest element to each of `points`, using the specified
+    /// distance metric function. Results are returned in the same order as `points`. Faster
+    /// than calling nearest_one() for each point as allocations are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
+    ///
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.nearest_one_batch(points, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned sorted nearest-first
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.within(point, radius, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points`, using the
+    /// specified distance metric function. Results are returned in the same order as `points`,
+    /// each sorted nearest-first. Faster than calling within() for each point as allocations
+    /// are reused between queries
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let within = tree.within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.within_batch(points, radius, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. Faster than within()
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let within = tree.within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn within_unsorted<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.within_unsorted(point, radius, distance)
+    }
+
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let best_n_within = tree.best_n_within(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean)?;
+    ///
+    /// assert_eq!(best_n_within[0], 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn best_n_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> Result<Vec<T>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Ord,
+    {
+        self.tree.best_n_within(point, radius, max_qty, distance)
+    }
+
+    /// Queries the tree to find the best `n` elements within `radius` of `point`, using the specified
+    /// distance metric function. Results are returned in arbitrary order. 'Best' is determined by
+    /// performing a comparison of the elements using < (ie, std::ord::lt). Returns an iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 1)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let mut best_n_within_iter = tree.best_n_within_into_iter(&[1.0, 2.0, 5.0], 10f64, 1, &squared_euclidean);
+    /// let first = best_n_within_iter.next().unwrap();
+    ///
+    /// assert_eq!(first, 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn best_n_within_into_iter<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        max_qty: usize,
+        distance: &F,
+    ) -> impl Iterator<Item = T>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+        T: Copy + Ord,
+    {
+        self.tree
+            .best_n_within_into_iter(point, radius, max_qty, distance)
+    }
+
+    /// Returns an iterator over all elements in the tree, sorted nearest-first to the query point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let mut nearest_iter = tree.iter_nearest(&[1.0, 2.0, 5.1], &squared_euclidean)?;
+    ///
+    /// let nearest_first = nearest_iter.next().unwrap();
+    ///
+    /// assert!((nearest_first.0 - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(*nearest_first.1, 100);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn iter_nearest<'p, D, F>(
+        &self,
+        point: &'p [A; K],
+        distance: &'p F,
+    ) -> Result<ImmutableNearestIter<'p, 'a, A, T, F, K, D>, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.iter_nearest(point, distance)
+    }
+
+    /// Queries the tree to find all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive. Results are returned in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let within = tree.within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within, vec![([1.0, 2.0, 5.0], &100)]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn within_box(
+        &self,
+        min: &[A; K],
+        max: &[A; K],
+    ) -> Result<Vec<([A; K], &'a T)>, ErrorKind> {
+        Ok(self.iter_within_box(min, max)?.collect())
+    }
+
+    /// Returns an iterator over all elements inside the axis-aligned box with corners
+    /// `min` and `max`, inclusive, in arbitrary order.
+    ///
+    /// Subtrees that lie entirely outside the box are skipped, and the elements of subtrees
+    /// that lie entirely inside it are returned without checking each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let mut within = tree.iter_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(within.next(), Some(([1.0, 2.0, 5.0], &100)));
+    /// assert_eq!(within.next(), None);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn iter_within_box(
+        &self,
+        min: &[A; K],
+        max: &[A; K],
+    ) -> Result<ImmutableWithinBoxIter<'a, A, T, K>, ErrorKind> {
+        self.tree.iter_within_box(min, max)
+    }
+
+    /// Counts the elements within `radius` of `point`, using the specified distance
+    /// metric function, without collecting them.
+    ///
+    /// The elements of subtrees that lie entirely within `radius` are counted without
+    /// visiting each point, which assumes that the distance metric does not decrease as
+    /// the difference along an axis grows. This holds for all of the metrics in `distance`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let count = tree.count_within(&[1.0, 2.0, 5.0], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(count, 2);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn count_within<D, F>(
+        &self,
+        point: &[A; K],
+        radius: D,
+        distance: &F,
+    ) -> Result<usize, ErrorKind>
+    where
+        D: Scalar,
+        F: Distance<A, K, D>,
+    {
+        self.tree.count_within(point, radius, distance)
+    }
+
+    /// Counts the elements inside the axis-aligned box with corners `min` and `max`,
+    /// inclusive, without collecting them. The elements of subtrees that lie entirely
+    /// inside the box are counted without visiting each point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let count = tree.count_within_box(&[0.0, 0.0, 0.0], &[1.5, 2.5, 5.5])?;
+    ///
+    /// assert_eq!(count, 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn count_within_box(&self, min: &[A; K], max: &[A; K]) -> Result<usize, ErrorKind> {
+        self.tree.count_within_box(min, max)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, A, T, const K: usize> MappedKdTree<'a, A, T, K>
+where
+    A: MappedCoordinate + Send + Sync,
+    T: Pod + Sync,
+{
+    /// Queries the tree to find the nearest element to each of `points` using all available
+    /// threads. Results are returned in the same order as `points`. See `nearest_one()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.par_nearest_one_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0].1, 100);
+    /// assert_eq!(*nearest[1].1, 101);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_nearest_one_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        distance: &F,
+    ) -> Result<Vec<(D, &'a T)>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree.par_nearest_one_batch(points, distance)
+    }
+
+    /// Queries the tree to find the nearest `num` elements to each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `nearest()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let nearest = tree.par_nearest_batch(&[[1.0, 2.0, 5.1], [2.0, 3.0, 6.1]], 2, &squared_euclidean)?;
+    ///
+    /// assert_eq!(*nearest[0][0].1, 100);
+    /// assert_eq!(*nearest[1][0].1, 101);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_nearest_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        num: usize,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree.par_nearest_batch(points, num, distance)
+    }
+
+    /// Queries the tree to find all elements within `radius` of each of `points` using all
+    /// available threads. Results are returned in the same order as `points`, each sorted
+    /// nearest-first. See `within()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::distance::squared_euclidean;
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    /// tree.add(&[2.0, 3.0, 6.0], 101)?;
+    /// tree.add(&[200.0, 300.0, 600.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let within = tree.par_within_batch(&[[1.0, 2.0, 5.0], [200.0, 300.0, 600.0]], 10f64, &squared_euclidean)?;
+    ///
+    /// assert_eq!(within[0].len(), 2);
+    /// assert_eq!(within[1].len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn par_within_batch<D, F>(
+        &self,
+        points: &[[A; K]],
+        radius: D,
+        distance: &F,
+    ) -> Result<Vec<Vec<(D, &'a T)>>, ErrorKind>
+    where
+        D: Scalar + Send + Sync,
+        F: Distance<A, K, D> + Sync,
+    {
+        self.tree.par_within_batch(points, radius, distance)
+    }
+}
+
+impl<'a, T: Pod, const K: usize> MappedKdTree<'a, f32, T, K> {
+    /// Queries the tree to find the `num` elements with the greatest inner product with
+    /// `query`. For unit vectors, this is their cosine similarity. Results are returned
+    /// sorted most similar first, along with their similarity
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f32, usize, 2> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f32, usize, 2> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let similar = tree.most_similar(&[0.0, 1.0], 2)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101), (0.0, &100)]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn most_similar(
+        &self,
+        query: &[f32; K],
+        num: usize,
+    ) -> Result<Vec<(f32, &'a T)>, ErrorKind> {
+        self.tree.most_similar(query, num)
+    }
+
+    /// Queries the tree to find all elements whose inner product with `query` is at least
+    /// `min_similarity`. For unit vectors, this is their cosine similarity. Results are
+    /// returned sorted most similar first, along with their similarity
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    /// use kiddo::mapped::MappedKdTree;
+    ///
+    /// let mut tree: KdTree<f32, usize, 2> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 0.0], 100)?;
+    /// tree.add(&[0.6, 0.8], 101)?;
+    /// tree.add(&[0.0, -1.0], 102)?;
+    ///
+    /// let tree: ImmutableKdTree<f32, usize, 2> = tree.into();
+    /// # let mut bytes = Vec::new();
+    /// # tree.write_to(&mut bytes)?;
+    /// # let mut mmap = memmap2::MmapMut::map_anon(bytes.len())?;
+    /// # mmap.copy_from_slice(&bytes);
+    /// let tree: MappedKdTree<f32, usize, 2> = MappedKdTree::from_bytes(&mmap)?;
+    ///
+    /// let similar = tree.similar_within(&[0.0, 1.0], 0.5)?;
+    ///
+    /// assert_eq!(similar, vec![(0.8, &101)]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn similar_within(
+        &self,
+        query: &[f32; K],
+        min_similarity: f32,
+    ) -> Result<Vec<(f32, &'a T)>, ErrorKind> {
+        self.tree.similar_within(query, min_similarity)
+    }
+}
+
+impl<A: MappedCoordinate, T: Pod, const K: usize> ImmutableKdTree<A, T, K> {
+    /// Writes the tree to `writer` in a format that `MappedKdTree` can query in place.
+    /// `writer` should be buffered, as the tree is written one node at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, usize, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 5.0], 100)?;
+    ///
+    /// let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    ///
+    /// let mut bytes = Vec::new();
+    /// tree.write_to(&mut bytes)?;
+    ///
+    /// assert_eq!(&bytes[0..8], b"KIDDOMAP");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let stems_offset = align_up(HEADER_LEN, SECTION_ALIGN);
+        let leaves_offset = align_up(
+            stems_offset + self.stems.len() * size_of::<StemNode<A, K>>(),
+            SECTION_ALIGN,
+        );
+        let coords_offset = align_up(
+            leaves_offset + self.leaves.len() * size_of::<LeafNode<A, K>>(),
+            SECTION_ALIGN,
+        );
+        let items_offset = align_up(
+            coords_offset + self.coords.len() * size_of::<A>(),
+            SECTION_ALIGN,
+        );
+
+        let header = Header {
+            version: FORMAT_VERSION,
+            endianness: ENDIANNESS_MARKER,
+            dimensions: K as u32,
+            coordinate_type: A::TYPE_ID,
+            item_size: size_of::<T>() as u32,
+            item_align: align_of::<T>() as u32,
+            stem_size: size_of::<StemNode<A, K>>() as u32,
+            leaf_size: size_of::<LeafNode<A, K>>() as u32,
+            capacity: self.capacity as u64,
+            size: self.size as u64,
+            root: self.root,
+            stem_count: self.stems.len() as u64,
+            leaf_count: self.leaves.len() as u64,
+            stems_offset: stems_offset as u64,
+            leaves_offset: leaves_offset as u64,
+            coords_offset: coords_offset as u64,
+            items_offset: items_offset as u64,
+        };
+
+        let mut position = header.write(&mut writer)?;
+
+        position += write_padding(&mut writer, position, stems_offset)?;
+        for stem in self.stems.iter() {
+            position += write_stem(&mut writer, stem)?;
+        }
+
+        position += write_padding(&mut writer, position, leaves_offset)?;
+        for leaf in self.leaves.iter() {
+            position += write_leaf(&mut writer, leaf)?;
+        }
+
+        position += write_padding(&mut writer, position, coords_offset)?;
+        position += write_pod(&mut writer, &self.coords)?;
+
+        write_padding(&mut writer, position, items_offset)?;
+        write_pod(&mut writer, &self.items)?;
+
+        writer.flush()
+    }
+}
+
+struct Header {
+    version: u32,
+    endianness: u32,
+    dimensions: u32,
+    coordinate_type: u32,
+    item_size: u32,
+    item_align: u32,
+    stem_size: u32,
+    leaf_size: u32,
+    capacity: u64,
+    size: u64,
+    root: u32,
+    stem_count: u64,
+    leaf_count: u64,
+    stems_offset: u64,
+    leaves_offset: u64,
+    coords_offset: u64,
+    items_offset: u64,
+}
+
+impl Header {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut buf = Vec::with_capacity(HEADER_LEN);
+        buf.extend_from_slice(&MAGIC);
+        for field in [
+            self.version,
+            self.endianness,
+            self.dimensions,
+            self.coordinate_type,
+            self.item_size,
+            self.item_align,
+            self.stem_size,
+            self.leaf_size,
+            self.root,
+        ] {
+            buf.extend_from_slice(&field.to_ne_bytes());
+        }
+        for field in [
+            self.capacity,
+            self.size,
+            self.stem_count,
+            self.leaf_count,
+            self.stems_offset,
+            self.leaves_offset,
+            self.coords_offset,
+            self.items_offset,
+        ] {
+            buf.extend_from_slice(&field.to_ne_bytes());
+        }
+        buf.resize(HEADER_LEN, 0);
+
+        writer.write_all(&buf)?;
+        Ok(HEADER_LEN)
+    }
+
+    fn read(bytes: &[u8]) -> Result<Header, FormatError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(FormatError::Truncated);
+        }
+        if bytes[0..8] != MAGIC {
+            return Err(FormatError::InvalidMagic);
+        }
+
+        let mut pos = 8;
+        let mut u32_field = || {
+            let mut buf = [0u8; 4];
+            buf.copy_from_slice(&bytes[pos..pos + 4]);
+            pos += 4;
+            u32::from_ne_bytes(buf)
+        };
+        let version = u32_field();
+        let endianness = u32_field();
+        let dimensions = u32_field();
+        let coordinate_type = u32_field();
+        let item_size = u32_field();
+        let item_align = u32_field();
+        let stem_size = u32_field();
+        let leaf_size = u32_field();
+        let root = u32_field();
+
+        let mut pos = 8 + 9 * 4;
+        let mut u64_field = || {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&bytes[pos..pos + 8]);
+            pos += 8;
+            u64::from_ne_bytes(buf)
+        };
+
+        Ok(Header {
+            version,
+            endianness,
+            dimensions,
+            coordinate_type,
+            item_size,
+            item_align,
+            stem_size,
+            leaf_size,
+            root,
+            capacity: u64_field(),
+            size: u64_field(),
+            stem_count: u64_field(),
+            leaf_count: u64_field(),
+            stems_offset: u64_field(),
+            leaves_offset: u64_field(),
+            coords_offset: u64_field(),
+            items_offset: u64_field(),
+        })
+    }
+
+    fn check<A: MappedCoordinate, T: Pod, const K: usize>(&self) -> Result<(), FormatError> {
+        if self.version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion(self.version));
+        }
+        if self.endianness != ENDIANNESS_MARKER {
+            return Err(FormatError::IncompatibleLayout);
+        }
+        if self.dimensions as usize != K {
+            return Err(FormatError::DimensionMismatch);
+        }
+        if self.coordinate_type != A::TYPE_ID {
+            return Err(FormatError::CoordinateTypeMismatch);
+        }
+        if self.item_size as usize != size_of::<T>() || self.item_align as usize != align_of::<T>()
+        {
+            return Err(FormatError::ItemTypeMismatch);
+        }
+        if self.stem_size as usize != size_of::<StemNode<A, K>>()
+            || self.leaf_size as usize != size_of::<LeafNode<A, K>>()
+        {
+            return Err(FormatError::IncompatibleLayout);
+        }
+        Ok(())
+    }
+}
+
+/// Borrows `count` values of type `U` from `bytes`, starting at `offset`.
+/// `U` must be valid for any bit pattern.
+fn section<U>(bytes: &[u8], offset: u64, count: u64) -> Result<&[U], FormatError> {
+    let offset = usize::try_from(offset).map_err(|_| FormatError::Truncated)?;
+    let count = usize::try_from(count).map_err(|_| FormatError::Truncated)?;
+    let end = count
+        .checked_mul(size_of::<U>())
+        .and_then(|len| len.checked_add(offset))
+        .ok_or(FormatError::Truncated)?;
+    if end > bytes.len() {
+        return Err(FormatError::Truncated);
+    }
+
+    let start = bytes[offset..].as_ptr();
+    if !(start as usize).is_multiple_of(align_of::<U>()) {
+        return Err(FormatError::Misaligned);
+    }
+
+    // SAFETY: the range is in bounds and suitably aligned, and every type read through
+    // this function is valid for any bit pattern
+    Ok(unsafe { std::slice::from_raw_parts(start as *const U, count) })
+}
+
+/// Checks that every node index and item range in the tree is in bounds, and that every
+/// stem's children come after it, so that queries can neither panic nor loop forever.
+/// Leaves are written in the order that their items are, so their ranges must follow on
+/// from each other and cover every item exactly once. A leaf may only hold more than
+/// `capacity` items if its points could not have been split.
+fn validate<A: Scalar, T, const K: usize>(
+    tree: &TreeRef<A, T, K>,
+    capacity: usize,
+) -> Result<(), FormatError> {
+    let valid_child = |parent: Option<usize>, node: u32| {
+        if node & LEAF_FLAG != 0 {
+            ((node & !LEAF_FLAG) as usize) < tree.leaves.len()
+        } else {
+            (node as usize) < tree.stems.len() && parent.is_none_or(|p| node as usize > p)
+        }
+    };
+
+    if !valid_child(None, tree.root) {
+        return Err(FormatError::Corrupt);
+    }
+    for (idx, stem) in tree.stems.iter().enumerate() {
+        if stem.split_dimension as usize >= K
+            || !valid_child(Some(idx), stem.left)
+            || !valid_child(Some(idx), stem.right)
+        {
+            return Err(FormatError::Corrupt);
+        }
+    }
+    let mut end = 0u64;
+    for leaf in tree.leaves.iter() {
+        if u64::from(leaf.start) != end
+            || (leaf.len as usize > capacity
+                && SplitStrategy::Midpoint.can_split(&leaf.min_bounds, &leaf.max_bounds))
+        {
+            return Err(FormatError::Corrupt);
+        }
+        end += u64::from(leaf.len);
+    }
+    if end != tree.size as u64 {
+        return Err(FormatError::Corrupt);
+    }
+
+    Ok(())
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+fn write_padding<W: Write>(writer: &mut W, position: usize, target: usize) -> io::Result<usize> {
+    let padding = [0u8; SECTION_ALIGN];
+    writer.write_all(&padding[..target - position])?;
+    Ok(target - position)
+}
+
+fn write_pod<W: Write, P: Pod>(writer: &mut W, values: &[P]) -> io::Result<usize> {
+    let len = std::mem::size_of_val(values);
+    // SAFETY: Pod types have no padding, so every byte of the slice is initialised
+    let bytes = unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, len) };
+    writer.write_all(bytes)?;
+    Ok(len)
+}
+
+fn write_stem<W: Write, A: Copy, const K: usize>(
+    writer: &mut W,
+    stem: &StemNode<A, K>,
+) -> io::Result<usize> {
+    // the node is written field by field into zeroed memory, so that any padding
+    // bytes between its fields are initialised
+    let mut node = MaybeUninit::<StemNode<A, K>>::zeroed();
+    let ptr = node.as_mut_ptr();
+    // SAFETY: each field is written through a raw pointer into memory owned by `node`
+    unsafe {
+        addr_of_mut!((*ptr).min_bounds).write(stem.min_bounds);
+        addr_of_mut!((*ptr).max_bounds).write(stem.max_bounds);
+        addr_of_mut!((*ptr).split_value).write(stem.split_value);
+        addr_of_mut!((*ptr).split_dimension).write(stem.split_dimension);
+        addr_of_mut!((*ptr).left).write(stem.left);
+        addr_of_mut!((*ptr).right).write(stem.right);
+    }
+    write_node(writer, &node)
+}
+
+fn write_leaf<W: Write, A: Copy, const K: usize>(
+    writer: &mut W,
+    leaf: &LeafNode<A, K>,
+) -> io::Result<usize> {
+    let mut node = MaybeUninit::<LeafNode<A, K>>::zeroed();
+    let ptr = node.as_mut_ptr();
+    // SAFETY: see write_stem
+    unsafe {
+        addr_of_mut!((*ptr).min_bounds).write(leaf.min_bounds);
+        addr_of_mut!((*ptr).max_bounds).write(leaf.max_bounds);
+        addr_of_mut!((*ptr).start).write(leaf.start);
+        addr_of_mut!((*ptr).len).write(leaf.len);
+    }
+    write_node(writer, &node)
+}
+
+fn write_node<W: Write, N>(writer: &mut W, node: &MaybeUninit<N>) -> io::Result<usize> {
+    // SAFETY: every byte of the node, including padding, was zeroed before its fields were set
+    let bytes = unsafe { std::slice::from_raw_parts(node.as_ptr() as *const u8, size_of::<N>()) };
+    writer.write_all(bytes)?;
+    Ok(size_of::<N>())
+}
+
+impl std::error::Error for FormatError {}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FormatError::InvalidMagic => write!(f, "not a mapped KdTree"),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported mapped KdTree format version {}", version)
+            }
+            FormatError::IncompatibleLayout => {
+                write!(f, "mapped KdTree was written on an incompatible platform")
+            }
+            FormatError::DimensionMismatch => write!(f, "mapped KdTree has different dimensions"),
+            FormatError::CoordinateTypeMismatch => {
+                write!(f, "mapped KdTree has a different coordinate type")
+            }
+            FormatError::ItemTypeMismatch => write!(f, "mapped KdTree has a different item type"),
+            FormatError::Truncated => write!(f, "mapped KdTree is truncated"),
+            FormatError::Misaligned => write!(f, "mapped KdTree buffer is misaligned"),
+            FormatError::Corrupt => write!(f, "mapped KdTree is corrupt"),
+        }
+    }
+}
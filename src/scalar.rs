@@ -0,0 +1,225 @@
+//! The numeric types that points' coordinates, and the distances between them, can be made
+//! of.
+//!
+//! Floating point coordinates are measured with distances of the same type. Integer
+//! coordinates, such as grid or voxel indices and fixed-point values, are usually measured
+//! with a wider distance type, so that squared distances do not overflow. The metrics in
+//! `distance` measure 8, 16 and 32 bit integer coordinates with `u64` distances, and 64 bit
+//! integer coordinates with `u128` distances.
+
+use core::convert::TryFrom;
+
+use num_traits::{Bounded, ToPrimitive, Zero};
+
+/// A numeric type that points' coordinates, or the distances between them, can be made of.
+///
+/// Implemented for `f32` and `f64`, and for the primitive integer types.
+pub trait Scalar: Copy + PartialOrd + Zero + ToPrimitive {
+    /// Returns true if a point may have this coordinate. Floats must be finite, and any
+    /// integer is valid.
+    fn is_valid(self) -> bool;
+
+    /// Returns a value that no coordinate or distance is below: negative infinity for
+    /// floats, or the minimum value of an integer type
+    fn lowest() -> Self;
+
+    /// Returns a value that no coordinate or distance is above: infinity for floats, or the
+    /// maximum value of an integer type
+    fn highest() -> Self;
+
+    /// Returns the value halfway between `min` and `max`, at which a node whose points span
+    /// `min..=max` is split. Integer types round up, so that when `min < max`, the points
+    /// at `min` fall below the split and those at `max` do not.
+    fn midpoint(min: Self, max: Self) -> Self;
+
+    /// Returns the absolute difference between `a` and `b`, saturating for signed integer
+    /// types whose difference does not fit
+    fn abs_diff(a: Self, b: Self) -> Self;
+
+    /// Returns `value - |value| * fraction`, for a `fraction` from zero to one. Integer
+    /// types round the product down and saturate at their minimum value, and are exact
+    /// however large `value` is, which converting it to `f64` would not be.
+    fn sub_fraction(value: Self, fraction: f64) -> Self;
+}
+
+macro_rules! float_scalar {
+    ($($float:ident),*) => {$(
+        impl Scalar for $float {
+            #[inline]
+            fn is_valid(self) -> bool {
+                self.is_finite()
+            }
+
+            #[inline]
+            fn lowest() -> Self {
+                $float::NEG_INFINITY
+            }
+
+            #[inline]
+            fn highest() -> Self {
+                $float::INFINITY
+            }
+
+            #[inline]
+            fn midpoint(min: Self, max: Self) -> Self {
+                let mid = min + (max - min) / 2.0;
+                if mid.is_finite() {
+                    mid
+                } else {
+                    // the difference overflows for bounds near the ends of the range
+                    min / 2.0 + max / 2.0
+                }
+            }
+
+            #[inline]
+            fn abs_diff(a: Self, b: Self) -> Self {
+                if a > b {
+                    a - b
+                } else {
+                    b - a
+                }
+            }
+
+            #[inline]
+            fn sub_fraction(value: Self, fraction: f64) -> Self {
+                value - value.abs() * fraction as $float
+            }
+        }
+    )*};
+}
+
+float_scalar!(f32, f64);
+
+macro_rules! integer_scalar {
+    ($($int:ident),*) => {$(
+        impl Scalar for $int {
+            #[inline]
+            fn is_valid(self) -> bool {
+                true
+            }
+
+            #[inline]
+            fn lowest() -> Self {
+                Bounded::min_value()
+            }
+
+            #[inline]
+            fn highest() -> Self {
+                Bounded::max_value()
+            }
+
+            #[inline]
+            fn midpoint(min: Self, max: Self) -> Self {
+                // halving each side first cannot overflow, and rounding up adds one if
+                // either side was odd
+                let carry = min.rem_euclid(2) | max.rem_euclid(2);
+                min.div_euclid(2) + max.div_euclid(2) + carry
+            }
+
+            #[inline]
+            fn abs_diff(a: Self, b: Self) -> Self {
+                Self::try_from(a.abs_diff(b)).unwrap_or(Self::MAX)
+            }
+
+            #[inline]
+            fn sub_fraction(value: Self, fraction: f64) -> Self {
+                let product = mul_fraction(value.abs_diff(0) as u128, fraction);
+                Self::try_from(product).map_or(Self::MIN, |product| value.saturating_sub(product))
+            }
+        }
+    )*};
+}
+
+integer_scalar!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Returns `value * fraction` rounded down, for a `fraction` from zero to one. The fraction
+/// is split into its integer mantissa and power of two, so that `value` is never rounded.
+fn mul_fraction(value: u128, fraction: f64) -> u128 {
+    let bits = fraction.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as u32;
+    let mantissa = (bits & ((1 << 52) - 1)) as u128;
+    // fraction == mantissa / 2^shift, and shift is at least 52 as the fraction is below one
+    let (mantissa, shift) = if exponent == 0 {
+        (mantissa, 1074)
+    } else if exponent >= 1023 {
+        return value;
+    } else {
+        (mantissa | 1 << 52, 1075 - exponent)
+    };
+
+    // multiplying each 64 bit half of the value by the 53 bit mantissa cannot overflow
+    let high = (value >> 64) * mantissa;
+    let low = (value & u64::MAX as u128) * mantissa;
+    if shift >= 64 {
+        (high + (low >> 64)).checked_shr(shift - 64).unwrap_or(0)
+    } else {
+        (high << (64 - shift)) + (low >> shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scalar;
+
+    #[test]
+    fn integer_midpoints_round_up() {
+        assert_eq!(<i32 as Scalar>::midpoint(0, 1), 1);
+        assert_eq!(<i32 as Scalar>::midpoint(-1, 0), 0);
+        assert_eq!(<i32 as Scalar>::midpoint(-3, 0), -1);
+        assert_eq!(<i32 as Scalar>::midpoint(2, 6), 4);
+        assert_eq!(<u8 as Scalar>::midpoint(254, 255), 255);
+    }
+
+    #[test]
+    fn integer_midpoints_do_not_overflow() {
+        assert_eq!(<i32 as Scalar>::midpoint(i32::MIN, i32::MAX), 0);
+        assert_eq!(<i32 as Scalar>::midpoint(i32::MAX - 1, i32::MAX), i32::MAX);
+        assert_eq!(
+            <u64 as Scalar>::midpoint(u64::MAX - 2, u64::MAX),
+            u64::MAX - 1
+        );
+    }
+
+    #[test]
+    fn integer_differences_saturate() {
+        assert_eq!(<i32 as Scalar>::abs_diff(i32::MIN, i32::MAX), i32::MAX);
+        assert_eq!(<i32 as Scalar>::abs_diff(-5, 5), 10);
+        assert_eq!(<u16 as Scalar>::abs_diff(3, 10), 7);
+    }
+
+    #[test]
+    fn integer_fractions_are_exact() {
+        let large = (1u128 << 100) + 3;
+        assert_eq!(<u128 as Scalar>::sub_fraction(large, 0.5), (1 << 99) + 2);
+        assert_eq!(
+            <u128 as Scalar>::sub_fraction(u128::MAX, 0.25),
+            u128::MAX - (u128::MAX >> 2)
+        );
+        assert_eq!(<u64 as Scalar>::sub_fraction(u64::MAX, 0.0), u64::MAX);
+        assert_eq!(<u64 as Scalar>::sub_fraction((1 << 60) + 1, 1e-18), 1 << 60);
+        assert_eq!(<u64 as Scalar>::sub_fraction(1 << 60, 0.75), 1 << 58);
+        assert_eq!(<i64 as Scalar>::sub_fraction(-7, 0.5), -10);
+        assert_eq!(<i64 as Scalar>::sub_fraction(7, 0.5), 4);
+        assert_eq!(<i128 as Scalar>::sub_fraction(i128::MIN, 0.5), i128::MIN);
+        assert_eq!(<u8 as Scalar>::sub_fraction(255, 1.0), 0);
+    }
+
+    #[test]
+    fn float_fractions_are_relative_to_the_magnitude() {
+        assert_eq!(<f64 as Scalar>::sub_fraction(4.0, 0.25), 3.0);
+        assert_eq!(<f64 as Scalar>::sub_fraction(-4.0, 0.25), -5.0);
+    }
+
+    #[test]
+    fn float_midpoints_do_not_overflow() {
+        assert_eq!(<f32 as Scalar>::midpoint(-f32::MAX, f32::MAX), 0.0);
+        assert_eq!(<f32 as Scalar>::midpoint(0.0, f32::MAX), f32::MAX / 2.0);
+        assert_eq!(<f64 as Scalar>::midpoint(1.0, 2.0), 1.5);
+    }
+
+    #[test]
+    fn float_differences_are_absolute() {
+        assert_eq!(<f64 as Scalar>::abs_diff(1.0, 3.5), 2.5);
+        assert_eq!(<f64 as Scalar>::abs_diff(3.5, 1.0), 2.5);
+    }
+}
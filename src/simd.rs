@@ -0,0 +1,644 @@
+//! Kernels that measure the distance from one query point to many points at once, using
+//! the widest SIMD instructions that the CPU supports at runtime.
+//!
+//! The points are given as one slice per axis, so that a vector of one coordinate from
+//! several points is a single load. Each lane holds a different point and the axes are
+//! summed in order, so the kernels give bitwise the same results as the scalar functions
+//! in `distance`.
+
+use core::any::TypeId;
+use core::slice;
+
+use num_traits::Float;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use num_traits::Zero;
+
+/// Checks for an x86 CPU feature at runtime with `std`, or otherwise only for the features
+/// that the crate was compiled with
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+macro_rules! x86_feature_detected {
+    ($feature:tt) => {
+        is_x86_feature_detected!($feature)
+    };
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+macro_rules! x86_feature_detected {
+    ($feature:tt) => {
+        cfg!(target_feature = $feature)
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) use x86_feature_detected;
+
+/// Calls `$kernel` from the module for the best instruction set available, or `$fallback`
+/// on architectures without one
+macro_rules! dispatch {
+    ($kernel:ident, $fallback:path, $($arg:expr),*) => {{
+        #[cfg(target_arch = "x86_64")]
+        {
+            if x86_feature_detected!("avx2") {
+                unsafe { x86::avx2::$kernel($($arg),*) }
+            } else {
+                // SSE2 is always available on x86_64
+                unsafe { x86::sse::$kernel($($arg),*) }
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            // NEON is always available on aarch64
+            unsafe { aarch64::neon::$kernel($($arg),*) }
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            $fallback($($arg),*)
+        }
+    }};
+}
+
+/// Defines the kernels for an instruction set, given its `Lanes` types for `f32` and `f64`
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+macro_rules! kernels {
+    ($feature:literal, $f32:ty, $f64:ty) => {
+        #[target_feature(enable = $feature)]
+        pub(crate) unsafe fn squared_euclidean_f32<const K: usize>(
+            query: &[f32; K],
+            columns: &[&[f32]; K],
+            out: &mut [f32],
+        ) {
+            crate::simd::squared_euclidean_lanes::<$f32, K>(query, columns, out)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(crate) unsafe fn squared_euclidean_f64<const K: usize>(
+            query: &[f64; K],
+            columns: &[&[f64]; K],
+            out: &mut [f64],
+        ) {
+            crate::simd::squared_euclidean_lanes::<$f64, K>(query, columns, out)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(crate) unsafe fn dot_f32<const K: usize>(
+            query: &[f32; K],
+            columns: &[&[f32]; K],
+            out: &mut [f32],
+        ) {
+            crate::simd::dot_lanes::<$f32, K>(query, columns, out)
+        }
+
+        #[target_feature(enable = $feature)]
+        pub(crate) unsafe fn dot_f64<const K: usize>(
+            query: &[f64; K],
+            columns: &[&[f64]; K],
+            out: &mut [f64],
+        ) {
+            crate::simd::dot_lanes::<$f64, K>(query, columns, out)
+        }
+    };
+}
+
+/// Computes the squared euclidean distance from `query` to each of the points given by
+/// `columns` into `out`, where `columns[dim][i]` is coordinate `dim` of point `i`
+///
+/// # Panics
+///
+/// Panics if a column is shorter than `out`.
+pub(crate) fn squared_euclidean<A: Float + 'static, const K: usize>(
+    query: &[A; K],
+    columns: &[&[A]; K],
+    out: &mut [A],
+) {
+    assert!(columns.iter().all(|column| column.len() >= out.len()));
+
+    if let Some((query, columns, out)) = cast::<A, f32, K>(query, columns, out) {
+        dispatch!(
+            squared_euclidean_f32,
+            scalar::squared_euclidean,
+            query,
+            columns,
+            out
+        )
+    } else if let Some((query, columns, out)) = cast::<A, f64, K>(query, columns, out) {
+        dispatch!(
+            squared_euclidean_f64,
+            scalar::squared_euclidean,
+            query,
+            columns,
+            out
+        )
+    } else {
+        scalar::squared_euclidean(query, columns, out)
+    }
+}
+
+/// Computes the dot product of `query` with each of the points given by `columns` into
+/// `out`, where `columns[dim][i]` is coordinate `dim` of point `i`
+///
+/// # Panics
+///
+/// Panics if a column is shorter than `out`.
+pub(crate) fn dot<A: Float + 'static, const K: usize>(
+    query: &[A; K],
+    columns: &[&[A]; K],
+    out: &mut [A],
+) {
+    assert!(columns.iter().all(|column| column.len() >= out.len()));
+
+    if let Some((query, columns, out)) = cast::<A, f32, K>(query, columns, out) {
+        dispatch!(dot_f32, scalar::dot, query, columns, out)
+    } else if let Some((query, columns, out)) = cast::<A, f64, K>(query, columns, out) {
+        dispatch!(dot_f64, scalar::dot, query, columns, out)
+    } else {
+        scalar::dot(query, columns, out)
+    }
+}
+
+/// Returns the arguments of a kernel as `B`s, if `A` is `B`
+#[allow(clippy::type_complexity)]
+fn cast<'a, 'c, A: 'static, B: 'static, const K: usize>(
+    query: &'a [A; K],
+    columns: &'a [&'c [A]; K],
+    out: &'a mut [A],
+) -> Option<(&'a [B; K], &'a [&'c [B]; K], &'a mut [B])> {
+    if TypeId::of::<A>() != TypeId::of::<B>() {
+        return None;
+    }
+
+    // A and B are the same type, so this only changes the name of the type
+    unsafe {
+        Some((
+            &*(query as *const [A; K] as *const [B; K]),
+            &*(columns as *const [&[A]; K] as *const [&[B]; K]),
+            slice::from_raw_parts_mut(out.as_mut_ptr() as *mut B, out.len()),
+        ))
+    }
+}
+
+pub(crate) mod scalar {
+    use num_traits::Float;
+
+    pub(crate) fn squared_euclidean<A: Float, const K: usize>(
+        query: &[A; K],
+        columns: &[&[A]; K],
+        out: &mut [A],
+    ) {
+        squared_euclidean_from(query, columns, out, 0)
+    }
+
+    /// Computes the distances to the points from index `start` onwards
+    pub(crate) fn squared_euclidean_from<A: Float, const K: usize>(
+        query: &[A; K],
+        columns: &[&[A]; K],
+        out: &mut [A],
+        start: usize,
+    ) {
+        for (idx, dist) in out.iter_mut().enumerate().skip(start) {
+            *dist = (0..K).fold(A::zero(), |acc, dim| {
+                let diff = query[dim] - columns[dim][idx];
+                acc + diff * diff
+            });
+        }
+    }
+
+    pub(crate) fn dot<A: Float, const K: usize>(
+        query: &[A; K],
+        columns: &[&[A]; K],
+        out: &mut [A],
+    ) {
+        dot_from(query, columns, out, 0)
+    }
+
+    /// Computes the dot products with the points from index `start` onwards
+    pub(crate) fn dot_from<A: Float, const K: usize>(
+        query: &[A; K],
+        columns: &[&[A]; K],
+        out: &mut [A],
+        start: usize,
+    ) {
+        for (idx, dist) in out.iter_mut().enumerate().skip(start) {
+            *dist = (0..K).fold(A::zero(), |acc, dim| acc + query[dim] * columns[dim][idx]);
+        }
+    }
+}
+
+/// A vector of `WIDTH` floats
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+trait Lanes: Copy {
+    type Scalar: Float;
+    const WIDTH: usize;
+
+    unsafe fn splat(x: Self::Scalar) -> Self;
+    unsafe fn load(ptr: *const Self::Scalar) -> Self;
+    unsafe fn store(self, ptr: *mut Self::Scalar);
+    unsafe fn add(self, other: Self) -> Self;
+    unsafe fn sub(self, other: Self) -> Self;
+    unsafe fn mul(self, other: Self) -> Self;
+}
+
+/// Every column must be at least as long as `out`
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn squared_euclidean_lanes<V: Lanes, const K: usize>(
+    query: &[V::Scalar; K],
+    columns: &[&[V::Scalar]; K],
+    out: &mut [V::Scalar],
+) {
+    let whole = out.len() - out.len() % V::WIDTH;
+
+    for start in (0..whole).step_by(V::WIDTH) {
+        let mut acc = V::splat(V::Scalar::zero());
+        for dim in 0..K {
+            let column = V::load(columns[dim].as_ptr().add(start));
+            let diff = V::splat(query[dim]).sub(column);
+            acc = acc.add(diff.mul(diff));
+        }
+        acc.store(out.as_mut_ptr().add(start));
+    }
+
+    scalar::squared_euclidean_from(query, columns, out, whole);
+}
+
+/// Every column must be at least as long as `out`
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn dot_lanes<V: Lanes, const K: usize>(
+    query: &[V::Scalar; K],
+    columns: &[&[V::Scalar]; K],
+    out: &mut [V::Scalar],
+) {
+    let whole = out.len() - out.len() % V::WIDTH;
+
+    for start in (0..whole).step_by(V::WIDTH) {
+        let mut acc = V::splat(V::Scalar::zero());
+        for dim in 0..K {
+            let column = V::load(columns[dim].as_ptr().add(start));
+            acc = acc.add(V::splat(query[dim]).mul(column));
+        }
+        acc.store(out.as_mut_ptr().add(start));
+    }
+
+    scalar::dot_from(query, columns, out, whole);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    use super::Lanes;
+
+    impl Lanes for __m128 {
+        type Scalar = f32;
+        const WIDTH: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(x: f32) -> Self {
+            _mm_set1_ps(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f32) -> Self {
+            _mm_loadu_ps(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f32) {
+            _mm_storeu_ps(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm_add_ps(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            _mm_sub_ps(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            _mm_mul_ps(self, other)
+        }
+    }
+
+    impl Lanes for __m128d {
+        type Scalar = f64;
+        const WIDTH: usize = 2;
+
+        #[inline(always)]
+        unsafe fn splat(x: f64) -> Self {
+            _mm_set1_pd(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f64) -> Self {
+            _mm_loadu_pd(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f64) {
+            _mm_storeu_pd(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm_add_pd(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            _mm_sub_pd(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            _mm_mul_pd(self, other)
+        }
+    }
+
+    impl Lanes for __m256 {
+        type Scalar = f32;
+        const WIDTH: usize = 8;
+
+        #[inline(always)]
+        unsafe fn splat(x: f32) -> Self {
+            _mm256_set1_ps(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f32) -> Self {
+            _mm256_loadu_ps(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f32) {
+            _mm256_storeu_ps(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm256_add_ps(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            _mm256_sub_ps(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            _mm256_mul_ps(self, other)
+        }
+    }
+
+    impl Lanes for __m256d {
+        type Scalar = f64;
+        const WIDTH: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(x: f64) -> Self {
+            _mm256_set1_pd(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f64) -> Self {
+            _mm256_loadu_pd(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f64) {
+            _mm256_storeu_pd(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            _mm256_add_pd(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            _mm256_sub_pd(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            _mm256_mul_pd(self, other)
+        }
+    }
+
+    pub(crate) mod sse {
+        use core::arch::x86_64::*;
+
+        kernels!("sse2", __m128, __m128d);
+    }
+
+    pub(crate) mod avx2 {
+        use core::arch::x86_64::*;
+
+        kernels!("avx2", __m256, __m256d);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    use core::arch::aarch64::*;
+
+    use super::Lanes;
+
+    impl Lanes for float32x4_t {
+        type Scalar = f32;
+        const WIDTH: usize = 4;
+
+        #[inline(always)]
+        unsafe fn splat(x: f32) -> Self {
+            vdupq_n_f32(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f32) -> Self {
+            vld1q_f32(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f32) {
+            vst1q_f32(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            vaddq_f32(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            vsubq_f32(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            vmulq_f32(self, other)
+        }
+    }
+
+    impl Lanes for float64x2_t {
+        type Scalar = f64;
+        const WIDTH: usize = 2;
+
+        #[inline(always)]
+        unsafe fn splat(x: f64) -> Self {
+            vdupq_n_f64(x)
+        }
+        #[inline(always)]
+        unsafe fn load(ptr: *const f64) -> Self {
+            vld1q_f64(ptr)
+        }
+        #[inline(always)]
+        unsafe fn store(self, ptr: *mut f64) {
+            vst1q_f64(ptr, self)
+        }
+        #[inline(always)]
+        unsafe fn add(self, other: Self) -> Self {
+            vaddq_f64(self, other)
+        }
+        #[inline(always)]
+        unsafe fn sub(self, other: Self) -> Self {
+            vsubq_f64(self, other)
+        }
+        #[inline(always)]
+        unsafe fn mul(self, other: Self) -> Self {
+            vmulq_f64(self, other)
+        }
+    }
+
+    pub(crate) mod neon {
+        use core::arch::aarch64::*;
+
+        kernels!("neon", float32x4_t, float64x2_t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_points<A: Float, const K: usize>(qty: usize) -> Vec<[A; K]> {
+        (0..qty)
+            .map(|_| {
+                let mut point = [A::zero(); K];
+                for x in point.iter_mut() {
+                    // a range of magnitudes, so that summing in a different order would
+                    // round differently
+                    let exponent = (rand::random::<u8>() % 8) as i32 - 4;
+                    *x = A::from((rand::random::<f64>() - 0.5) * 10f64.powi(exponent)).unwrap();
+                }
+                point
+            })
+            .collect()
+    }
+
+    fn check_kernel<A: Float, const K: usize>(
+        name: &str,
+        kernel: impl Fn(&[A; K], &[&[A]; K], &mut [A]),
+        scalar: impl Fn(&[A; K], &[A; K]) -> A,
+    ) {
+        for qty in 0..40 {
+            let query = random_points::<A, K>(1)[0];
+            let points = random_points::<A, K>(qty);
+            let columns: [Vec<A>; K] =
+                core::array::from_fn(|dim| points.iter().map(|p| p[dim]).collect());
+            let columns: [&[A]; K] = core::array::from_fn(|dim| &columns[dim][..]);
+
+            let mut out = vec![A::nan(); qty];
+            kernel(&query, &columns, &mut out);
+
+            for (dist, p) in out.iter().zip(&points) {
+                assert_eq!(
+                    dist.integer_decode(),
+                    scalar(&query, p).integer_decode(),
+                    "{} kernel differs for K = {}, {} points",
+                    name,
+                    K,
+                    qty
+                );
+            }
+        }
+    }
+
+    fn dot_product<A: Float, const K: usize>(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .fold(A::zero(), |acc, (x, y)| acc + *x * *y)
+    }
+
+    fn check_kernels<const K: usize>() {
+        use crate::distance::squared_euclidean as scalar_squared_euclidean;
+
+        check_kernel::<f32, K>("dispatched", squared_euclidean, scalar_squared_euclidean);
+        check_kernel::<f64, K>("dispatched", squared_euclidean, scalar_squared_euclidean);
+        check_kernel::<f32, K>("dispatched", dot, crate::distance::dot_product);
+        check_kernel::<f64, K>("dispatched", dot, dot_product);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            use x86::{avx2, sse};
+
+            check_kernel(
+                "sse",
+                |q, p, o| unsafe { sse::squared_euclidean_f32::<K>(q, p, o) },
+                scalar_squared_euclidean,
+            );
+            check_kernel(
+                "sse",
+                |q, p, o| unsafe { sse::squared_euclidean_f64::<K>(q, p, o) },
+                scalar_squared_euclidean,
+            );
+            check_kernel(
+                "sse",
+                |q, p, o| unsafe { sse::dot_f32::<K>(q, p, o) },
+                dot_product,
+            );
+            check_kernel(
+                "sse",
+                |q, p, o| unsafe { sse::dot_f64::<K>(q, p, o) },
+                dot_product,
+            );
+
+            if is_x86_feature_detected!("avx2") {
+                check_kernel(
+                    "avx2",
+                    |q, p, o| unsafe { avx2::squared_euclidean_f32::<K>(q, p, o) },
+                    scalar_squared_euclidean,
+                );
+                check_kernel(
+                    "avx2",
+                    |q, p, o| unsafe { avx2::squared_euclidean_f64::<K>(q, p, o) },
+                    scalar_squared_euclidean,
+                );
+                check_kernel(
+                    "avx2",
+                    |q, p, o| unsafe { avx2::dot_f32::<K>(q, p, o) },
+                    dot_product,
+                );
+                check_kernel(
+                    "avx2",
+                    |q, p, o| unsafe { avx2::dot_f64::<K>(q, p, o) },
+                    dot_product,
+                );
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            use aarch64::neon;
+
+            check_kernel(
+                "neon",
+                |q, p, o| unsafe { neon::squared_euclidean_f32::<K>(q, p, o) },
+                scalar_squared_euclidean,
+            );
+            check_kernel(
+                "neon",
+                |q, p, o| unsafe { neon::squared_euclidean_f64::<K>(q, p, o) },
+                scalar_squared_euclidean,
+            );
+            check_kernel(
+                "neon",
+                |q, p, o| unsafe { neon::dot_f32::<K>(q, p, o) },
+                dot_product,
+            );
+            check_kernel(
+                "neon",
+                |q, p, o| unsafe { neon::dot_f64::<K>(q, p, o) },
+                dot_product,
+            );
+        }
+    }
+
+    #[test]
+    fn kernels_match_the_scalar_functions_bitwise() {
+        check_kernels::<1>();
+        check_kernels::<2>();
+        check_kernels::<3>();
+        check_kernels::<4>();
+        check_kernels::<5>();
+        check_kernels::<8>();
+        check_kernels::<13>();
+    }
+}
@@ -0,0 +1,365 @@
+//! Strategies for choosing where a node is split into two children.
+
+use crate::scalar::Scalar;
+
+/// How a `KdTree` chooses the dimension and value at which a node is split in two.
+///
+/// A tree's strategy is chosen when it is created, with `KdTree::with_split_strategy()` or
+/// `KdTree::from_points_with_split_strategy()`, and applies to every leaf that overflows its
+/// capacity as points are added. Trees built in one pass, by `from_points()` or by
+/// `rebalance()`, are always split at the median so that they stay balanced; only the
+/// `MaxVariance` strategy changes the dimension they are split along.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::{KdTree, SplitStrategy};
+///
+/// let mut tree: KdTree<f64, usize, 3> = KdTree::with_split_strategy(8, SplitStrategy::Median)?;
+///
+/// tree.add(&[1.0, 2.0, 5.0], 100)?;
+///
+/// assert_eq!(tree.split_strategy(), SplitStrategy::Median);
+/// # Ok::<(), kiddo::ErrorKind>(())
+/// ```
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Splits halfway between the smallest and largest coordinates of the widest dimension.
+    /// This is the cheapest strategy, and keeps nodes compact, but on clustered data it can
+    /// leave most of a node's points on one side of the split.
+    ///
+    /// Floating point coordinates that differ by only a few ulps may have no value between
+    /// them that separates them, in which case the next widest dimension is tried. If no
+    /// dimension can be split, the leaf is left over capacity until a point that can be split
+    /// from the others is added.
+    #[default]
+    Midpoint,
+    /// Splits at the median coordinate of the widest dimension, so that each child holds
+    /// half of the points. Splitting is slower, as the points must be partially sorted.
+    Median,
+    /// Splits at the midpoint of the widest dimension, like `Midpoint`, but if every point
+    /// would fall on one side, slides the split to the nearest point so that both children
+    /// hold at least one. As a node's bounds fit its points, this only happens when the
+    /// midpoint cannot be told apart from the smallest coordinate, where `Midpoint` would
+    /// try another dimension or leave the leaf over capacity.
+    SlidingMidpoint,
+    /// Splits at the median coordinate of the dimension in which the points' coordinates
+    /// have the greatest variance, rather than the widest one. This resists the few outliers
+    /// that can stretch a node's bounds along a dimension in which most of its points are
+    /// close together.
+    MaxVariance,
+}
+
+impl SplitStrategy {
+    /// Returns true if a node whose points span `min_bounds..=max_bounds` can be split into
+    /// two non-empty children. Only depends on the bounds, so it is cheap to check before
+    /// every attempt to split a leaf that has been left over capacity.
+    pub(crate) fn can_split<A: Scalar, const K: usize>(
+        self,
+        min_bounds: &[A; K],
+        max_bounds: &[A; K],
+    ) -> bool {
+        match self {
+            SplitStrategy::Midpoint => midpoint_dimension(min_bounds, max_bounds).is_some(),
+            _ => widest_dimension(min_bounds, max_bounds).is_some(),
+        }
+    }
+
+    /// Reorders `entries` so that every point that belongs in the left child comes first.
+    /// Returns the split dimension, the split value and the number of entries that belong
+    /// in the left child, or `None` if the points cannot be split into two non-empty
+    /// children, such as when they are all identical.
+    pub(crate) fn partition<A: Scalar, T, const K: usize>(
+        self,
+        entries: &mut [([A; K], T)],
+        min_bounds: &[A; K],
+        max_bounds: &[A; K],
+    ) -> Option<(usize, A, usize)> {
+        let (dim, split_value, left_len) = match self {
+            SplitStrategy::Midpoint => {
+                let dim = midpoint_dimension(min_bounds, max_bounds)?;
+                let split_value = A::midpoint(min_bounds[dim], max_bounds[dim]);
+                (
+                    dim,
+                    split_value,
+                    partition(entries, |p| p[dim] < split_value),
+                )
+            }
+            SplitStrategy::SlidingMidpoint => {
+                let dim = widest_dimension(min_bounds, max_bounds)?;
+                let mut split_value = A::midpoint(min_bounds[dim], max_bounds[dim]);
+                let mut left_len = partition(entries, |p| p[dim] < split_value);
+                if left_len == 0 {
+                    split_value = next_above(entries, dim, min_bounds[dim], max_bounds[dim]);
+                    left_len = partition(entries, |p| p[dim] < split_value);
+                }
+                (dim, split_value, left_len)
+            }
+            SplitStrategy::Median | SplitStrategy::MaxVariance => {
+                let dim = self.split_dimension(entries, min_bounds, max_bounds)?;
+                median_partition(entries, dim, max_bounds[dim])
+            }
+        };
+
+        non_empty(entries, (dim, split_value, left_len))
+    }
+
+    /// Reorders `entries` like `partition()`, but always splits at the median, so that
+    /// a tree built by splitting recursively is balanced
+    pub(crate) fn balanced_partition<A: Scalar, T, const K: usize>(
+        self,
+        entries: &mut [([A; K], T)],
+        min_bounds: &[A; K],
+        max_bounds: &[A; K],
+    ) -> Option<(usize, A, usize)> {
+        let dim = self.split_dimension(entries, min_bounds, max_bounds)?;
+        let split = median_partition(entries, dim, max_bounds[dim]);
+        non_empty(entries, split)
+    }
+
+    fn split_dimension<A: Scalar, T, const K: usize>(
+        self,
+        entries: &[([A; K], T)],
+        min_bounds: &[A; K],
+        max_bounds: &[A; K],
+    ) -> Option<usize> {
+        let widest = widest_dimension(min_bounds, max_bounds)?;
+        match self {
+            SplitStrategy::MaxVariance => Some(max_variance_dimension(entries).unwrap_or(widest)),
+            _ => Some(widest),
+        }
+    }
+}
+
+/// Returns the dimension along which the bounds are widest, or `None` if they are a
+/// single point
+pub(crate) fn widest_dimension<A: Scalar, const K: usize>(
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
+) -> Option<usize> {
+    let mut split_dimension: Option<usize> = None;
+    let mut max = A::zero();
+    for dim in 0..K {
+        // the difference saturates for integer types, and is NaN for empty float bounds
+        let diff = A::abs_diff(max_bounds[dim], min_bounds[dim]);
+        if diff > max {
+            max = diff;
+            split_dimension = Some(dim);
+        }
+    }
+    split_dimension
+}
+
+/// Returns the widest dimension whose midpoint separates its smallest coordinate from its
+/// largest, or `None` if there is none
+fn midpoint_dimension<A: Scalar, const K: usize>(
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
+) -> Option<usize> {
+    let mut split_dimension: Option<usize> = None;
+    let mut max = A::zero();
+    for dim in 0..K {
+        let (lower, upper) = (min_bounds[dim], max_bounds[dim]);
+        // points at `lower` go left of the midpoint, and points at `upper` go right of it
+        let mid = A::midpoint(lower, upper);
+        let diff = A::abs_diff(upper, lower);
+        if lower < mid && mid <= upper && diff > max {
+            max = diff;
+            split_dimension = Some(dim);
+        }
+    }
+    split_dimension
+}
+
+/// Returns `split` unless it would leave one of the children empty, which would send every
+/// point to the same child each time the leaf is split
+fn non_empty<A, T, const K: usize>(
+    entries: &[([A; K], T)],
+    split: (usize, A, usize),
+) -> Option<(usize, A, usize)> {
+    let left_len = split.2;
+    if left_len == 0 || left_len == entries.len() {
+        None
+    } else {
+        Some(split)
+    }
+}
+
+/// Returns the dimension in which the coordinates of `entries` have the greatest variance,
+/// or `None` if it is zero in every dimension
+fn max_variance_dimension<A: Scalar, T, const K: usize>(entries: &[([A; K], T)]) -> Option<usize> {
+    let len = entries.len() as f64;
+    let coord = |point: &[A; K], dim: usize| point[dim].to_f64().unwrap_or(0.0);
+
+    let mut split_dimension = None;
+    let mut max = 0.0;
+    for dim in 0..K {
+        let mean = entries.iter().map(|(p, _)| coord(p, dim)).sum::<f64>() / len;
+        let variance = entries
+            .iter()
+            .map(|(p, _)| {
+                let diff = coord(p, dim) - mean;
+                diff * diff
+            })
+            .sum::<f64>();
+        if variance > max {
+            max = variance;
+            split_dimension = Some(dim);
+        }
+    }
+    split_dimension
+}
+
+/// Reorders `entries` so that every point that belongs in the left child of a split at the
+/// median of dimension `dim` comes first, where the largest coordinate of `entries` in that
+/// dimension is `max_bound`. Returns the split dimension, the split value and the number of
+/// entries that belong in the left child. Both children are guaranteed to be non-empty if
+/// the coordinates in dimension `dim` are not all the same.
+fn median_partition<A: Scalar, T, const K: usize>(
+    entries: &mut [([A; K], T)],
+    dim: usize,
+    max_bound: A,
+) -> (usize, A, usize) {
+    let mid = entries.len() / 2;
+    entries.select_nth_unstable_by(mid, |a, b| a.0[dim].partial_cmp(&b.0[dim]).unwrap());
+    let mut split_value = entries[mid].0[dim];
+
+    // entries before `mid` are <= the median, but only those strictly below it can go left
+    let mut left_len = partition(&mut entries[..mid], |p| p[dim] < split_value);
+    if left_len == 0 {
+        // the median is also the minimum, so split just above it instead
+        split_value = next_above(&entries[mid..], dim, split_value, max_bound);
+        left_len = partition(entries, |p| p[dim] < split_value);
+    }
+
+    (dim, split_value, left_len)
+}
+
+/// Returns the smallest coordinate of `entries` in dimension `dim` that is above `value`,
+/// or `max_bound` if there is none
+fn next_above<A: Scalar, T, const K: usize>(
+    entries: &[([A; K], T)],
+    dim: usize,
+    value: A,
+    max_bound: A,
+) -> A {
+    entries
+        .iter()
+        .map(|(p, _)| p[dim])
+        .filter(|v| *v > value)
+        .fold(max_bound, |min, v| if v < min { v } else { min })
+}
+
+fn partition<A, T, const K: usize, P>(entries: &mut [([A; K], T)], pred: P) -> usize
+where
+    P: Fn(&[A; K]) -> bool,
+{
+    let mut left_len = 0;
+    for i in 0..entries.len() {
+        if pred(&entries[i].0) {
+            entries.swap(i, left_len);
+            left_len += 1;
+        }
+    }
+    left_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SplitStrategy;
+
+    fn entries(xs: &[f64]) -> Vec<([f64; 2], usize)> {
+        xs.iter().enumerate().map(|(i, x)| ([*x, 0.0], i)).collect()
+    }
+
+    #[test]
+    fn midpoint_splits_halfway_across_the_bounds() {
+        let mut points = entries(&[0.0, 0.1, 0.2, 0.3, 10.0]);
+        let split = SplitStrategy::Midpoint.partition(&mut points, &[0.0, 0.0], &[10.0, 0.0]);
+        assert_eq!(split, Some((0, 5.0, 4)));
+    }
+
+    #[test]
+    fn median_splits_half_of_the_points_to_each_side() {
+        let mut points = entries(&[0.0, 0.1, 0.2, 0.3, 10.0]);
+        let split = SplitStrategy::Median.partition(&mut points, &[0.0, 0.0], &[10.0, 0.0]);
+        assert_eq!(split, Some((0, 0.2, 2)));
+        assert!(points[..2].iter().all(|(p, _)| p[0] < 0.2));
+    }
+
+    #[test]
+    fn sliding_midpoint_slides_to_the_nearest_point() {
+        // the midpoint of two adjacent floats rounds down to the smaller one
+        let above = 1.0 + f64::EPSILON;
+        let mut points = entries(&[1.0, 1.0, above]);
+        let midpoint = SplitStrategy::Midpoint.partition(&mut points, &[1.0, 0.0], &[above, 0.0]);
+        assert_eq!(midpoint, None);
+
+        let sliding =
+            SplitStrategy::SlidingMidpoint.partition(&mut points, &[1.0, 0.0], &[above, 0.0]);
+        assert_eq!(sliding, Some((0, above, 2)));
+    }
+
+    #[test]
+    fn midpoint_tries_the_next_widest_dimension() {
+        // x cannot be split at its midpoint, but the narrower y can
+        let above = 1.0 + f64::EPSILON;
+        let mut points = vec![([1.0, 0.0], 0), ([above, 0.0], 1), ([1.0, 1e-20], 2)];
+        let split = SplitStrategy::Midpoint.partition(&mut points, &[1.0, 0.0], &[above, 1e-20]);
+        assert_eq!(split, Some((1, 5e-21, 2)));
+        assert!(SplitStrategy::Midpoint.can_split(&[1.0, 0.0], &[above, 1e-20]));
+        assert!(!SplitStrategy::Midpoint.can_split(&[1.0, 0.0], &[above, 0.0]));
+    }
+
+    #[test]
+    fn no_strategy_leaves_a_child_empty() {
+        // the median is the minimum, and the midpoint cannot be told apart from it
+        let above = 1.0f32 + f32::EPSILON;
+        for strategy in [
+            SplitStrategy::Midpoint,
+            SplitStrategy::Median,
+            SplitStrategy::SlidingMidpoint,
+            SplitStrategy::MaxVariance,
+        ] {
+            let mut points: Vec<([f32; 1], usize)> = (0..10)
+                .map(|i| ([if i < 8 { 1.0 } else { above }], i))
+                .collect();
+            if let Some((_, _, left_len)) = strategy.partition(&mut points, &[1.0], &[above]) {
+                assert!(left_len > 0 && left_len < points.len());
+            }
+        }
+    }
+
+    #[test]
+    fn max_variance_ignores_a_single_outlier() {
+        // x is widest because of one outlier, but y varies more across the other points
+        let mut points: Vec<([f64; 2], usize)> = (0..100)
+            .map(|i| ([0.0, i as f64 / 10.0], i))
+            .chain(Some(([12.0, 0.0], 100)))
+            .collect();
+        let (min_bounds, max_bounds) = ([0.0, 0.0], [12.0, 9.9]);
+
+        let split = SplitStrategy::MaxVariance.partition(&mut points, &min_bounds, &max_bounds);
+        assert_eq!(split.map(|(dim, ..)| dim), Some(1));
+
+        let split = SplitStrategy::Median.partition(&mut points, &min_bounds, &max_bounds);
+        assert_eq!(split.map(|(dim, ..)| dim), Some(0));
+    }
+
+    #[test]
+    fn identical_points_are_not_split() {
+        for strategy in [
+            SplitStrategy::Midpoint,
+            SplitStrategy::Median,
+            SplitStrategy::SlidingMidpoint,
+            SplitStrategy::MaxVariance,
+        ] {
+            let mut points = entries(&[1.0, 1.0, 1.0]);
+            assert_eq!(
+                strategy.partition(&mut points, &[1.0, 0.0], &[1.0, 0.0]),
+                None
+            );
+        }
+    }
+}
@@ -1,17 +1,49 @@
-use num_traits::Float;
+use crate::distance::Distance;
+use crate::kiddo::ErrorKind;
+use crate::scalar::Scalar;
 
-pub fn distance_to_space_mod<F, T>(
-    p1: &[T],
-    min_bounds: &[T],
-    max_bounds: &[T],
+pub fn check_point<A: Scalar, const K: usize>(point: &[A; K]) -> Result<(), ErrorKind> {
+    if point.iter().all(|n| n.is_valid()) {
+        Ok(())
+    } else {
+        Err(ErrorKind::NonFiniteCoordinate)
+    }
+}
+
+/// Returns true if the box given by `min_bounds` and `max_bounds` lies entirely inside the
+/// query box given by `min` and `max`
+pub fn bounds_within_box<A: PartialOrd, const K: usize>(
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
+    min: &[A; K],
+    max: &[A; K],
+) -> bool {
+    (0..K).all(|dim| min_bounds[dim] >= min[dim] && max_bounds[dim] <= max[dim])
+}
+
+/// Returns true if the box given by `min_bounds` and `max_bounds` overlaps the
+/// query box given by `min` and `max`
+pub fn bounds_intersect_box<A: PartialOrd, const K: usize>(
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
+    min: &[A; K],
+    max: &[A; K],
+) -> bool {
+    (0..K).all(|dim| max_bounds[dim] >= min[dim] && min_bounds[dim] <= max[dim])
+}
+
+pub fn distance_to_space<F, A, D, const K: usize>(
+    p1: &[A; K],
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
     distance: &F,
-) -> T
+) -> D
 where
-    F: Fn(&[T], &[T]) -> T,
-    T: Float,
+    F: Fn(&[A; K], &[A; K]) -> D,
+    A: Scalar,
 {
-    let mut p2 = p1.to_owned();//[T::nan(); p1.len()];
-    for i in 0..p1.len() {
+    let mut p2 = *p1;
+    for i in 0..K {
         if p1[i] > max_bounds[i] {
             p2[i] = max_bounds[i];
         } else if p1[i] < min_bounds[i] {
@@ -23,34 +55,74 @@ where
     distance(p1, &p2)
 }
 
-pub fn distance_to_space<F, T, const K: usize>(
-    p1: &[T; K],
-    min_bounds: &[T; K],
-    max_bounds: &[T; K],
+/// Returns the distance from `p1` to the furthest point of the box given by `min_bounds`
+/// and `max_bounds`. Every point in the box is within this distance of `p1` for any metric
+/// that does not decrease as the difference along an axis grows.
+pub fn distance_to_furthest_corner<F, A, D, const K: usize>(
+    p1: &[A; K],
+    min_bounds: &[A; K],
+    max_bounds: &[A; K],
     distance: &F,
-) -> T
+) -> D
 where
-    F: Fn(&[T; K], &[T; K]) -> T,
-    T: Float,
+    F: Distance<A, K, D> + ?Sized,
+    A: Scalar,
+    D: Scalar,
 {
-    let mut p2 = [T::nan(); K];
+    let mut p2 = *p1;
     for i in 0..K {
-        if p1[i] > max_bounds[i] {
-            p2[i] = max_bounds[i];
-        } else if p1[i] < min_bounds[i] {
+        if A::abs_diff(p1[i], min_bounds[i]) > A::abs_diff(max_bounds[i], p1[i]) {
             p2[i] = min_bounds[i];
         } else {
-            p2[i] = p1[i];
+            p2[i] = max_bounds[i];
+        }
+    }
+    distance.dist(p1, &p2)
+}
+
+/// The number of points that are measured together by `Distance::dist_batch()`
+pub const LEAF_BATCH: usize = 32;
+
+/// Measures the distance from `point` to each of the first `len` points in `columns`, where
+/// `columns[dim][i]` is coordinate `dim` of point `i`, passing the index and distance of
+/// each point to `visit`. Metrics that opt in with `Distance::BATCHED` measure up to
+/// `LEAF_BATCH` points per call to `dist_batch()`.
+pub fn for_each_dist<A, D, F, V, const K: usize>(
+    point: &[A; K],
+    columns: &[&[A]; K],
+    len: usize,
+    distance: &F,
+    mut visit: V,
+) where
+    A: Scalar,
+    D: Scalar,
+    F: Distance<A, K, D>,
+    V: FnMut(usize, D),
+{
+    if F::BATCHED {
+        let mut dists = [D::zero(); LEAF_BATCH];
+        for start in (0..len).step_by(LEAF_BATCH) {
+            let end = core::cmp::min(start + LEAF_BATCH, len);
+            let batch: [&[A]; K] = core::array::from_fn(|dim| &columns[dim][start..end]);
+            let dists = &mut dists[..end - start];
+            distance.dist_batch(point, &batch, dists);
+
+            for (offset, dist) in dists.iter().enumerate() {
+                visit(start + offset, *dist);
+            }
+        }
+    } else {
+        let gather = |idx: usize| -> [A; K] { core::array::from_fn(|dim| columns[dim][idx]) };
+        for idx in 0..len {
+            visit(idx, distance.dist(point, &gather(idx)));
         }
     }
-    distance(p1, &p2)
 }
 
 #[cfg(test)]
 mod tests {
     use super::distance_to_space;
     use crate::distance::squared_euclidean;
-    use std::f64::{INFINITY, NEG_INFINITY};
 
     #[test]
     fn test_normal_distance_to_space() {
@@ -63,7 +135,7 @@ mod tests {
         let dis = distance_to_space(
             &[0.0, 0.0],
             &[1.0, 1.0],
-            &[INFINITY, INFINITY],
+            &[f64::INFINITY, f64::INFINITY],
             &squared_euclidean,
         );
         assert_eq!(dis, 2.0);
@@ -73,8 +145,8 @@ mod tests {
     fn test_distance_inside_inf() {
         let dis = distance_to_space(
             &[2.0, 2.0],
-            &[NEG_INFINITY, NEG_INFINITY],
-            &[INFINITY, INFINITY],
+            &[f64::NEG_INFINITY, f64::NEG_INFINITY],
+            &[f64::INFINITY, f64::INFINITY],
             &squared_euclidean,
         );
         assert_eq!(dis, 0.0);
@@ -90,8 +162,8 @@ mod tests {
     fn distance_to_half_space() {
         let dis = distance_to_space(
             &[-2.0, 0.0],
-            &[0.0, NEG_INFINITY],
-            &[INFINITY, INFINITY],
+            &[0.0, f64::NEG_INFINITY],
+            &[f64::INFINITY, f64::INFINITY],
             &squared_euclidean,
         );
         assert_eq!(dis, 4.0);
@@ -0,0 +1,227 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force, random_points, random_queries, random_tree};
+use kiddo::distance::{squared_euclidean, Distance, InnerProduct, Manhattan, SquaredEuclidean};
+use kiddo::{ErrorKind, ImmutableKdTree, KdTree};
+use rand::Rng;
+
+const EPSILONS: [f64; 4] = [0.0, 0.1, 1.0, 10.0];
+
+#[test]
+fn it_finds_neighbours_within_a_factor_of_the_true_distance() {
+    let points: Vec<([f64; 3], usize)> = random_points(2000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+
+    for query in random_queries(100) {
+        let expected = brute_force(&points, &query, &squared_euclidean);
+
+        for &epsilon in EPSILONS.iter() {
+            let nearest = kdtree
+                .nearest_approx(&query, 10, epsilon, &SquaredEuclidean)
+                .unwrap();
+            assert_eq!(nearest.len(), 10);
+            for ((dist, &item), (expected_dist, _)) in nearest.iter().zip(&expected) {
+                assert_eq!(*dist, squared_euclidean(&query, &points[item].0));
+                assert!(*dist <= expected_dist * (1.0 + epsilon));
+            }
+            assert!(nearest.windows(2).all(|w| w[0].0 <= w[1].0));
+
+            let (dist, &item) = kdtree
+                .nearest_one_approx(&query, epsilon, &SquaredEuclidean)
+                .unwrap();
+            assert_eq!(dist, squared_euclidean(&query, &points[item].0));
+            assert!(dist <= expected[0].0 * (1.0 + epsilon));
+        }
+    }
+}
+
+#[test]
+fn it_bounds_negative_distances() {
+    let points: Vec<([f32; 8], usize)> = random_points(2000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+    let mut approximated = 0;
+
+    for query in random_queries(100) {
+        let expected = brute_force(&points, &query, &InnerProduct);
+        // no element the search skipped is closer than this
+        let bound = |dist: f32, epsilon: f64| {
+            dist as f64 - (dist as f64).abs() * epsilon / (1.0 + epsilon) - 1e-4
+        };
+
+        for &epsilon in EPSILONS.iter() {
+            let nearest = kdtree
+                .nearest_approx(&query, 10, epsilon, &InnerProduct)
+                .unwrap();
+            assert_eq!(nearest.len(), 10);
+            for ((dist, _), (expected_dist, _)) in nearest.iter().zip(&expected) {
+                assert!(*expected_dist as f64 >= bound(*dist, epsilon));
+            }
+
+            let (dist, _) = kdtree
+                .nearest_one_approx(&query, epsilon, &InnerProduct)
+                .unwrap();
+            assert!(expected[0].0 as f64 >= bound(dist, epsilon));
+            if dist > expected[0].0 + 1e-4 {
+                approximated += 1;
+            }
+        }
+    }
+
+    // a large epsilon must skip nodes, so some results are not the nearest
+    assert!(approximated > 0);
+}
+
+#[test]
+fn it_matches_the_exact_search_without_an_epsilon() {
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(1000));
+
+    for query in random_queries(100) {
+        assert_eq!(
+            kdtree.nearest_approx(&query, 5, 0.0, &Manhattan).unwrap(),
+            kdtree.nearest(&query, 5, &Manhattan).unwrap()
+        );
+        assert_eq!(
+            kdtree.nearest_one_approx(&query, 0.0, &Manhattan).unwrap(),
+            kdtree.nearest_one(&query, &Manhattan).unwrap()
+        );
+    }
+}
+
+#[test]
+fn it_stops_searching_when_the_budget_is_spent() {
+    let points: Vec<([f64; 3], usize)> = (0..64).map(|i| ([i as f64, 0.0, 0.0], i)).collect();
+    let kdtree = KdTree::from_points_with_capacity(points, 4).unwrap();
+    let query = [33.4, 0.0, 0.0];
+
+    // the first leaf searched is the one holding the query point
+    let nearest = kdtree
+        .nearest_approx_with_budget(&query, 10, 0.0, 1, &squared_euclidean)
+        .unwrap();
+    assert_eq!(nearest.len(), 4);
+    assert_eq!(*nearest[0].1, 33);
+
+    let nearest = kdtree
+        .nearest_approx_with_budget(&query, 10, 0.0, 3, &squared_euclidean)
+        .unwrap();
+    assert_eq!(nearest.len(), 10);
+
+    assert_eq!(
+        kdtree
+            .nearest_approx_with_budget(&query, 10, 0.0, usize::MAX, &squared_euclidean)
+            .unwrap(),
+        kdtree.nearest(&query, 10, &squared_euclidean).unwrap()
+    );
+    assert_eq!(
+        kdtree
+            .nearest_one_approx_with_budget(&query, 0.0, 1, &squared_euclidean)
+            .unwrap()
+            .1,
+        &33
+    );
+}
+
+#[test]
+fn it_always_finds_one_element_within_the_budget() {
+    let mut kdtree = KdTree::with_capacity(1).unwrap();
+    kdtree.add(&[0.0, 0.0, 0.0], 0).unwrap();
+    kdtree.add(&[10.0, 0.0, 0.0], 1).unwrap();
+
+    let nearest = kdtree
+        .nearest_one_approx_with_budget(&[4.0, 0.0, 0.0], 0.0, 0, &squared_euclidean)
+        .unwrap();
+    assert_eq!(nearest, (16.0, &0));
+    assert!(kdtree
+        .nearest_approx_with_budget(&[4.0, 0.0, 0.0], 1, 0.0, 0, &squared_euclidean)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn it_searches_integer_coordinates() {
+    let mut rng = rand::thread_rng();
+    let points: Vec<([i32; 2], usize)> = (0..500)
+        .map(|i| ([rng.gen_range(-1000, 1000), rng.gen_range(-1000, 1000)], i))
+        .collect();
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+
+    for _ in 0..50 {
+        let query = [rng.gen_range(-1000, 1000), rng.gen_range(-1000, 1000)];
+        let expected = points
+            .iter()
+            .map(|(p, _)| SquaredEuclidean.dist(&query, p))
+            .min()
+            .unwrap();
+
+        let (dist, _) = kdtree
+            .nearest_one_approx(&query, 0.5, &SquaredEuclidean)
+            .unwrap();
+        assert!(dist as f64 <= expected as f64 * 1.5);
+    }
+}
+
+#[test]
+fn it_compares_large_integer_distances_exactly() {
+    // the query is nearer to the first point in the split dimension, so its leaf is searched
+    // first and finds a distance of 4k, and with an epsilon of one the second leaf is only
+    // searched if it is no further than 2k. Distances this large are rounded by an f64,
+    // which cannot tell 2k from 2k + 1.
+    let k: i64 = (1 << 60) + 1;
+    let query = [k, 3 * k];
+
+    for (offset, expected) in [(0, (2 * k) as u128), (1, (4 * k) as u128)] {
+        let far = 3 * k + offset;
+        let points = vec![([0, 0], 0), ([far, 3 * k], 1)];
+        let kdtree: KdTree<i64, usize, 2> = KdTree::from_points_with_capacity(points, 1).unwrap();
+
+        let (dist, _) = kdtree.nearest_one_approx(&query, 1.0, &Manhattan).unwrap();
+        assert_eq!(dist, expected);
+        let nearest = kdtree.nearest_approx(&query, 1, 1.0, &Manhattan).unwrap();
+        assert_eq!(nearest[0].0, expected);
+
+        let immutable: ImmutableKdTree<i64, usize, 2> = kdtree.into();
+        let (dist, _) = immutable
+            .nearest_one_approx(&query, 1.0, &Manhattan)
+            .unwrap();
+        assert_eq!(dist, expected);
+    }
+}
+
+#[test]
+fn it_rejects_invalid_epsilons() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::new();
+    kdtree.add(&[0.0, 0.0, 0.0], 0).unwrap();
+
+    for &epsilon in [-0.1, f64::NAN, f64::INFINITY].iter() {
+        assert_eq!(
+            kdtree
+                .nearest_approx(&[0.0, 0.0, 0.0], 1, epsilon, &squared_euclidean)
+                .unwrap_err(),
+            ErrorKind::InvalidEpsilon
+        );
+        assert_eq!(
+            kdtree
+                .nearest_one_approx(&[0.0, 0.0, 0.0], epsilon, &squared_euclidean)
+                .unwrap_err(),
+            ErrorKind::InvalidEpsilon
+        );
+    }
+}
+
+#[test]
+fn it_handles_an_empty_tree() {
+    let kdtree: KdTree<f64, usize, 3> = KdTree::new();
+
+    assert!(kdtree
+        .nearest_approx(&[0.0, 0.0, 0.0], 1, 0.5, &squared_euclidean)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        kdtree
+            .nearest_one_approx(&[0.0, 0.0, 0.0], 0.5, &squared_euclidean)
+            .unwrap_err(),
+        ErrorKind::Empty
+    );
+}
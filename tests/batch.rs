@@ -0,0 +1,116 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{random_points, random_queries, random_tree};
+use kiddo::distance::squared_euclidean;
+use kiddo::ErrorKind;
+use kiddo::KdTree;
+
+#[test]
+fn nearest_one_batch_matches_nearest_one() {
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(1000));
+    let queries = random_queries(200);
+
+    let results = kdtree
+        .nearest_one_batch(&queries, &squared_euclidean)
+        .unwrap();
+
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(results) {
+        assert_eq!(
+            result,
+            kdtree.nearest_one(query, &squared_euclidean).unwrap()
+        );
+    }
+}
+
+#[test]
+fn nearest_batch_matches_iter_nearest() {
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(1000));
+    let queries = random_queries(200);
+
+    let results = kdtree
+        .nearest_batch(&queries, 10, &squared_euclidean)
+        .unwrap();
+
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(results) {
+        let expected: Vec<f64> = kdtree
+            .iter_nearest(query, &squared_euclidean)
+            .unwrap()
+            .take(10)
+            .map(|(d, _)| d)
+            .collect();
+        assert_eq!(result.iter().map(|(d, _)| *d).collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn within_batch_matches_within() {
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(1000));
+    let queries = random_queries(200);
+
+    let results = kdtree
+        .within_batch(&queries, 0.02, &squared_euclidean)
+        .unwrap();
+
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(results) {
+        let expected = kdtree.within(query, 0.02, &squared_euclidean).unwrap();
+        assert_eq!(
+            result.iter().map(|(d, _)| *d).collect::<Vec<_>>(),
+            expected.iter().map(|(d, _)| *d).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn batches_handle_empty_trees_and_invalid_points() {
+    let kdtree: KdTree<f64, usize, 3> = KdTree::new();
+
+    assert_eq!(
+        kdtree.nearest_one_batch(&[[0f64; 3]], &squared_euclidean),
+        Err(ErrorKind::Empty)
+    );
+    assert_eq!(
+        kdtree.nearest_batch(&[[0f64; 3]], 1, &squared_euclidean),
+        Ok(vec![vec![]])
+    );
+    assert_eq!(
+        kdtree.within_batch(&[[0f64; 3]], 1.0, &squared_euclidean),
+        Ok(vec![vec![]])
+    );
+
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(10));
+    let queries = [[0f64; 3], [f64::NAN; 3]];
+    assert_eq!(
+        kdtree.nearest_batch(&queries, 1, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+    assert_eq!(
+        kdtree.within_batch(&queries, 1.0, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_batches_match_sequential_batches() {
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&random_points(10_000));
+    let queries = random_queries(1000);
+
+    assert_eq!(
+        kdtree.par_nearest_one_batch(&queries, &squared_euclidean),
+        kdtree.nearest_one_batch(&queries, &squared_euclidean)
+    );
+    assert_eq!(
+        kdtree.par_nearest_batch(&queries, 5, &squared_euclidean),
+        kdtree.nearest_batch(&queries, 5, &squared_euclidean)
+    );
+    assert_eq!(
+        kdtree.par_within_batch(&queries, 0.01, &squared_euclidean),
+        kdtree.within_batch(&queries, 0.01, &squared_euclidean)
+    );
+}
@@ -0,0 +1,134 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{random_points, random_queries};
+use kiddo::distance::{squared_euclidean, SquaredEuclidean};
+use kiddo::{ImmutableKdTree, KdTree};
+
+/// `SquaredEuclidean` measures leaves with batch kernels, while the plain function measures
+/// one point at a time, and both must give exactly the same results
+fn assert_batched_matches_per_point(kdtree: &KdTree<f32, usize, 3>) {
+    for query in random_queries(50) {
+        assert_eq!(
+            kdtree.nearest(&query, 10, &SquaredEuclidean).unwrap(),
+            kdtree.nearest(&query, 10, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.nearest_one(&query, &SquaredEuclidean).unwrap(),
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.within(&query, 0.05, &SquaredEuclidean).unwrap(),
+            kdtree.within(&query, 0.05, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree
+                .count_within(&query, 0.05, &SquaredEuclidean)
+                .unwrap(),
+            kdtree
+                .count_within(&query, 0.05, &squared_euclidean)
+                .unwrap()
+        );
+
+        let mut best = kdtree
+            .best_n_within(&query, 0.05, 5, &SquaredEuclidean)
+            .unwrap();
+        let mut expected_best = kdtree
+            .best_n_within(&query, 0.05, 5, &squared_euclidean)
+            .unwrap();
+        best.sort_unstable();
+        expected_best.sort_unstable();
+        assert_eq!(best, expected_best);
+
+        let iterated: Vec<_> = kdtree
+            .iter_nearest(&query, &SquaredEuclidean)
+            .unwrap()
+            .take(20)
+            .collect();
+        let expected_iterated: Vec<_> = kdtree
+            .iter_nearest(&query, &squared_euclidean)
+            .unwrap()
+            .take(20)
+            .collect();
+        assert_eq!(iterated, expected_iterated);
+    }
+}
+
+#[test]
+fn it_matches_per_point_distances_after_adding_points() {
+    // a capacity larger than a batch, so leaves are measured in several batches
+    let mut kdtree = KdTree::with_capacity(50).unwrap();
+    for (point, data) in random_points(2000) {
+        kdtree.add(&point, data).unwrap();
+    }
+
+    assert_batched_matches_per_point(&kdtree);
+}
+
+#[test]
+fn it_matches_per_point_distances_after_building_from_points() {
+    let kdtree = KdTree::from_points_with_capacity(random_points(2000), 37).unwrap();
+
+    assert_batched_matches_per_point(&kdtree);
+}
+
+#[test]
+fn it_matches_per_point_distances_after_moving_and_removing_points() {
+    let mut points = random_points(2000);
+    let mut kdtree = KdTree::from_points_with_capacity(points.clone(), 40).unwrap();
+
+    for (point, data) in points.iter_mut() {
+        let new_point = [
+            point[0] + (rand::random::<f32>() - 0.5) * 0.02,
+            point[1] + (rand::random::<f32>() - 0.5) * 0.02,
+            point[2] + (rand::random::<f32>() - 0.5) * 0.02,
+        ];
+        assert!(kdtree.update_position(point, &new_point, data).unwrap());
+        *point = new_point;
+    }
+    assert_batched_matches_per_point(&kdtree);
+
+    for (point, data) in points.iter().filter(|(_, data)| data % 3 == 0) {
+        assert_eq!(kdtree.remove(point, data).unwrap(), 1);
+    }
+    assert_batched_matches_per_point(&kdtree);
+
+    kdtree.retain(|_, data| data % 5 != 0);
+    kdtree.rebalance();
+    assert_batched_matches_per_point(&kdtree);
+}
+
+#[test]
+fn it_matches_per_point_distances_in_an_immutable_tree() {
+    let kdtree = KdTree::from_points_with_capacity(random_points(2000), 50).unwrap();
+    let kdtree: ImmutableKdTree<f32, usize, 3> = kdtree.into();
+
+    for query in random_queries(50) {
+        assert_eq!(
+            kdtree.nearest(&query, 10, &SquaredEuclidean).unwrap(),
+            kdtree.nearest(&query, 10, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.nearest_one(&query, &SquaredEuclidean).unwrap(),
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.within(&query, 0.05, &SquaredEuclidean).unwrap(),
+            kdtree.within(&query, 0.05, &squared_euclidean).unwrap()
+        );
+
+        let iterated: Vec<_> = kdtree
+            .iter_nearest(&query, &SquaredEuclidean)
+            .unwrap()
+            .take(20)
+            .collect();
+        let expected_iterated: Vec<_> = kdtree
+            .iter_nearest(&query, &squared_euclidean)
+            .unwrap()
+            .take(20)
+            .collect();
+        assert_eq!(iterated, expected_iterated);
+    }
+}
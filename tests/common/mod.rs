@@ -0,0 +1,239 @@
+//! Fixtures shared by the integration tests. Each test file compiles its own copy of this
+//! module and uses only some of it.
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+
+use kiddo::distance::Distance;
+use kiddo::{KdTree, Scalar};
+use rand::distributions::{Distribution, Standard};
+
+/// Returns `qty` random points, each paired with its index
+pub fn random_points<A, const K: usize>(qty: usize) -> Vec<([A; K], usize)>
+where
+    Standard: Distribution<[A; K]>,
+{
+    (0..qty).map(|i| (rand::random(), i)).collect()
+}
+
+/// Returns `qty` random query points
+pub fn random_queries<A, const K: usize>(qty: usize) -> Vec<[A; K]>
+where
+    Standard: Distribution<[A; K]>,
+{
+    (0..qty).map(|_| rand::random()).collect()
+}
+
+/// Returns a random box within the unit cube, as its `min` and `max` corners
+pub fn random_box() -> ([f64; 3], [f64; 3]) {
+    let a = rand::random::<[f64; 3]>();
+    let b = rand::random::<[f64; 3]>();
+    (
+        [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+        [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+    )
+}
+
+/// Builds a tree of `points` by adding them one at a time
+pub fn random_tree<A: Scalar, const K: usize>(points: &[([A; K], usize)]) -> KdTree<A, usize, K> {
+    let mut kdtree = KdTree::with_capacity(8).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+    kdtree
+}
+
+/// Returns the distance from `query` to the nearest of `points`, and its index
+pub fn brute_force_nearest<A, D, F, const K: usize>(
+    points: &[([A; K], usize)],
+    query: &[A; K],
+    distance: &F,
+) -> (D, usize)
+where
+    A: Scalar,
+    D: Scalar,
+    F: Distance<A, K, D>,
+{
+    points
+        .iter()
+        .map(|(p, i)| (distance.dist(query, p), *i))
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+        .unwrap()
+}
+
+/// Returns the indices of the points inside the box with corners `min` and `max`, in order
+pub fn brute_force_within_box<A: Scalar, const K: usize>(
+    points: &[([A; K], usize)],
+    min: &[A; K],
+    max: &[A; K],
+) -> Vec<usize> {
+    let mut result: Vec<_> = points
+        .iter()
+        .filter(|(p, _)| (0..K).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim]))
+        .map(|(_, i)| *i)
+        .collect();
+    result.sort_unstable();
+    result
+}
+
+/// Returns the distance from `query` to each of `points`, paired with the point's index and
+/// sorted nearest-first, breaking ties by index
+pub fn brute_force<A, D, F, const K: usize>(
+    points: &[([A; K], usize)],
+    query: &[A; K],
+    distance: &F,
+) -> Vec<(D, usize)>
+where
+    A: Scalar,
+    D: Scalar,
+    F: Distance<A, K, D>,
+{
+    let mut dists: Vec<(D, usize)> = points
+        .iter()
+        .map(|(p, i)| (distance.dist(query, p), *i))
+        .collect();
+    dists.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    dists
+}
+
+/// Returns the indices of the points no further than `radius` from `query`, nearest-first
+pub fn brute_force_within<A, D, F, const K: usize>(
+    points: &[([A; K], usize)],
+    query: &[A; K],
+    radius: D,
+    distance: &F,
+) -> Vec<usize>
+where
+    A: Scalar,
+    D: Scalar,
+    F: Distance<A, K, D>,
+{
+    brute_force(points, query, distance)
+        .into_iter()
+        .filter(|(d, _)| *d <= radius)
+        .map(|(_, i)| i)
+        .collect()
+}
+
+/// Checks that every query of `kdtree`, which holds `points`, finds the same elements as a
+/// brute force search from each of `queries`, using `radius` for queries that take one and
+/// boxes between consecutive `queries` for box queries. Results may order elements at equal
+/// distances differently, so where the order matters only their distances are compared,
+/// along with the distance to each returned element.
+pub fn assert_matches_brute_force<A, D, F, const K: usize>(
+    kdtree: &KdTree<A, usize, K>,
+    points: &[([A; K], usize)],
+    queries: &[[A; K]],
+    radius: D,
+    metric: &F,
+) where
+    A: Scalar + Debug,
+    D: Scalar + Debug,
+    F: Distance<A, K, D>,
+{
+    assert_eq!(kdtree.size(), points.len());
+
+    let mut positions = vec![None; points.iter().map(|(_, i)| i + 1).max().unwrap_or(0)];
+    for (p, i) in points {
+        positions[*i] = Some(*p);
+    }
+    let dist_to = |query: &[A; K], item: &usize| metric.dist(query, &positions[*item].unwrap());
+    let distances = |expected: &[(D, usize)], qty: usize| -> Vec<D> {
+        expected.iter().take(qty).map(|(d, _)| *d).collect()
+    };
+
+    for query in queries {
+        let expected = brute_force(points, query, metric);
+        let expected_within = sorted(brute_force_within(points, query, radius, metric));
+
+        let nearest = kdtree.nearest(query, 50, metric).unwrap();
+        assert_eq!(
+            nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>(),
+            distances(&expected, 50)
+        );
+        assert!(nearest.iter().all(|(d, item)| dist_to(query, item) == *d));
+
+        let (dist, item) = kdtree.nearest_one(query, metric).unwrap();
+        assert_eq!(dist, expected[0].0);
+        assert_eq!(dist_to(query, item), dist);
+
+        assert_eq!(
+            kdtree
+                .iter_nearest(query, metric)
+                .unwrap()
+                .take(500)
+                .map(|(d, _)| d)
+                .collect::<Vec<_>>(),
+            distances(&expected, 500)
+        );
+
+        let within = kdtree.within(query, radius, metric).unwrap();
+        assert!(within.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(within.iter().all(|(d, item)| dist_to(query, item) == *d));
+        assert_eq!(sorted(within.iter().map(|(_, i)| **i)), expected_within);
+        assert_eq!(
+            sorted(
+                kdtree
+                    .within_unsorted(query, radius, metric)
+                    .unwrap()
+                    .iter()
+                    .map(|(_, i)| **i)
+            ),
+            expected_within
+        );
+        assert_eq!(
+            kdtree.count_within(query, radius, metric).unwrap(),
+            expected_within.len()
+        );
+
+        let best: Vec<usize> = expected_within.iter().take(10).copied().collect();
+        assert_eq!(
+            sorted(kdtree.best_n_within(query, radius, 10, metric).unwrap()),
+            best
+        );
+        assert_eq!(
+            sorted(kdtree.best_n_within_into_iter(query, radius, 10, metric)),
+            best
+        );
+    }
+
+    for pair in queries.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let min = std::array::from_fn(|dim| if a[dim] < b[dim] { a[dim] } else { b[dim] });
+        let max = std::array::from_fn(|dim| if a[dim] < b[dim] { b[dim] } else { a[dim] });
+        let expected = brute_force_within_box(points, &min, &max);
+
+        let in_box = kdtree.within_box(&min, &max).unwrap();
+        assert!(in_box
+            .iter()
+            .all(|(p, i)| positions[**i].as_ref() == Some(p)));
+        assert_eq!(sorted(in_box.iter().map(|(_, i)| **i)), expected);
+        assert_eq!(
+            sorted(kdtree.iter_within_box(&min, &max).unwrap().map(|(_, i)| *i)),
+            expected
+        );
+        assert_eq!(kdtree.count_within_box(&min, &max).unwrap(), expected.len());
+    }
+
+    let nearest_one = kdtree.nearest_one_batch(queries, metric).unwrap();
+    let nearest = kdtree.nearest_batch(queries, 20, metric).unwrap();
+    let within = kdtree.within_batch(queries, radius, metric).unwrap();
+    for (idx, query) in queries.iter().enumerate() {
+        let expected = brute_force(points, query, metric);
+        assert_eq!(nearest_one[idx].0, expected[0].0);
+        assert_eq!(
+            nearest[idx].iter().map(|(d, _)| *d).collect::<Vec<_>>(),
+            distances(&expected, 20)
+        );
+        assert_eq!(
+            within[idx].len(),
+            brute_force_within(points, query, radius, metric).len()
+        );
+    }
+}
+
+fn sorted(items: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let mut items: Vec<usize> = items.into_iter().collect();
+    items.sort_unstable();
+    items
+}
@@ -0,0 +1,129 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force, random_tree};
+use kiddo::distance::{dot_product, Distance, InnerProduct};
+use kiddo::KdTree;
+
+fn random_unit_vector() -> [f32; 8] {
+    let mut v = [0f32; 8];
+    for x in v.iter_mut() {
+        *x = rand::random::<f32>() * 2.0 - 1.0;
+    }
+    let norm = dot_product(&v, &v).sqrt();
+    for x in v.iter_mut() {
+        *x /= norm;
+    }
+    v
+}
+
+fn random_unit_vectors(qty: usize) -> Vec<([f32; 8], usize)> {
+    (0..qty).map(|i| (random_unit_vector(), i)).collect()
+}
+
+/// The similarity of `query` to each of `points`, most similar first
+fn brute_force_similar(points: &[([f32; 8], usize)], query: &[f32; 8]) -> Vec<(f32, usize)> {
+    brute_force(points, query, &InnerProduct)
+        .into_iter()
+        .map(|(dist, i)| (-dist, i))
+        .collect()
+}
+
+/// Sorts most similar first, breaking ties by index, so that vectors with exactly the same
+/// similarity to the query are listed in the same order however they were found
+fn sort_by_similarity(similar: &mut [(f32, usize)]) {
+    similar.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)));
+}
+
+#[test]
+fn dot_product_sums_the_products() {
+    assert_eq!(dot_product(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+    assert_eq!(dot_product(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+}
+
+#[test]
+fn box_distances_bound_every_point_in_the_box() {
+    for _ in 0..1000 {
+        let p = random_unit_vector();
+        let (a, b) = (random_unit_vector(), random_unit_vector());
+        let mut min = a;
+        let mut max = b;
+        for dim in 0..8 {
+            min[dim] = a[dim].min(b[dim]);
+            max[dim] = a[dim].max(b[dim]);
+        }
+
+        let to_box = InnerProduct.dist_to_box(&p, &min, &max);
+        let to_far_side = InnerProduct.max_dist_to_box(&p, &min, &max);
+        for _ in 0..50 {
+            let mut inside = min;
+            for dim in 0..8 {
+                inside[dim] += (max[dim] - min[dim]) * rand::random::<f32>();
+            }
+            let dist = InnerProduct.dist(&p, &inside);
+            assert!(to_box <= dist + 1e-5);
+            assert!(to_far_side >= dist - 1e-5);
+        }
+    }
+}
+
+#[test]
+fn it_finds_the_most_similar_vectors() {
+    let points = random_unit_vectors(2000);
+    let kdtree = random_tree(&points);
+
+    for _ in 0..100 {
+        let query = random_unit_vector();
+        let expected = brute_force_similar(&points, &query);
+
+        let mut similar: Vec<(f32, usize)> = kdtree
+            .most_similar(&query, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(similarity, &i)| (similarity, i))
+            .collect();
+        assert!(similar.windows(2).all(|w| w[0].0 >= w[1].0));
+        sort_by_similarity(&mut similar);
+        assert_eq!(similar.len(), 10);
+        for ((similarity, i), (expected_similarity, expected_i)) in similar.iter().zip(&expected) {
+            assert_eq!(i, expected_i);
+            assert_eq!(similarity, expected_similarity);
+        }
+
+        let mut within: Vec<(f32, usize)> = kdtree
+            .similar_within(&query, 0.6)
+            .unwrap()
+            .into_iter()
+            .map(|(similarity, &i)| (similarity, i))
+            .collect();
+        assert!(within.windows(2).all(|w| w[0].0 >= w[1].0));
+        sort_by_similarity(&mut within);
+        let expected_within: Vec<(f32, usize)> = expected
+            .iter()
+            .copied()
+            .take_while(|(similarity, _)| *similarity >= 0.6)
+            .collect();
+        assert_eq!(within, expected_within);
+
+        let mut iterated: Vec<(f32, usize)> = kdtree
+            .iter_nearest(&query, &InnerProduct)
+            .unwrap()
+            .take(20)
+            .map(|(dist, &i)| (-dist, i))
+            .collect();
+        assert!(iterated.windows(2).all(|w| w[0].0 >= w[1].0));
+        sort_by_similarity(&mut iterated);
+        assert_eq!(iterated, expected[..20]);
+    }
+}
+
+#[test]
+fn it_finds_nothing_in_an_empty_tree() {
+    let kdtree: KdTree<f32, usize, 8> = KdTree::new();
+    let query = random_unit_vector();
+
+    assert!(kdtree.most_similar(&query, 5).unwrap().is_empty());
+    assert!(kdtree.similar_within(&query, 0.0).unwrap().is_empty());
+}
@@ -0,0 +1,94 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{random_points, random_tree};
+use kiddo::distance::squared_euclidean;
+use kiddo::KdTree;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn it_counts_the_same_points_as_within() {
+    let points = random_points(2000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    for (query, _) in random_points(100) {
+        for radius in [0.001, 0.01, 0.1, 1.0, 4.0].iter() {
+            assert_eq!(
+                kdtree
+                    .count_within(&query, *radius, &squared_euclidean)
+                    .unwrap(),
+                kdtree
+                    .within(&query, *radius, &squared_euclidean)
+                    .unwrap()
+                    .len()
+            );
+        }
+    }
+}
+
+#[test]
+fn it_counts_the_same_points_as_within_box() {
+    let points = random_points(2000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    for (corner, _) in random_points::<f64, 3>(100) {
+        let (min, max) = (
+            [corner[0] - 0.2, corner[1] - 0.3, 0.0],
+            [corner[0], corner[1], corner[2]],
+        );
+        assert_eq!(
+            kdtree.count_within_box(&min, &max).unwrap(),
+            kdtree.within_box(&min, &max).unwrap().len()
+        );
+    }
+    assert_eq!(
+        kdtree
+            .count_within_box(&[0.0, 0.0, 0.0], &[1.0, 1.0, 1.0])
+            .unwrap(),
+        2000
+    );
+}
+
+#[test]
+fn it_counts_whole_subtrees_without_visiting_their_points() {
+    let points = random_points(2000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    let count = AtomicUsize::new(0);
+    let counting_dist = |a: &[f64; 3], b: &[f64; 3]| {
+        count.fetch_add(1, Ordering::SeqCst);
+        squared_euclidean(a, b)
+    };
+
+    // every point is within the radius, so only the root needs to be measured
+    assert_eq!(
+        kdtree
+            .count_within(&[0.5, 0.5, 0.5], 1.0, &counting_dist)
+            .unwrap(),
+        2000
+    );
+    assert_eq!(count.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn it_handles_an_empty_tree() {
+    let kdtree: KdTree<f64, usize, 3> = KdTree::new();
+
+    assert_eq!(
+        kdtree
+            .count_within(&[0.5, 0.5, 0.5], 1.0, &squared_euclidean)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        kdtree
+            .count_within_box(&[0.0, 0.0, 0.0], &[1.0, 1.0, 1.0])
+            .unwrap(),
+        0
+    );
+    assert!(kdtree
+        .count_within(&[f64::NAN, 0.5, 0.5], 1.0, &squared_euclidean)
+        .is_err());
+}
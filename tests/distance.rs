@@ -0,0 +1,160 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force, brute_force_nearest, random_points, random_tree};
+use kiddo::distance::{
+    chebyshev, euclidean, manhattan, minkowski, squared_euclidean, Chebyshev, Distance, Manhattan,
+    Minkowski, SquaredEuclidean,
+};
+use kiddo::KdTree;
+
+fn check_box_distance_is_a_lower_bound<D: Distance<f64, 3>>(distance: &D) {
+    for _ in 0..1000 {
+        let p = rand::random::<[f64; 3]>();
+        let a = rand::random::<[f64; 3]>();
+        let b = rand::random::<[f64; 3]>();
+        let min = [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])];
+        let max = [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])];
+
+        let to_box = distance.dist_to_box(&p, &min, &max);
+        let inside = [
+            min[0] + (max[0] - min[0]) * rand::random::<f64>(),
+            min[1] + (max[1] - min[1]) * rand::random::<f64>(),
+            min[2] + (max[2] - min[2]) * rand::random::<f64>(),
+        ];
+        assert!(to_box <= distance.dist(&p, &inside));
+        assert!(to_box <= distance.dist(&p, &min));
+        assert!(to_box <= distance.dist(&p, &max));
+        assert!(distance.dist1(p[0] - a[0]) <= distance.dist(&p, &a));
+    }
+}
+
+#[test]
+fn box_distances_are_lower_bounds() {
+    check_box_distance_is_a_lower_bound(&SquaredEuclidean);
+    check_box_distance_is_a_lower_bound(&Manhattan);
+    check_box_distance_is_a_lower_bound(&Chebyshev);
+    check_box_distance_is_a_lower_bound(&Minkowski::<3>);
+    check_box_distance_is_a_lower_bound(&squared_euclidean);
+}
+
+#[test]
+fn metrics_agree_with_equivalent_closures() {
+    let points = random_points(1000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    let manhattan = |a: &[f64; 3], b: &[f64; 3]| (0..3).map(|i| (a[i] - b[i]).abs()).sum::<f64>();
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            kdtree.nearest(&query, 5, &SquaredEuclidean).unwrap(),
+            kdtree.nearest(&query, 5, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.nearest(&query, 5, &Minkowski::<2>).unwrap(),
+            kdtree.nearest(&query, 5, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            kdtree.within(&query, 0.2, &Manhattan).unwrap(),
+            kdtree.within(&query, 0.2, &manhattan).unwrap()
+        );
+        assert_eq!(
+            kdtree.nearest_one(&query, &Minkowski::<1>).unwrap(),
+            kdtree.nearest_one(&query, &manhattan).unwrap()
+        );
+    }
+}
+
+/// A metric that weights each axis differently
+struct Weighted([f64; 3]);
+
+impl Distance<f64, 3> for Weighted {
+    fn dist(&self, a: &[f64; 3], b: &[f64; 3]) -> f64 {
+        (0..3).map(|i| self.0[i] * (a[i] - b[i]).abs()).sum()
+    }
+
+    fn dist1(&self, axis_delta: f64) -> f64 {
+        let smallest_weight = self.0.iter().cloned().fold(f64::INFINITY, f64::min);
+        smallest_weight * axis_delta.abs()
+    }
+}
+
+#[test]
+fn custom_metrics_can_be_used_in_queries() {
+    let points = random_points(1000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+    let weighted = Weighted([1.0, 10.0, 100.0]);
+
+    for (query, _) in random_points(100) {
+        let expected = brute_force_nearest(&points, &query, &weighted);
+        let (dist, data) = kdtree.nearest_one(&query, &weighted).unwrap();
+
+        assert_eq!((dist, *data), expected);
+    }
+}
+
+fn check_against_brute_force<D: Distance<f64, 3>>(distance: &D, radius: f64) {
+    let points = random_points(1000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    for (query, _) in random_points(50) {
+        let expected = brute_force(&points, &query, distance);
+
+        let nearest: Vec<_> = kdtree
+            .nearest(&query, 10, distance)
+            .unwrap()
+            .into_iter()
+            .map(|(d, i)| (d, *i))
+            .collect();
+        let expected_dists: Vec<_> = expected.iter().take(10).map(|(d, _)| *d).collect();
+        assert_eq!(
+            nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>(),
+            expected_dists
+        );
+
+        let mut within: Vec<_> = kdtree
+            .within(&query, radius, distance)
+            .unwrap()
+            .into_iter()
+            .map(|(d, i)| (d, *i))
+            .collect();
+        within.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let expected_within: Vec<_> = expected
+            .iter()
+            .cloned()
+            .filter(|(d, _)| *d <= radius)
+            .collect();
+        assert_eq!(within, expected_within);
+
+        let (dist, _) = kdtree.nearest_one(&query, distance).unwrap();
+        assert_eq!(dist, expected[0].0);
+    }
+}
+
+#[test]
+fn euclidean_queries_match_brute_force() {
+    check_against_brute_force(&euclidean, 0.2);
+    check_against_brute_force(&squared_euclidean, 0.04);
+    check_against_brute_force(&SquaredEuclidean, 0.04);
+}
+
+#[test]
+fn manhattan_queries_match_brute_force() {
+    check_against_brute_force(&manhattan, 0.3);
+    check_against_brute_force(&Manhattan, 0.3);
+}
+
+#[test]
+fn chebyshev_queries_match_brute_force() {
+    check_against_brute_force(&chebyshev, 0.1);
+    check_against_brute_force(&Chebyshev, 0.1);
+}
+
+#[test]
+fn minkowski_queries_match_brute_force() {
+    check_against_brute_force(&minkowski::<3, _, _>, 0.2);
+    check_against_brute_force(&Minkowski::<3>, 0.008);
+    check_against_brute_force(&minkowski::<1, _, _>, 0.3);
+}
@@ -0,0 +1,223 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{assert_matches_brute_force, brute_force};
+use kiddo::distance::squared_euclidean;
+use kiddo::{ImmutableKdTree, KdTree};
+
+const CLUSTERS: [[f64; 2]; 5] = [
+    [0.0, 0.0],
+    [0.5, 0.5],
+    [0.5, 0.50001],
+    [1.0, 0.0],
+    [0.25, 0.75],
+];
+
+/// Most points are copies of a few locations, and the rest are scattered among them
+fn duplicated_points(qty: usize) -> Vec<([f64; 2], usize)> {
+    (0..qty)
+        .map(|i| {
+            if i % 3 == 0 {
+                (rand::random(), i)
+            } else {
+                (CLUSTERS[i % CLUSTERS.len()], i)
+            }
+        })
+        .collect()
+}
+
+fn identical_points(qty: usize) -> Vec<([f64; 2], usize)> {
+    (0..qty).map(|i| ([0.5, 0.5], i)).collect()
+}
+
+fn queries() -> Vec<[f64; 2]> {
+    let mut queries: Vec<[f64; 2]> = (0..30).map(|_| rand::random()).collect();
+    queries.extend(CLUSTERS.iter());
+    queries
+}
+
+fn sorted_items<'a>(items: impl IntoIterator<Item = &'a usize>) -> Vec<usize> {
+    let mut items: Vec<usize> = items.into_iter().copied().collect();
+    items.sort_unstable();
+    items
+}
+
+fn distances<'a>(results: impl IntoIterator<Item = (f64, &'a usize)>) -> Vec<f64> {
+    results.into_iter().map(|(d, _)| d).collect()
+}
+
+#[test]
+fn it_queries_duplicates_added_one_at_a_time() {
+    for points in [duplicated_points(3000), identical_points(1000)].iter() {
+        let mut kdtree = KdTree::with_capacity(8).unwrap();
+        for (point, data) in points.iter() {
+            kdtree.add(point, *data).unwrap();
+        }
+
+        assert_matches_brute_force(&kdtree, points, &queries(), 0.01, &squared_euclidean);
+    }
+}
+
+#[test]
+fn it_queries_duplicates_built_from_points() {
+    for points in [duplicated_points(3000), identical_points(1000)].iter() {
+        let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+
+        assert_matches_brute_force(&kdtree, points, &queries(), 0.01, &squared_euclidean);
+    }
+}
+
+#[test]
+fn it_adds_new_points_among_duplicates() {
+    let mut points = identical_points(1000);
+    let mut kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+
+    for (point, data) in duplicated_points(1000) {
+        kdtree.add(&point, data + 1000).unwrap();
+        points.push((point, data + 1000));
+    }
+
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+}
+
+#[test]
+fn it_removes_and_moves_duplicates() {
+    let mut points = duplicated_points(3000);
+    let mut kdtree = KdTree::with_capacity(8).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    // remove most copies of the first location, one at a time
+    for (point, data) in points
+        .iter()
+        .filter(|(p, i)| *p == CLUSTERS[0] && i % 10 != 0)
+    {
+        assert_eq!(kdtree.remove_one(point, data).unwrap(), Some(*data));
+    }
+    points.retain(|(p, i)| *p != CLUSTERS[0] || i % 10 == 0);
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+
+    // move every copy of the second location to the third
+    for (point, data) in points.iter_mut().filter(|(p, _)| *p == CLUSTERS[1]) {
+        assert!(kdtree.update_position(point, &CLUSTERS[2], data).unwrap());
+        *point = CLUSTERS[2];
+    }
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+
+    // staying at the same point leaves a duplicate where it is
+    let (point, data) = points.iter().find(|(p, _)| *p == CLUSTERS[2]).unwrap();
+    assert!(kdtree.update_position(point, point, data).unwrap());
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+
+    let removed = kdtree.remove_where(&CLUSTERS[3], |i| i % 2 == 0).unwrap();
+    assert_eq!(
+        sorted_items(&removed),
+        sorted_items(
+            points
+                .iter()
+                .filter(|(p, i)| *p == CLUSTERS[3] && i % 2 == 0)
+                .map(|(_, i)| i)
+        )
+    );
+    points.retain(|(p, i)| *p != CLUSTERS[3] || i % 2 != 0);
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+
+    let (point, data) = points.iter().find(|(p, _)| *p == CLUSTERS[4]).unwrap();
+    assert_eq!(kdtree.remove(point, data).unwrap(), 1);
+    assert_eq!(kdtree.remove(point, data).unwrap(), 0);
+    let data = *data;
+    points.retain(|(_, i)| *i != data);
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+
+    kdtree.retain(|p, _| *p != CLUSTERS[4]);
+    points.retain(|(p, _)| *p != CLUSTERS[4]);
+    kdtree.rebalance();
+    assert_matches_brute_force(&kdtree, &points, &queries(), 0.01, &squared_euclidean);
+}
+
+#[test]
+fn it_finds_duplicates_to_update() {
+    let mut kdtree: KdTree<f64, (usize, u32), 2> = KdTree::with_capacity(4).unwrap();
+    for i in 0..100 {
+        kdtree.add(&[1.0, 1.0], (i, 0)).unwrap();
+    }
+
+    for i in 0..100 {
+        kdtree.get_mut(&[1.0, 1.0], &(i, 0)).unwrap().1 = 1;
+    }
+
+    assert!(kdtree.get_mut(&[1.0, 1.0], &(50, 0)).is_none());
+    assert!(kdtree.get_mut(&[1.0, 1.0], &(50, 1)).is_some());
+    assert!(kdtree.get_mut(&[1.0, 2.0], &(50, 1)).is_none());
+}
+
+#[test]
+fn it_queries_duplicates_in_an_immutable_tree() {
+    let points = duplicated_points(3000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+    let kdtree: ImmutableKdTree<f64, usize, 2> = kdtree.into();
+
+    for query in queries() {
+        let expected = brute_force(&points, &query, &squared_euclidean);
+
+        assert_eq!(
+            distances(kdtree.nearest(&query, 50, &squared_euclidean).unwrap()),
+            expected
+                .iter()
+                .take(50)
+                .map(|(d, _)| *d)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap().0,
+            expected[0].0
+        );
+        assert_eq!(
+            sorted_items(
+                kdtree
+                    .within(&query, 0.01, &squared_euclidean)
+                    .unwrap()
+                    .iter()
+                    .map(|(_, i)| *i)
+            ),
+            sorted_items(expected.iter().filter(|(d, _)| *d <= 0.01).map(|(_, i)| i))
+        );
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn it_queries_duplicates_in_parallel() {
+    let points = duplicated_points(3000);
+    let kdtree = KdTree::par_from_points_with_capacity(points.clone(), 8).unwrap();
+    let queries = queries();
+
+    assert_eq!(
+        kdtree
+            .par_nearest_batch(&queries, 20, &squared_euclidean)
+            .unwrap(),
+        kdtree
+            .nearest_batch(&queries, 20, &squared_euclidean)
+            .unwrap()
+    );
+    assert_eq!(
+        kdtree
+            .par_within_batch(&queries, 0.01, &squared_euclidean)
+            .unwrap()
+            .iter()
+            .map(|within| within.len())
+            .collect::<Vec<_>>(),
+        queries
+            .iter()
+            .map(|query| {
+                brute_force(&points, query, &squared_euclidean)
+                    .iter()
+                    .filter(|(d, _)| *d <= 0.01)
+                    .count()
+            })
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,174 @@
+extern crate kiddo;
+extern crate rand;
+
+use kiddo::distance::squared_euclidean;
+use kiddo::{KdTree, SplitStrategy};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const CASES: u64 = 200;
+
+const STRATEGIES: [SplitStrategy; 4] = [
+    SplitStrategy::Midpoint,
+    SplitStrategy::Median,
+    SplitStrategy::SlidingMidpoint,
+    SplitStrategy::MaxVariance,
+];
+
+/// A value within a few ulps of `base`
+fn near(rng: &mut StdRng, base: f32) -> f32 {
+    f32::from_bits(base.to_bits() + rng.gen_range(0, 4))
+}
+
+/// Points whose coordinates are each within a few ulps of one another, so that there is
+/// rarely a value between the smallest and largest coordinate of a dimension
+fn near_identical_points(rng: &mut StdRng) -> Vec<([f32; 3], usize)> {
+    let scale = [1e-30, 1.0, 3.0e5, f32::MAX / 2.0][rng.gen_range(0, 4)];
+    let base: [f32; 3] = [
+        rng.gen::<f32>() * scale,
+        rng.gen::<f32>() * scale,
+        -rng.gen::<f32>() * scale,
+    ];
+    let qty = rng.gen_range(1, 300);
+    (0..qty)
+        .map(|i| {
+            let point = [
+                near(rng, base[0]),
+                near(rng, base[1]),
+                if i % 2 == 0 {
+                    base[2]
+                } else {
+                    near(rng, base[2])
+                },
+            ];
+            (point, i)
+        })
+        .collect()
+}
+
+/// Every element can be found by following split values down the tree, and queries give the
+/// same results as a brute force search
+fn assert_invariants(seed: u64, kdtree: &mut KdTree<f32, usize, 3>, points: &[([f32; 3], usize)]) {
+    assert_eq!(kdtree.size(), points.len(), "seed {}", seed);
+    assert_eq!(kdtree.validate(), Ok(()), "seed {}", seed);
+
+    for (point, item) in points.iter() {
+        assert_eq!(
+            kdtree.get_mut(point, item).copied(),
+            Some(*item),
+            "seed {}: {:?} is not where its split values lead",
+            seed,
+            point
+        );
+        assert_eq!(
+            kdtree.nearest_one(point, &squared_euclidean).unwrap().0,
+            0.0,
+            "seed {}",
+            seed
+        );
+        let at_point = points.iter().filter(|(p, _)| p == point).count();
+        assert_eq!(
+            kdtree.count_within_box(point, point).unwrap(),
+            at_point,
+            "seed {}",
+            seed
+        );
+    }
+
+    let query = points[0].0;
+    let mut expected: Vec<f32> = points
+        .iter()
+        .map(|(p, _)| squared_euclidean(&query, p))
+        .collect();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let nearest: Vec<f32> = kdtree
+        .nearest(&query, 20, &squared_euclidean)
+        .unwrap()
+        .into_iter()
+        .map(|(d, _)| d)
+        .collect();
+    assert_eq!(nearest, expected[..expected.len().min(20)], "seed {}", seed);
+}
+
+#[test]
+fn adding_near_identical_points_keeps_the_tree_consistent() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let points = near_identical_points(&mut rng);
+        let capacity = rng.gen_range(1, 5);
+
+        for strategy in STRATEGIES.iter() {
+            let mut kdtree = KdTree::with_split_strategy(capacity, *strategy).unwrap();
+            for (point, item) in points.iter() {
+                kdtree.add(point, *item).unwrap();
+            }
+
+            assert_invariants(seed, &mut kdtree, &points);
+        }
+    }
+}
+
+#[test]
+fn building_from_near_identical_points_keeps_the_tree_consistent() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let points = near_identical_points(&mut rng);
+        let capacity = rng.gen_range(1, 5);
+
+        for strategy in STRATEGIES.iter() {
+            let mut kdtree =
+                KdTree::from_points_with_split_strategy(points.clone(), capacity, *strategy)
+                    .unwrap();
+
+            assert_invariants(seed, &mut kdtree, &points);
+        }
+    }
+}
+
+#[test]
+fn removing_and_rebalancing_near_identical_points_keeps_the_tree_consistent() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut points = near_identical_points(&mut rng);
+        let capacity = rng.gen_range(1, 5);
+        let strategy = STRATEGIES[rng.gen_range(0, STRATEGIES.len())];
+
+        let mut kdtree = KdTree::with_split_strategy(capacity, strategy).unwrap();
+        for (point, item) in points.iter() {
+            kdtree.add(point, *item).unwrap();
+        }
+
+        while points.len() > 1 {
+            let (point, item) = points.swap_remove(rng.gen_range(0, points.len()));
+            assert_eq!(kdtree.remove(&point, &item).unwrap(), 1, "seed {}", seed);
+            if points.len().is_multiple_of(16) {
+                kdtree.rebalance();
+                assert_invariants(seed, &mut kdtree, &points);
+            }
+        }
+        assert_invariants(seed, &mut kdtree, &points);
+    }
+}
+
+#[test]
+fn points_at_the_ends_of_the_range_can_be_split() {
+    let points: Vec<([f32; 3], usize)> = (0..64)
+        .map(|i| {
+            let x = if i % 2 == 0 { -f32::MAX } else { f32::MAX };
+            let y = if i % 4 < 2 { f32::MIN_POSITIVE } else { 0.0 };
+            ([x, y, i as f32], i)
+        })
+        .collect();
+
+    for strategy in STRATEGIES.iter() {
+        let mut kdtree = KdTree::with_split_strategy(1, *strategy).unwrap();
+        for (point, item) in points.iter() {
+            kdtree.add(point, *item).unwrap();
+        }
+
+        assert_eq!(kdtree.size(), points.len());
+        for (point, item) in points.iter() {
+            assert_eq!(kdtree.get_mut(point, item).copied(), Some(*item));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_nearest, random_points, random_tree};
+use kiddo::distance::squared_euclidean;
+use kiddo::ErrorKind;
+use kiddo::KdTree;
+
+#[test]
+fn it_builds_a_tree_containing_every_point() {
+    let points = random_points(1000);
+    let kdtree: KdTree<f64, usize, 3> = KdTree::from_points_with_capacity(points, 4).unwrap();
+
+    assert_eq!(kdtree.size(), 1000);
+    assert!(!kdtree.is_leaf());
+    assert_eq!(
+        kdtree
+            .iter_nearest(&[0.5, 0.5, 0.5], &squared_euclidean)
+            .unwrap()
+            .count(),
+        1000
+    );
+}
+
+#[test]
+fn it_answers_queries_the_same_as_an_incrementally_built_tree() {
+    let points = random_points(2000);
+
+    let incremental = random_tree(&points);
+    let bulk: KdTree<f64, usize, 3> = KdTree::from_points_with_capacity(points, 8).unwrap();
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            bulk.nearest_one(&query, &squared_euclidean).unwrap(),
+            incremental.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+
+        let mut expected = incremental
+            .within(&query, 0.05, &squared_euclidean)
+            .unwrap();
+        let mut actual = bulk.within(&query, 0.05, &squared_euclidean).unwrap();
+        expected.sort_by(|a, b| a.1.cmp(b.1));
+        actual.sort_by(|a, b| a.1.cmp(b.1));
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn it_finds_the_nearest_one_outside_the_leaf_the_query_descends_into() {
+    // the query descends into the leaf holding 3, but 4 is nearer, across the split
+    let points: Vec<([f64; 1], usize)> = (0..8).map(|i| ([i as f64], i)).collect();
+    let kdtree = KdTree::from_points_with_capacity(points, 1).unwrap();
+
+    let (dist, item) = kdtree.nearest_one(&[3.9], &squared_euclidean).unwrap();
+    assert!((dist - 0.01).abs() < 1e-9);
+    assert_eq!(*item, 4);
+}
+
+#[test]
+fn it_finds_the_same_nearest_one_as_a_brute_force_search() {
+    let points = random_points(1000);
+    let kdtree: KdTree<f64, usize, 3> =
+        KdTree::from_points_with_capacity(points.clone(), 4).unwrap();
+
+    for (query, _) in random_points(200) {
+        let (dist, item) = brute_force_nearest(&points, &query, &squared_euclidean);
+        assert_eq!(
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap(),
+            (dist, &item)
+        );
+    }
+}
+
+#[test]
+fn it_can_be_collected_from_an_iterator() {
+    let kdtree: KdTree<f64, usize, 3> = random_points(100).into_iter().collect();
+
+    assert_eq!(kdtree.size(), 100);
+}
+
+#[test]
+fn it_handles_skewed_and_duplicate_points() {
+    let mut points: Vec<([f64; 1], usize)> = (0..100).map(|i| ([0f64], i)).collect();
+    points.push(([1f64], 100));
+    points.push(([1000f64], 101));
+
+    let kdtree = KdTree::from_points_with_capacity(points, 2).unwrap();
+
+    assert_eq!(kdtree.size(), 102);
+    assert_eq!(
+        kdtree.nearest_one(&[999f64], &squared_euclidean).unwrap(),
+        (1f64, &101)
+    );
+    assert_eq!(
+        kdtree
+            .within(&[0f64], 0.0, &squared_euclidean)
+            .unwrap()
+            .len(),
+        100
+    );
+}
+
+#[test]
+fn it_builds_an_empty_tree() {
+    let kdtree: KdTree<f64, usize, 2> = KdTree::from_points(vec![]).unwrap();
+
+    assert_eq!(kdtree.size(), 0);
+    assert_eq!(
+        kdtree.nearest_one(&[0f64, 0f64], &squared_euclidean),
+        Err(ErrorKind::Empty)
+    );
+}
+
+#[test]
+fn it_rejects_invalid_input() {
+    assert_eq!(
+        KdTree::<f64, usize, 2>::from_points_with_capacity(vec![([0f64, 0f64], 0)], 0).unwrap_err(),
+        ErrorKind::ZeroCapacity
+    );
+    assert_eq!(
+        KdTree::<f64, usize, 2>::from_points(vec![([f64::NAN, 0f64], 0)]).unwrap_err(),
+        ErrorKind::NonFiniteCoordinate
+    );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn it_builds_the_same_tree_in_parallel() {
+    let points = random_points(50_000);
+
+    let serial: KdTree<f64, usize, 3> =
+        KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+    let parallel: KdTree<f64, usize, 3> = KdTree::par_from_points_with_capacity(points, 8).unwrap();
+
+    assert_eq!(parallel.size(), 50_000);
+    assert_eq!(format!("{:?}", parallel), format!("{:?}", serial));
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            parallel.nearest_one(&query, &squared_euclidean).unwrap(),
+            serial.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn it_rejects_invalid_input_in_parallel() {
+    assert_eq!(
+        KdTree::<f64, usize, 2>::par_from_points_with_capacity(vec![([0f64, 0f64], 0)], 0)
+            .unwrap_err(),
+        ErrorKind::ZeroCapacity
+    );
+    assert_eq!(
+        KdTree::<f64, usize, 2>::par_from_points(vec![([f64::NAN, 0f64], 0)]).unwrap_err(),
+        ErrorKind::NonFiniteCoordinate
+    );
+}
@@ -0,0 +1,120 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_within, random_tree};
+use kiddo::distance::Distance;
+use kiddo::geo::{haversine, Haversine};
+
+fn random_lat_lon() -> [f64; 2] {
+    let [lat, lon] = rand::random::<[f64; 2]>();
+    [(2.0 * lat - 1.0).asin().to_degrees(), lon * 360.0 - 180.0]
+}
+
+fn random_locations(qty: usize) -> Vec<([f64; 2], usize)> {
+    (0..qty).map(|i| (random_lat_lon(), i)).collect()
+}
+
+#[test]
+fn box_distances_are_lower_bounds() {
+    for _ in 0..2000 {
+        let p = random_lat_lon();
+        let (a, b) = (random_lat_lon(), random_lat_lon());
+        let min = [a[0].min(b[0]), a[1].min(b[1])];
+        let max = [a[0].max(b[0]), a[1].max(b[1])];
+
+        let to_box = Haversine.dist_to_box(&p, &min, &max);
+        let nearest_sample = (0..200)
+            .map(|_| {
+                let [x, y] = rand::random::<[f64; 2]>();
+                let inside = [
+                    min[0] + (max[0] - min[0]) * x,
+                    min[1] + (max[1] - min[1]) * y,
+                ];
+                haversine(&p, &inside)
+            })
+            .fold(f64::INFINITY, f64::min);
+
+        assert!(to_box <= nearest_sample + 1e-9);
+        assert!(to_box <= haversine(&p, &min) + 1e-9);
+        assert!(to_box <= haversine(&p, &max) + 1e-9);
+        assert!(to_box <= haversine(&p, &[min[0], max[1]]) + 1e-9);
+        assert!(to_box <= haversine(&p, &[max[0], min[1]]) + 1e-9);
+    }
+}
+
+#[test]
+fn box_distances_wrap_around_the_antimeridian() {
+    let to_box = Haversine.dist_to_box(&[0.0, 179.0], &[-1.0, -180.0], &[1.0, -179.0]);
+    assert!((to_box - haversine(&[0.0, 179.0], &[0.0, -180.0])).abs() < 1e-9);
+
+    let to_box = Haversine.dist_to_box(&[10.0, -179.5], &[20.0, 170.0], &[30.0, 179.5]);
+    assert!((to_box - haversine(&[10.0, -179.5], &[20.0, 179.5])).abs() < 1e-9);
+
+    // near the pole, the nearest point of the box is across it
+    let to_box = Haversine.dist_to_box(&[89.0, 0.0], &[88.0, 170.0], &[89.5, 190.0]);
+    assert!((to_box - haversine(&[89.0, 0.0], &[89.5, 170.0])).abs() < 1e-9);
+}
+
+#[test]
+fn it_finds_the_same_points_as_brute_force() {
+    let points = random_locations(3000);
+    let kdtree = random_tree(&points);
+
+    for (query, _) in random_locations(100) {
+        let expected = brute_force_within(&points, &query, 800.0, &haversine);
+        let within: Vec<usize> = kdtree
+            .within_km(&query, 800.0)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(within, expected);
+        assert_eq!(
+            kdtree.count_within(&query, 800.0, &Haversine).unwrap(),
+            expected.len()
+        );
+
+        let nearest: Vec<usize> = kdtree
+            .nearest_km(&query, 5)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(
+            nearest,
+            brute_force_within(&points, &query, f64::INFINITY, &haversine)[..5]
+        );
+    }
+}
+
+#[test]
+fn it_finds_points_across_the_antimeridian_and_poles() {
+    let points = random_locations(3000);
+    let kdtree = random_tree(&points);
+
+    let queries = [
+        [0.0, 180.0],
+        [0.0, -180.0],
+        [-40.0, 179.9],
+        [65.0, -179.9],
+        [89.9, 0.0],
+        [-89.9, 90.0],
+    ];
+    for query in queries.iter() {
+        let expected = brute_force_within(&points, query, 1500.0, &haversine);
+        assert!(!expected.is_empty());
+
+        let within: Vec<usize> = kdtree
+            .within_km(query, 1500.0)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(within, expected);
+
+        let nearest = kdtree.nearest_km(query, 1).unwrap();
+        assert_eq!(*nearest[0].1, expected[0]);
+    }
+}
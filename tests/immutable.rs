@@ -0,0 +1,340 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{random_box, random_points, random_queries, random_tree};
+use kiddo::distance::squared_euclidean;
+use kiddo::ErrorKind;
+use kiddo::{ImmutableKdTree, KdTree};
+
+fn random_trees(qty: usize) -> (KdTree<f64, usize, 3>, ImmutableKdTree<f64, usize, 3>) {
+    let kdtree = random_tree(&random_points(qty));
+    let immutable = ImmutableKdTree::from(&kdtree);
+
+    (kdtree, immutable)
+}
+
+fn distances<T>(results: &[(f64, T)]) -> Vec<f64> {
+    results.iter().map(|(d, _)| *d).collect()
+}
+
+#[test]
+fn it_answers_queries_the_same_as_the_tree_it_was_built_from() {
+    let (kdtree, immutable) = random_trees(2000);
+
+    assert_eq!(immutable.size(), kdtree.size());
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            immutable.nearest_one(&query, &squared_euclidean).unwrap(),
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+
+        let expected: Vec<_> = kdtree
+            .iter_nearest(&query, &squared_euclidean)
+            .unwrap()
+            .take(10)
+            .collect();
+        assert_eq!(
+            distances(&immutable.nearest(&query, 10, &squared_euclidean).unwrap()),
+            distances(&expected)
+        );
+
+        let expected = kdtree.within(&query, 0.02, &squared_euclidean).unwrap();
+        assert_eq!(
+            distances(&immutable.within(&query, 0.02, &squared_euclidean).unwrap()),
+            distances(&expected)
+        );
+
+        let mut unsorted = immutable
+            .within_unsorted(&query, 0.02, &squared_euclidean)
+            .unwrap();
+        unsorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        assert_eq!(distances(&unsorted), distances(&expected));
+
+        let mut expected = kdtree
+            .best_n_within(&query, 0.05, 5, &squared_euclidean)
+            .unwrap();
+        let mut actual = immutable
+            .best_n_within(&query, 0.05, 5, &squared_euclidean)
+            .unwrap();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn it_iterates_over_every_element_nearest_first() {
+    let (kdtree, immutable) = random_trees(500);
+    let query = [0.5, 0.5, 0.5];
+
+    let expected: Vec<_> = kdtree
+        .iter_nearest(&query, &squared_euclidean)
+        .unwrap()
+        .collect();
+    let actual: Vec<_> = immutable
+        .iter_nearest(&query, &squared_euclidean)
+        .unwrap()
+        .collect();
+
+    assert_eq!(actual.len(), 500);
+    assert_eq!(distances(&actual), distances(&expected));
+}
+
+#[test]
+fn it_handles_empty_trees() {
+    let immutable: ImmutableKdTree<f64, usize, 2> = KdTree::new().into();
+
+    assert_eq!(immutable.size(), 0);
+    assert_eq!(
+        immutable.nearest_one(&[0f64, 0f64], &squared_euclidean),
+        Err(ErrorKind::Empty)
+    );
+    assert_eq!(
+        immutable.nearest(&[0f64, 0f64], 1, &squared_euclidean),
+        Ok(vec![])
+    );
+    assert_eq!(
+        immutable.within(&[0f64, 0f64], 1.0, &squared_euclidean),
+        Ok(vec![])
+    );
+    assert_eq!(
+        immutable
+            .iter_nearest(&[0f64, 0f64], &squared_euclidean)
+            .unwrap()
+            .count(),
+        0
+    );
+    assert_eq!(
+        immutable.nearest_one_batch(&[[0f64, 0f64]], &squared_euclidean),
+        Err(ErrorKind::Empty)
+    );
+    assert_eq!(
+        immutable.count_within(&[0f64, 0f64], 1.0, &squared_euclidean),
+        Ok(0)
+    );
+    assert_eq!(
+        immutable.within_box(&[0f64, 0f64], &[1f64, 1f64]),
+        Ok(vec![])
+    );
+    assert_eq!(
+        immutable.count_within_box(&[0f64, 0f64], &[1f64, 1f64]),
+        Ok(0)
+    );
+}
+
+#[test]
+fn it_handles_non_finite_coordinates() {
+    let (_, immutable) = random_trees(10);
+    let point = [f64::NAN, 0f64, 0f64];
+
+    assert_eq!(
+        immutable.nearest(&point, 1, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+    assert_eq!(
+        immutable.nearest_one(&point, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+    assert_eq!(
+        immutable.within(&point, 1.0, &squared_euclidean),
+        Err(ErrorKind::NonFiniteCoordinate)
+    );
+}
+
+#[test]
+fn it_handles_a_metric_that_returns_nan_like_the_tree_it_was_built_from() {
+    let (kdtree, immutable) = random_trees(500);
+    // undefined for points in the lower half of the first axis
+    let metric = |a: &[f64; 3], b: &[f64; 3]| {
+        if b[0] < 0.5 {
+            f64::NAN
+        } else {
+            squared_euclidean(a, b)
+        }
+    };
+
+    for (query, _) in random_points(50) {
+        assert_eq!(
+            immutable.within(&query, 0.1, &metric).unwrap(),
+            kdtree.within(&query, 0.1, &metric).unwrap()
+        );
+    }
+}
+
+#[test]
+fn it_answers_approximate_queries_the_same_as_the_tree_it_was_built_from() {
+    let (kdtree, immutable) = random_trees(2000);
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            distances(
+                &immutable
+                    .nearest_approx(&query, 10, 0.5, &squared_euclidean)
+                    .unwrap()
+            ),
+            distances(
+                &kdtree
+                    .nearest_approx(&query, 10, 0.5, &squared_euclidean)
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            distances(
+                &immutable
+                    .nearest_approx_with_budget(&query, 10, 0.0, 3, &squared_euclidean)
+                    .unwrap()
+            ),
+            distances(
+                &kdtree
+                    .nearest_approx_with_budget(&query, 10, 0.0, 3, &squared_euclidean)
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            immutable
+                .nearest_one_approx(&query, 0.5, &squared_euclidean)
+                .unwrap()
+                .0,
+            kdtree
+                .nearest_one_approx(&query, 0.5, &squared_euclidean)
+                .unwrap()
+                .0
+        );
+        assert_eq!(
+            immutable
+                .nearest_one_approx_with_budget(&query, 0.0, 1, &squared_euclidean)
+                .unwrap()
+                .0,
+            kdtree
+                .nearest_one_approx_with_budget(&query, 0.0, 1, &squared_euclidean)
+                .unwrap()
+                .0
+        );
+    }
+
+    assert_eq!(
+        immutable.nearest_approx(&[0.5; 3], 1, -1.0, &squared_euclidean),
+        Err(ErrorKind::InvalidEpsilon)
+    );
+}
+
+#[test]
+fn it_answers_batch_queries_the_same_as_the_tree_it_was_built_from() {
+    let (kdtree, immutable) = random_trees(2000);
+    let queries = random_queries(100);
+
+    let expected = kdtree
+        .nearest_one_batch(&queries, &squared_euclidean)
+        .unwrap();
+    let actual = immutable
+        .nearest_one_batch(&queries, &squared_euclidean)
+        .unwrap();
+    assert_eq!(distances(&actual), distances(&expected));
+
+    let expected = kdtree
+        .nearest_batch(&queries, 5, &squared_euclidean)
+        .unwrap();
+    let actual = immutable
+        .nearest_batch(&queries, 5, &squared_euclidean)
+        .unwrap();
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert_eq!(distances(actual), distances(expected));
+    }
+
+    let expected = kdtree
+        .within_batch(&queries, 0.02, &squared_euclidean)
+        .unwrap();
+    let actual = immutable
+        .within_batch(&queries, 0.02, &squared_euclidean)
+        .unwrap();
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert_eq!(distances(actual), distances(expected));
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_batches_match_sequential_batches() {
+    let (_, immutable) = random_trees(2000);
+    let queries = random_queries(100);
+
+    assert_eq!(
+        immutable.par_nearest_one_batch(&queries, &squared_euclidean),
+        immutable.nearest_one_batch(&queries, &squared_euclidean)
+    );
+    assert_eq!(
+        immutable.par_nearest_batch(&queries, 5, &squared_euclidean),
+        immutable.nearest_batch(&queries, 5, &squared_euclidean)
+    );
+    assert_eq!(
+        immutable.par_within_batch(&queries, 0.02, &squared_euclidean),
+        immutable.within_batch(&queries, 0.02, &squared_euclidean)
+    );
+}
+
+#[test]
+fn it_answers_counting_and_box_queries_the_same_as_the_tree_it_was_built_from() {
+    let (kdtree, immutable) = random_trees(2000);
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            immutable
+                .count_within(&query, 0.05, &squared_euclidean)
+                .unwrap(),
+            kdtree
+                .count_within(&query, 0.05, &squared_euclidean)
+                .unwrap()
+        );
+
+        let mut expected: Vec<_> = kdtree
+            .best_n_within_into_iter(&query, 0.05, 5, &squared_euclidean)
+            .collect();
+        let mut actual: Vec<_> = immutable
+            .best_n_within_into_iter(&query, 0.05, 5, &squared_euclidean)
+            .collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+    }
+
+    for _ in 0..100 {
+        let (min, max) = random_box();
+
+        let mut expected = kdtree.within_box(&min, &max).unwrap();
+        let mut actual = immutable.within_box(&min, &max).unwrap();
+        expected.sort_by_key(|(_, i)| **i);
+        actual.sort_by_key(|(_, i)| **i);
+        assert_eq!(actual, expected);
+
+        assert_eq!(
+            immutable.iter_within_box(&min, &max).unwrap().count(),
+            expected.len()
+        );
+        assert_eq!(
+            immutable.count_within_box(&min, &max).unwrap(),
+            kdtree.count_within_box(&min, &max).unwrap()
+        );
+    }
+}
+
+#[test]
+fn it_answers_similarity_queries_the_same_as_the_tree_it_was_built_from() {
+    let mut kdtree: KdTree<f32, usize, 2> = KdTree::with_capacity(4).unwrap();
+    for i in 0..100 {
+        let angle = i as f32 * 0.0628;
+        kdtree.add(&[angle.cos(), angle.sin()], i).unwrap();
+    }
+    let immutable = ImmutableKdTree::from(&kdtree);
+
+    assert_eq!(
+        immutable.most_similar(&[0.0, 1.0], 5).unwrap(),
+        kdtree.most_similar(&[0.0, 1.0], 5).unwrap()
+    );
+    assert_eq!(
+        immutable.similar_within(&[0.0, 1.0], 0.9).unwrap(),
+        kdtree.similar_within(&[0.0, 1.0], 0.9).unwrap()
+    );
+}
@@ -0,0 +1,231 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{assert_matches_brute_force, brute_force};
+use kiddo::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+use kiddo::{ImmutableKdTree, KdTree};
+
+/// Returns `qty` random points with coordinates between -999 and 999
+fn random_small_points(qty: usize) -> Vec<([i32; 3], usize)> {
+    (0..qty)
+        .map(|i| {
+            let point = [
+                rand::random::<i32>() % 1000,
+                rand::random::<i32>() % 1000,
+                rand::random::<i32>() % 1000,
+            ];
+            (point, i)
+        })
+        .collect()
+}
+
+fn random_small_queries(qty: usize) -> Vec<[i32; 3]> {
+    random_small_points(qty)
+        .into_iter()
+        .map(|(p, _)| p)
+        .collect()
+}
+
+#[test]
+fn it_finds_the_same_neighbours_as_a_brute_force_search() {
+    let points = random_small_points(2000);
+    let mut kdtree = KdTree::with_capacity(16).unwrap();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    assert_matches_brute_force(
+        &kdtree,
+        &points,
+        &random_small_queries(50),
+        20_000,
+        &SquaredEuclidean,
+    );
+}
+
+#[test]
+fn it_finds_the_same_neighbours_after_building_from_points() {
+    let points = random_small_points(2000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 16).unwrap();
+
+    assert_matches_brute_force(
+        &kdtree,
+        &points,
+        &random_small_queries(50),
+        20_000,
+        &SquaredEuclidean,
+    );
+}
+
+#[test]
+fn it_finds_the_same_neighbours_after_moving_and_removing_points() {
+    let mut points = random_small_points(2000);
+    let mut kdtree = KdTree::from_points_with_capacity(points.clone(), 16).unwrap();
+
+    for (point, data) in points.iter_mut() {
+        let new_point = [point[0] + 3, point[1] - 2, point[2] + 1];
+        assert!(kdtree.update_position(point, &new_point, data).unwrap());
+        *point = new_point;
+    }
+    assert_matches_brute_force(
+        &kdtree,
+        &points,
+        &random_small_queries(50),
+        20_000,
+        &SquaredEuclidean,
+    );
+
+    for (point, data) in points.iter().filter(|(_, data)| data % 3 == 0) {
+        assert_eq!(kdtree.remove(point, data).unwrap(), 1);
+    }
+    points.retain(|(_, data)| data % 3 != 0);
+    assert_matches_brute_force(
+        &kdtree,
+        &points,
+        &random_small_queries(50),
+        20_000,
+        &SquaredEuclidean,
+    );
+}
+
+#[test]
+fn it_finds_points_within_a_box() {
+    let points = random_small_points(2000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 16).unwrap();
+
+    let (min, max) = ([-200, -500, 0], [300, 100, 999]);
+    let mut found: Vec<usize> = kdtree
+        .within_box(&min, &max)
+        .unwrap()
+        .into_iter()
+        .map(|(_, i)| *i)
+        .collect();
+    found.sort_unstable();
+
+    let expected: Vec<usize> = points
+        .iter()
+        .filter(|(p, _)| (0..3).all(|dim| p[dim] >= min[dim] && p[dim] <= max[dim]))
+        .map(|(_, i)| *i)
+        .collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn it_finds_the_same_neighbours_in_an_immutable_tree() {
+    let points = random_small_points(2000);
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 16).unwrap();
+    let kdtree: ImmutableKdTree<i32, usize, 3> = kdtree.into();
+
+    for query in random_small_queries(50) {
+        let expected = brute_force(&points, &query, &SquaredEuclidean);
+
+        let nearest: Vec<u64> = kdtree
+            .nearest(&query, 10, &SquaredEuclidean)
+            .unwrap()
+            .into_iter()
+            .map(|(d, _)| d)
+            .collect();
+        let expected_nearest: Vec<u64> = expected.iter().take(10).map(|(d, _)| *d).collect();
+        assert_eq!(nearest, expected_nearest);
+
+        let (dist, _) = kdtree.nearest_one(&query, &SquaredEuclidean).unwrap();
+        assert_eq!(dist, expected[0].0);
+
+        assert_eq!(
+            kdtree
+                .within(&query, 20_000, &SquaredEuclidean)
+                .unwrap()
+                .len(),
+            expected.iter().filter(|(d, _)| *d <= 20_000).count()
+        );
+    }
+}
+
+#[test]
+fn it_measures_small_unsigned_coordinates_with_wide_distances() {
+    let points: Vec<([u16; 2], usize)> =
+        (0..500).map(|i| (rand::random::<[u16; 2]>(), i)).collect();
+    let kdtree = KdTree::from_points_with_capacity(points.clone(), 8).unwrap();
+
+    for _ in 0..50 {
+        let query = rand::random::<[u16; 2]>();
+
+        let manhattan = |p: &[u16; 2]| -> u64 {
+            (0..2)
+                .map(|dim| (p[dim] as i64 - query[dim] as i64).unsigned_abs())
+                .sum()
+        };
+        let chebyshev = |p: &[u16; 2]| -> u64 {
+            (0..2)
+                .map(|dim| (p[dim] as i64 - query[dim] as i64).unsigned_abs())
+                .max()
+                .unwrap()
+        };
+
+        let (dist, _) = kdtree.nearest_one(&query, &Manhattan).unwrap();
+        assert_eq!(
+            dist,
+            points.iter().map(|(p, _)| manhattan(p)).min().unwrap()
+        );
+
+        let (dist, _) = kdtree.nearest_one(&query, &Chebyshev).unwrap();
+        assert_eq!(
+            dist,
+            points.iter().map(|(p, _)| chebyshev(p)).min().unwrap()
+        );
+    }
+}
+
+#[test]
+fn it_handles_the_extremes_of_the_coordinate_type() {
+    let mut kdtree: KdTree<i32, usize, 2> = KdTree::with_capacity(2).unwrap();
+    let corners = [
+        [i32::MIN, i32::MIN],
+        [i32::MIN, i32::MAX],
+        [i32::MAX, i32::MIN],
+        [i32::MAX, i32::MAX],
+        [0, 0],
+        [-1, 0],
+        [0, -1],
+    ];
+    for (i, point) in corners.iter().enumerate() {
+        kdtree.add(point, i).unwrap();
+    }
+    assert_eq!(kdtree.size(), corners.len());
+
+    for (i, point) in corners.iter().enumerate() {
+        assert_eq!(
+            kdtree.nearest_one(point, &SquaredEuclidean).unwrap(),
+            (0, &i)
+        );
+    }
+
+    // the squared distance along one axis of the type's full range fits in a `u64`, and the
+    // distance between opposite corners saturates rather than overflowing
+    let nearest = kdtree
+        .nearest(&[i32::MIN, i32::MIN], 7, &SquaredEuclidean)
+        .unwrap();
+    assert_eq!(nearest[4].0, (u32::MAX as u64).pow(2));
+    assert_eq!(nearest[6], (u64::MAX, &3));
+}
+
+#[test]
+fn it_splits_between_adjacent_integers() {
+    let mut kdtree: KdTree<u8, usize, 1> = KdTree::with_capacity(1).unwrap();
+    for i in 0..=255u8 {
+        kdtree.add(&[i], i as usize).unwrap();
+    }
+    assert_eq!(kdtree.size(), 256);
+
+    for i in 0..=255u8 {
+        assert_eq!(
+            kdtree.nearest_one(&[i], &SquaredEuclidean).unwrap(),
+            (0, &(i as usize))
+        );
+        assert_eq!(kdtree.within(&[i], 1, &Manhattan).unwrap().len(), {
+            1 + (i > 0) as usize + (i < 255) as usize
+        });
+    }
+}
@@ -82,8 +82,8 @@ fn it_works() {
 
 #[test]
 fn handles_non_finite_coordinate() {
-    let point_a = ([std::f64::NAN, std::f64::NAN], 0f64);
-    let point_b = ([std::f64::INFINITY, std::f64::INFINITY], 0f64);
+    let point_a = ([f64::NAN, f64::NAN], 0f64);
+    let point_b = ([f64::INFINITY, f64::INFINITY], 0f64);
     let mut kdtree = KdTree::with_capacity(1).unwrap();
 
     assert_eq!(
@@ -250,7 +250,7 @@ fn handles_remove_correctly() {
     kdtree.add(&item3.0, item3.1).unwrap();
     kdtree.add(&item4.0, item4.1).unwrap();
 
-    let num_removed = kdtree.remove(&&item3.0, &item3.1).unwrap();
+    let num_removed = kdtree.remove(&item3.0, &item3.1).unwrap();
     assert_eq!(kdtree.size(), 3);
     assert_eq!(num_removed, 1);
     assert_eq!(
@@ -276,7 +276,7 @@ fn handles_remove_multiple_match() {
     kdtree.add(&item4.0, item4.1).unwrap();
 
     assert_eq!(kdtree.size(), 4);
-    let num_removed = kdtree.remove(&&[0f64], &1).unwrap();
+    let num_removed = kdtree.remove(&[0f64], &1).unwrap();
     assert_eq!(kdtree.size(), 2);
     assert_eq!(num_removed, 2);
     assert_eq!(
@@ -301,7 +301,7 @@ fn handles_remove_no_match() {
     kdtree.add(&item3.0, item3.1).unwrap();
     kdtree.add(&item4.0, item4.1).unwrap();
 
-    let num_removed = kdtree.remove(&&[1f64], &2).unwrap();
+    let num_removed = kdtree.remove(&[1f64], &2).unwrap();
     assert_eq!(kdtree.size(), 4);
     assert_eq!(num_removed, 0);
     assert_eq!(
@@ -0,0 +1,339 @@
+extern crate kiddo;
+extern crate memmap2;
+extern crate rand;
+
+mod common;
+
+use std::convert::TryInto;
+
+use common::{random_box, random_points, random_queries, random_tree};
+use kiddo::distance::squared_euclidean;
+use kiddo::mapped::{FormatError, MappedKdTree, FORMAT_VERSION};
+use kiddo::{ImmutableKdTree, KdTree};
+
+#[repr(C, align(64))]
+#[derive(Clone, Copy)]
+struct Block([u8; 64]);
+
+/// A byte buffer with the same alignment as a memory-mapped file
+struct AlignedBuffer {
+    blocks: Vec<Block>,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    fn new(bytes: &[u8]) -> Self {
+        let mut blocks = vec![Block([0; 64]); bytes.len().div_ceil(64)];
+        for (block, chunk) in blocks.iter_mut().zip(bytes.chunks(64)) {
+            block.0[..chunk.len()].copy_from_slice(chunk);
+        }
+        AlignedBuffer {
+            blocks,
+            len: bytes.len(),
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        let ptr = self.blocks.as_ptr() as *const u8;
+        unsafe { std::slice::from_raw_parts(ptr, self.len) }
+    }
+}
+
+fn random_immutable_tree(qty: usize) -> ImmutableKdTree<f64, usize, 3> {
+    random_tree(&random_points(qty)).into()
+}
+
+fn serialize<T: kiddo::mapped::Pod, const K: usize>(
+    tree: &ImmutableKdTree<f64, T, K>,
+) -> AlignedBuffer {
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    AlignedBuffer::new(&bytes)
+}
+
+#[test]
+fn it_answers_queries_the_same_as_the_tree_it_was_written_from() {
+    let tree = random_immutable_tree(2000);
+    let buffer = serialize(&tree);
+    let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(buffer.bytes()).unwrap();
+
+    assert_eq!(mapped.size(), tree.size());
+    assert_eq!(mapped.capacity(), tree.capacity());
+
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            mapped.nearest_one(&query, &squared_euclidean).unwrap(),
+            tree.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            mapped.nearest(&query, 10, &squared_euclidean).unwrap(),
+            tree.nearest(&query, 10, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            mapped.within(&query, 0.02, &squared_euclidean).unwrap(),
+            tree.within(&query, 0.02, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            mapped
+                .within_unsorted(&query, 0.02, &squared_euclidean)
+                .unwrap(),
+            tree.within_unsorted(&query, 0.02, &squared_euclidean)
+                .unwrap()
+        );
+        assert_eq!(
+            mapped
+                .best_n_within(&query, 0.05, 5, &squared_euclidean)
+                .unwrap(),
+            tree.best_n_within(&query, 0.05, 5, &squared_euclidean)
+                .unwrap()
+        );
+        assert!(mapped
+            .iter_nearest(&query, &squared_euclidean)
+            .unwrap()
+            .take(20)
+            .eq(tree
+                .iter_nearest(&query, &squared_euclidean)
+                .unwrap()
+                .take(20)));
+    }
+}
+
+#[test]
+fn it_answers_batch_approximate_and_box_queries_the_same_as_the_tree_it_was_written_from() {
+    let tree = random_immutable_tree(2000);
+    let buffer = serialize(&tree);
+    let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(buffer.bytes()).unwrap();
+    let queries = random_queries(100);
+
+    assert_eq!(
+        mapped.nearest_one_batch(&queries, &squared_euclidean),
+        tree.nearest_one_batch(&queries, &squared_euclidean)
+    );
+    assert_eq!(
+        mapped.nearest_batch(&queries, 5, &squared_euclidean),
+        tree.nearest_batch(&queries, 5, &squared_euclidean)
+    );
+    assert_eq!(
+        mapped.within_batch(&queries, 0.02, &squared_euclidean),
+        tree.within_batch(&queries, 0.02, &squared_euclidean)
+    );
+
+    for query in queries.iter() {
+        assert_eq!(
+            mapped.nearest_approx(query, 10, 0.5, &squared_euclidean),
+            tree.nearest_approx(query, 10, 0.5, &squared_euclidean)
+        );
+        assert_eq!(
+            mapped.nearest_one_approx_with_budget(query, 0.0, 1, &squared_euclidean),
+            tree.nearest_one_approx_with_budget(query, 0.0, 1, &squared_euclidean)
+        );
+        assert_eq!(
+            mapped.count_within(query, 0.05, &squared_euclidean),
+            tree.count_within(query, 0.05, &squared_euclidean)
+        );
+    }
+
+    for _ in 0..100 {
+        let (min, max) = random_box();
+        assert_eq!(mapped.within_box(&min, &max), tree.within_box(&min, &max));
+        assert_eq!(
+            mapped.count_within_box(&min, &max),
+            tree.count_within_box(&min, &max)
+        );
+    }
+}
+
+#[test]
+fn it_can_be_queried_from_a_memory_mapped_file() {
+    let tree = random_immutable_tree(500);
+    let path = std::env::temp_dir().join(format!("kiddo_mapped_test_{}.bin", std::process::id()));
+    tree.write_to(std::io::BufWriter::new(
+        std::fs::File::create(&path).unwrap(),
+    ))
+    .unwrap();
+
+    let file = std::fs::File::open(&path).unwrap();
+    let mmap = unsafe { memmap2::Mmap::map(&file).unwrap() };
+    let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(&mmap).unwrap();
+
+    let query = [0.5, 0.5, 0.5];
+    assert_eq!(
+        mapped.nearest(&query, 5, &squared_euclidean).unwrap(),
+        tree.nearest(&query, 5, &squared_euclidean).unwrap()
+    );
+
+    drop(mmap);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn it_handles_an_empty_tree() {
+    let tree: ImmutableKdTree<f64, usize, 3> = KdTree::new().into();
+    let buffer = serialize(&tree);
+    let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(buffer.bytes()).unwrap();
+
+    assert_eq!(mapped.size(), 0);
+    assert!(mapped
+        .nearest(&[0.0, 0.0, 0.0], 1, &squared_euclidean)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        mapped.count_within(&[0.0, 0.0, 0.0], 1.0, &squared_euclidean),
+        Ok(0)
+    );
+    assert_eq!(
+        mapped.within_box(&[0.0, 0.0, 0.0], &[1.0, 1.0, 1.0]),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn it_rejects_invalid_headers() {
+    let tree = random_immutable_tree(100);
+    let buffer = serialize(&tree);
+    let bytes = buffer.bytes();
+
+    let mut bad_magic = bytes.to_vec();
+    bad_magic[0] = b'X';
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&bad_magic).bytes()).err(),
+        Some(FormatError::InvalidMagic)
+    );
+
+    let mut bad_version = bytes.to_vec();
+    bad_version[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_ne_bytes());
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&bad_version).bytes()).err(),
+        Some(FormatError::UnsupportedVersion(FORMAT_VERSION + 1))
+    );
+
+    let mut bad_endianness = bytes.to_vec();
+    bad_endianness[12..16].reverse();
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&bad_endianness).bytes())
+            .err(),
+        Some(FormatError::IncompatibleLayout)
+    );
+}
+
+#[test]
+fn it_rejects_a_tree_of_a_different_type() {
+    let tree = random_immutable_tree(100);
+    let buffer = serialize(&tree);
+
+    assert_eq!(
+        MappedKdTree::<f64, usize, 2>::from_bytes(buffer.bytes()).err(),
+        Some(FormatError::DimensionMismatch)
+    );
+    assert_eq!(
+        MappedKdTree::<f32, usize, 3>::from_bytes(buffer.bytes()).err(),
+        Some(FormatError::CoordinateTypeMismatch)
+    );
+    assert_eq!(
+        MappedKdTree::<f64, u32, 3>::from_bytes(buffer.bytes()).err(),
+        Some(FormatError::ItemTypeMismatch)
+    );
+}
+
+#[test]
+fn it_rejects_truncated_or_misaligned_buffers() {
+    let tree = random_immutable_tree(100);
+    let buffer = serialize(&tree);
+    let bytes = buffer.bytes();
+
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(&bytes[..64]).err(),
+        Some(FormatError::Truncated)
+    );
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(&bytes[..bytes.len() - 1]).err(),
+        Some(FormatError::Truncated)
+    );
+
+    let mut shifted = vec![0u8];
+    shifted.extend_from_slice(bytes);
+    let shifted = AlignedBuffer::new(&shifted);
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(&shifted.bytes()[1..]).err(),
+        Some(FormatError::Misaligned)
+    );
+}
+
+#[test]
+fn it_rejects_a_corrupt_tree() {
+    let tree = random_immutable_tree(100);
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+
+    // the root index is the last 32-bit field of the header before the 64-bit fields
+    bytes[40..44].copy_from_slice(&u32::MAX.to_ne_bytes());
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&bytes).bytes()).err(),
+        Some(FormatError::Corrupt)
+    );
+}
+
+/// Returns `bytes` with the `u32` at `offset` from the start of leaf `leaf` replaced by the
+/// result of `patch`
+fn patch_leaf(bytes: &[u8], leaf: usize, offset: usize, patch: impl Fn(u32) -> u32) -> Vec<u8> {
+    // leaves_offset is the sixth of the 64-bit header fields, which start at byte 44
+    let leaves_offset = u64::from_ne_bytes(bytes[84..92].try_into().unwrap()) as usize;
+    // each leaf of an f64 tree in three dimensions holds its bounds, then start, then len
+    let at = leaves_offset + leaf * 56 + offset;
+    let value = u32::from_ne_bytes(bytes[at..at + 4].try_into().unwrap());
+
+    let mut bytes = bytes.to_vec();
+    bytes[at..at + 4].copy_from_slice(&patch(value).to_ne_bytes());
+    bytes
+}
+
+#[test]
+fn it_rejects_leaves_that_overlap_or_miss_items() {
+    let tree = random_immutable_tree(100);
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+    let (start, len) = (48, 52);
+
+    let overlapping = patch_leaf(&bytes, 1, start, |start| start - 1);
+    let gap = patch_leaf(&bytes, 1, start, |start| start + 1);
+    let short = patch_leaf(&bytes, 0, len, |len| len - 1);
+    let long = patch_leaf(&bytes, 0, len, |len| len + 1);
+    for corrupt in [overlapping, gap, short, long] {
+        assert_eq!(
+            MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&corrupt).bytes()).err(),
+            Some(FormatError::Corrupt)
+        );
+    }
+}
+
+#[test]
+fn it_rejects_leaves_over_capacity() {
+    let tree = random_immutable_tree(100);
+    let mut bytes = Vec::new();
+    tree.write_to(&mut bytes).unwrap();
+
+    // the capacity is the first of the 64-bit header fields
+    bytes[44..52].copy_from_slice(&1u64.to_ne_bytes());
+    assert_eq!(
+        MappedKdTree::<f64, usize, 3>::from_bytes(AlignedBuffer::new(&bytes).bytes()).err(),
+        Some(FormatError::Corrupt)
+    );
+}
+
+#[test]
+fn it_accepts_leaves_of_duplicates_over_capacity() {
+    let mut tree: KdTree<f64, usize, 3> = KdTree::with_capacity(4).unwrap();
+    for item in 0..20 {
+        tree.add(&[0.5, 0.5, 0.5], item).unwrap();
+    }
+    tree.add(&[0.25, 0.5, 0.5], 20).unwrap();
+    let tree: ImmutableKdTree<f64, usize, 3> = tree.into();
+    let buffer = serialize(&tree);
+    let mapped: MappedKdTree<f64, usize, 3> = MappedKdTree::from_bytes(buffer.bytes()).unwrap();
+
+    assert_eq!(mapped.size(), 21);
+    assert_eq!(
+        mapped.within(&[0.5, 0.5, 0.5], 0.0, &squared_euclidean),
+        tree.within(&[0.5, 0.5, 0.5], 0.0, &squared_euclidean)
+    );
+}
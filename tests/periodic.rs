@@ -0,0 +1,132 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_within, random_tree};
+use kiddo::distance::{squared_euclidean, Distance, Manhattan, Periodic, SquaredEuclidean};
+use kiddo::KdTree;
+
+const PERIODS: [f64; 3] = [1.0, 2.0, f64::INFINITY];
+
+/// Returns `qty` random points within one period of each axis that wraps
+fn random_points_in_period(qty: usize) -> Vec<([f64; 3], usize)> {
+    (0..qty)
+        .map(|i| {
+            let [x, y, z] = rand::random::<[f64; 3]>();
+            ([x, y * 2.0, z], i)
+        })
+        .collect()
+}
+
+/// The distance to the nearest of the images of `b` in the neighbouring periodic boxes
+fn nearest_image_distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    let mut nearest = f64::INFINITY;
+    for dx in [-1.0, 0.0, 1.0].iter() {
+        for dy in [-1.0, 0.0, 1.0].iter() {
+            let image = [b[0] + dx * PERIODS[0], b[1] + dy * PERIODS[1], b[2]];
+            nearest = nearest.min(squared_euclidean(a, &image));
+        }
+    }
+    nearest
+}
+
+#[test]
+fn it_measures_the_distance_to_the_nearest_image() {
+    let periodic = Periodic::new(SquaredEuclidean, PERIODS);
+
+    for (a, _) in random_points_in_period(1000) {
+        let b = random_points_in_period(1)[0].0;
+        assert!((periodic.dist(&a, &b) - nearest_image_distance(&a, &b)).abs() < 1e-12);
+    }
+
+    // axes with an infinite period do not wrap
+    assert_eq!(periodic.dist(&[0.0, 0.0, 0.0], &[0.0, 0.0, 3.0]), 9.0);
+}
+
+#[test]
+fn box_distances_bound_every_point_in_the_box() {
+    let periodic = Periodic::new(Manhattan, PERIODS);
+
+    for _ in 0..1000 {
+        let p = random_points_in_period(1)[0].0;
+        let (a, b) = (
+            random_points_in_period(1)[0].0,
+            random_points_in_period(1)[0].0,
+        );
+        let min = [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])];
+        let max = [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])];
+
+        let to_box = periodic.dist_to_box(&p, &min, &max);
+        let to_far_side = periodic.max_dist_to_box(&p, &min, &max);
+        for _ in 0..50 {
+            let [x, y, z] = rand::random::<[f64; 3]>();
+            let inside = [
+                min[0] + (max[0] - min[0]) * x,
+                min[1] + (max[1] - min[1]) * y,
+                min[2] + (max[2] - min[2]) * z,
+            ];
+            let dist = periodic.dist(&p, &inside);
+            assert!(to_box <= dist + 1e-12);
+            assert!(to_far_side >= dist - 1e-12);
+        }
+    }
+}
+
+#[test]
+fn it_finds_the_same_points_as_brute_force() {
+    let points = random_points_in_period(2000);
+    let kdtree = random_tree(&points);
+    let periodic = Periodic::new(SquaredEuclidean, PERIODS);
+
+    for (query, _) in random_points_in_period(100) {
+        let expected = brute_force_within(&points, &query, 0.02, &nearest_image_distance);
+        let within: Vec<usize> = kdtree
+            .within(&query, 0.02, &periodic)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(within, expected);
+        assert_eq!(
+            kdtree.count_within(&query, 0.02, &periodic).unwrap(),
+            expected.len()
+        );
+
+        let all = brute_force_within(&points, &query, f64::INFINITY, &nearest_image_distance);
+        let nearest: Vec<usize> = kdtree
+            .nearest(&query, 5, &periodic)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(nearest, all[..5]);
+
+        let iterated: Vec<usize> = kdtree
+            .iter_nearest(&query, &periodic)
+            .unwrap()
+            .take(20)
+            .map(|(_, &i)| i)
+            .collect();
+        assert_eq!(iterated, all[..20]);
+    }
+}
+
+#[test]
+fn it_finds_neighbours_across_the_boundary() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(2).unwrap();
+    for (i, x) in [0.01, 0.3, 0.5, 0.7, 0.9].iter().enumerate() {
+        kdtree.add(&[*x, 1.0, 0.5], i).unwrap();
+    }
+    let periodic = Periodic::new(SquaredEuclidean, PERIODS);
+
+    let nearest = kdtree.nearest_one(&[0.98, 1.0, 0.5], &periodic).unwrap();
+    assert_eq!(*nearest.1, 0);
+    assert!((nearest.0 - 0.03 * 0.03).abs() < 1e-12);
+}
+
+#[test]
+#[should_panic(expected = "periods must be greater than zero")]
+fn it_rejects_a_period_of_zero() {
+    Periodic::new(SquaredEuclidean, [1.0, 0.0, 1.0]);
+}
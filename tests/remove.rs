@@ -0,0 +1,169 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_nearest, random_points};
+use kiddo::distance::squared_euclidean;
+use kiddo::KdTree;
+
+#[test]
+fn it_answers_queries_correctly_after_heavy_churn() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(8).unwrap();
+    let mut points = random_points(2000);
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    // remove most of the points, then add some more back
+    for (point, data) in points.drain(..1800) {
+        assert_eq!(kdtree.remove(&point, &data).unwrap(), 1);
+    }
+    for (point, data) in random_points(100) {
+        let data = data + 2000;
+        kdtree.add(&point, data).unwrap();
+        points.push((point, data));
+    }
+
+    assert_eq!(kdtree.size(), points.len());
+    for (query, _) in random_points(100) {
+        let (dist, data) = kdtree.nearest_one(&query, &squared_euclidean).unwrap();
+        assert_eq!(
+            (dist, *data),
+            brute_force_nearest(&points, &query, &squared_euclidean)
+        );
+    }
+}
+
+#[test]
+fn it_collapses_back_into_a_leaf_when_emptied() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(4).unwrap();
+    let points = random_points(100);
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    for (point, data) in points.iter().skip(4) {
+        kdtree.remove(point, data).unwrap();
+    }
+    assert_eq!(kdtree.size(), 4);
+    assert!(kdtree.is_leaf());
+
+    for (point, data) in points.iter().take(4) {
+        kdtree.remove(point, data).unwrap();
+    }
+    assert_eq!(kdtree.size(), 0);
+    assert!(kdtree
+        .nearest_one(&[0.5, 0.5, 0.5], &squared_euclidean)
+        .is_err());
+}
+
+#[test]
+fn it_answers_queries_the_same_after_rebalancing() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(8).unwrap();
+    let points: Vec<_> = random_points::<f64, 3>(2000)
+        .into_iter()
+        .map(|(p, i)| ([p[0].powi(8), p[1], p[2]], i))
+        .collect();
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+    let mut rebalanced = kdtree.clone();
+    rebalanced.rebalance();
+
+    assert_eq!(rebalanced.size(), kdtree.size());
+    for (query, _) in random_points(100) {
+        assert_eq!(
+            rebalanced.nearest_one(&query, &squared_euclidean).unwrap(),
+            kdtree.nearest_one(&query, &squared_euclidean).unwrap()
+        );
+        assert_eq!(
+            rebalanced
+                .within(&query, 0.01, &squared_euclidean)
+                .unwrap()
+                .len(),
+            kdtree
+                .within(&query, 0.01, &squared_euclidean)
+                .unwrap()
+                .len()
+        );
+    }
+}
+
+#[test]
+fn it_removes_only_the_matching_item_when_a_point_is_shared() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(4).unwrap();
+    for i in 0..10 {
+        kdtree.add(&[0.5, 0.5, 0.5], i).unwrap();
+    }
+
+    assert_eq!(kdtree.remove(&[0.5, 0.5, 0.5], &3).unwrap(), 1);
+    assert_eq!(kdtree.remove(&[0.5, 0.5, 0.5], &3).unwrap(), 0);
+    assert_eq!(kdtree.size(), 9);
+}
+
+#[test]
+fn it_removes_items_at_a_point_by_predicate() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(4).unwrap();
+    let points = random_points(100);
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+        kdtree.add(point, *data + 1000).unwrap();
+    }
+
+    for (point, data) in points.iter() {
+        assert_eq!(
+            kdtree.remove_where(point, |item| *item >= 1000).unwrap(),
+            vec![*data + 1000]
+        );
+    }
+    assert_eq!(kdtree.size(), 100);
+    assert!(kdtree
+        .remove_where(&[2.0, 2.0, 2.0], |_| true)
+        .unwrap()
+        .is_empty());
+    assert!(kdtree
+        .remove_where(&[f64::NAN, 0.0, 0.0], |_| true)
+        .is_err());
+}
+
+#[test]
+fn it_removes_one_item_at_a_time() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(4).unwrap();
+    for _ in 0..3 {
+        kdtree.add(&[0.1, 0.2, 0.3], 7).unwrap();
+    }
+
+    assert_eq!(kdtree.remove_one(&[0.1, 0.2, 0.3], &7).unwrap(), Some(7));
+    assert_eq!(kdtree.size(), 2);
+    assert_eq!(kdtree.remove_one(&[0.1, 0.2, 0.3], &8).unwrap(), None);
+    assert_eq!(kdtree.remove_one(&[0.1, 0.2, 0.4], &7).unwrap(), None);
+    assert_eq!(kdtree.size(), 2);
+}
+
+#[test]
+fn it_retains_only_matching_items() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(8).unwrap();
+    let points = random_points(2000);
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    let mut removed = kdtree.retain(|point, data| point[0] < 0.5 && data % 3 != 0);
+    removed.sort_by_key(|(_, data)| *data);
+
+    let (kept, expected): (Vec<_>, Vec<_>) = points
+        .iter()
+        .cloned()
+        .partition(|(point, data)| point[0] < 0.5 && data % 3 != 0);
+    assert_eq!(removed, expected);
+    assert_eq!(kdtree.size(), kept.len());
+
+    for (query, _) in random_points(100) {
+        let (dist, data) = kdtree.nearest_one(&query, &squared_euclidean).unwrap();
+        assert_eq!(
+            (dist, *data),
+            brute_force_nearest(&kept, &query, &squared_euclidean)
+        );
+    }
+}
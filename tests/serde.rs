@@ -1,18 +1,16 @@
-#[cfg(feature = "serialize")]
-use serde_json;
+#![cfg(feature = "serialize")]
 
 extern crate kiddo;
+extern crate serde_json;
 
 use kiddo::distance::squared_euclidean;
-use kiddo::ErrorKind;
-use kiddo::KdTree;
+use kiddo::{KdTree, SplitStrategy};
 
 static POINT_A: ([f64; 2], usize) = ([0f64, 0f64], 0);
 static POINT_B: ([f64; 2], usize) = ([1f64, 1f64], 1);
 static POINT_C: ([f64; 2], usize) = ([2f64, 2f64], 2);
 static POINT_D: ([f64; 2], usize) = ([3f64, 3f64], 3);
 
-#[cfg(feature = "serialize")]
 #[test]
 fn it_serializes_and_deserializes_properly() {
     let capacity_per_node = 2;
@@ -24,10 +22,10 @@ fn it_serializes_and_deserializes_properly() {
     kdtree.add(&POINT_D.0, POINT_D.1).unwrap();
 
     let serialized = serde_json::to_string(&kdtree).unwrap();
-    println!("serialized: {:?}", &kdtree);
+    println!("serialized: {:?}", kdtree);
 
     let deserialized: KdTree<f64, usize, 2> = serde_json::from_str(&serialized).unwrap();
-    println!("deserialized: {:?}", &deserialized);
+    println!("deserialized: {:?}", deserialized);
 
     assert_eq!(deserialized.size(), 4);
     assert_eq!(
@@ -37,3 +35,47 @@ fn it_serializes_and_deserializes_properly() {
         vec![]
     );
 }
+
+#[test]
+fn it_keeps_the_split_strategy() {
+    let mut kdtree: KdTree<f64, usize, 2> =
+        KdTree::with_split_strategy(2, SplitStrategy::Median).unwrap();
+
+    kdtree.add(&POINT_A.0, POINT_A.1).unwrap();
+    kdtree.add(&POINT_B.0, POINT_B.1).unwrap();
+    kdtree.add(&POINT_C.0, POINT_C.1).unwrap();
+
+    let serialized = serde_json::to_string(&kdtree).unwrap();
+    let deserialized: KdTree<f64, usize, 2> = serde_json::from_str(&serialized).unwrap();
+
+    assert_eq!(deserialized.split_strategy(), SplitStrategy::Median);
+}
+
+#[test]
+fn it_deserializes_trees_without_a_split_strategy() {
+    let serialized = r#"{"size":1,"min_bounds":[0.0,0.0],"max_bounds":[0.0,0.0],"content":{"Leaf":{"points":[0.0,0.0],"bucket":[0],"capacity":2}}}"#;
+    let deserialized: KdTree<f64, usize, 2> = serde_json::from_str(serialized).unwrap();
+
+    assert_eq!(deserialized.size(), 1);
+    assert_eq!(deserialized.split_strategy(), SplitStrategy::Midpoint);
+}
+
+#[test]
+fn it_serializes_duplicates() {
+    let mut kdtree: KdTree<f64, usize, 2> = KdTree::with_capacity(2).unwrap();
+    for i in 0..10 {
+        kdtree.add(&POINT_B.0, i).unwrap();
+    }
+
+    let serialized = serde_json::to_string(&kdtree).unwrap();
+    let deserialized: KdTree<f64, usize, 2> = serde_json::from_str(&serialized).unwrap();
+
+    assert_eq!(deserialized.size(), 10);
+    assert_eq!(
+        deserialized
+            .within(&POINT_B.0, 0.0, &squared_euclidean)
+            .unwrap()
+            .len(),
+        10
+    );
+}
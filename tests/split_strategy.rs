@@ -0,0 +1,129 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{assert_matches_brute_force, random_queries};
+use kiddo::distance::squared_euclidean;
+use kiddo::{KdTree, SplitStrategy};
+
+const STRATEGIES: [SplitStrategy; 4] = [
+    SplitStrategy::Midpoint,
+    SplitStrategy::Median,
+    SplitStrategy::SlidingMidpoint,
+    SplitStrategy::MaxVariance,
+];
+
+/// Points in a few tight clusters, with some scattered outliers
+fn clustered_points(qty: usize) -> Vec<([f64; 3], usize)> {
+    let centres: Vec<[f64; 3]> = (0..5).map(|_| rand::random()).collect();
+    (0..qty)
+        .map(|i| {
+            let point = if i % 50 == 0 {
+                rand::random::<[f64; 3]>()
+            } else {
+                let centre = centres[i % centres.len()];
+                let offset = rand::random::<[f64; 3]>();
+                [
+                    centre[0] + (offset[0] - 0.5) * 0.001,
+                    centre[1] + (offset[1] - 0.5) * 0.001,
+                    centre[2] + (offset[2] - 0.5) * 0.001,
+                ]
+            };
+            (point, i)
+        })
+        .collect()
+}
+
+#[test]
+fn every_strategy_finds_the_same_neighbours_after_adding_points() {
+    let points = clustered_points(3000);
+    for strategy in STRATEGIES.iter() {
+        let mut kdtree = KdTree::with_split_strategy(8, *strategy).unwrap();
+        for (point, data) in points.iter() {
+            kdtree.add(point, *data).unwrap();
+        }
+
+        assert_eq!(kdtree.split_strategy(), *strategy);
+        assert_matches_brute_force(
+            &kdtree,
+            &points,
+            &random_queries(50),
+            0.0001,
+            &squared_euclidean,
+        );
+    }
+}
+
+#[test]
+fn every_strategy_finds_the_same_neighbours_after_building_from_points() {
+    let points = clustered_points(3000);
+    for strategy in STRATEGIES.iter() {
+        let mut kdtree =
+            KdTree::from_points_with_split_strategy(points.clone(), 8, *strategy).unwrap();
+        assert_matches_brute_force(
+            &kdtree,
+            &points,
+            &random_queries(50),
+            0.0001,
+            &squared_euclidean,
+        );
+
+        let more = clustered_points(1000);
+        for (point, data) in more.iter() {
+            kdtree.add(point, data + points.len()).unwrap();
+        }
+        let all: Vec<_> = points
+            .iter()
+            .copied()
+            .chain(more.iter().map(|(p, i)| (*p, i + points.len())))
+            .collect();
+        assert_matches_brute_force(
+            &kdtree,
+            &all,
+            &random_queries(50),
+            0.0001,
+            &squared_euclidean,
+        );
+    }
+}
+
+#[test]
+fn the_split_strategy_survives_rebalancing_and_removal() {
+    for strategy in STRATEGIES.iter() {
+        let mut points = clustered_points(2000);
+        let mut kdtree = KdTree::with_split_strategy(4, *strategy).unwrap();
+        for (point, data) in points.iter() {
+            kdtree.add(point, *data).unwrap();
+        }
+
+        for (point, data) in points.iter().filter(|(_, data)| data % 4 != 0) {
+            assert_eq!(kdtree.remove(point, data).unwrap(), 1);
+        }
+        points.retain(|(_, data)| data % 4 == 0);
+        kdtree.rebalance();
+
+        assert_eq!(kdtree.split_strategy(), *strategy);
+        assert_matches_brute_force(
+            &kdtree,
+            &points,
+            &random_queries(50),
+            0.0001,
+            &squared_euclidean,
+        );
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn every_strategy_builds_the_same_tree_in_parallel() {
+    let points = clustered_points(20_000);
+    for strategy in STRATEGIES.iter() {
+        let serial = KdTree::from_points_with_split_strategy(points.clone(), 8, *strategy).unwrap();
+        let parallel =
+            KdTree::par_from_points_with_split_strategy(points.clone(), 8, *strategy).unwrap();
+
+        assert_eq!(parallel.split_strategy(), *strategy);
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", serial));
+    }
+}
@@ -0,0 +1,78 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_nearest, random_points};
+use kiddo::distance::squared_euclidean;
+use kiddo::KdTree;
+
+#[test]
+fn it_answers_queries_correctly_after_moving_points() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(8).unwrap();
+    let mut points = random_points(1000);
+    for (point, data) in points.iter() {
+        kdtree.add(point, *data).unwrap();
+    }
+
+    for _ in 0..10 {
+        for (point, data) in points.iter_mut() {
+            // a mix of small moves, which mostly stay within a leaf, and large ones
+            let step = if *data % 4 == 0 { 0.5 } else { 0.01 };
+            let new_point = [
+                point[0] + (rand::random::<f64>() - 0.5) * step,
+                point[1] + (rand::random::<f64>() - 0.5) * step,
+                point[2] + (rand::random::<f64>() - 0.5) * step,
+            ];
+            assert!(kdtree.update_position(point, &new_point, data).unwrap());
+            *point = new_point;
+        }
+    }
+
+    assert_eq!(kdtree.size(), points.len());
+    for (query, _) in random_points(100) {
+        let (dist, data) = kdtree.nearest_one(&query, &squared_euclidean).unwrap();
+        assert_eq!(
+            (dist, *data),
+            brute_force_nearest(&points, &query, &squared_euclidean)
+        );
+    }
+}
+
+#[test]
+fn it_does_not_move_missing_points() {
+    let mut kdtree: KdTree<f64, usize, 3> = KdTree::with_capacity(2).unwrap();
+    for (point, data) in random_points(10) {
+        kdtree.add(&point, data).unwrap();
+    }
+
+    assert!(!kdtree
+        .update_position(&[2.0, 2.0, 2.0], &[0.5, 0.5, 0.5], &3)
+        .unwrap());
+    assert!(!kdtree
+        .update_position(&[2.0, 2.0, 2.0], &[2.0, 2.0, 2.1], &3)
+        .unwrap());
+    assert!(kdtree
+        .update_position(&[2.0, 2.0, 2.0], &[f64::NAN, 2.0, 2.0], &3)
+        .is_err());
+    assert_eq!(kdtree.size(), 10);
+}
+
+#[test]
+fn it_gets_mutable_references_to_items() {
+    let mut kdtree: KdTree<f64, (usize, u32), 3> = KdTree::with_capacity(4).unwrap();
+    let points = random_points(100);
+    for (point, data) in points.iter() {
+        kdtree.add(point, (*data, 0)).unwrap();
+    }
+
+    for (point, data) in points.iter() {
+        kdtree.get_mut(point, &(*data, 0)).unwrap().1 = 1;
+    }
+
+    for (point, data) in points.iter() {
+        assert!(kdtree.get_mut(point, &(*data, 0)).is_none());
+        assert!(kdtree.get_mut(point, &(*data, 1)).is_some());
+    }
+    assert!(kdtree.get_mut(&[2.0, 2.0, 2.0], &(0, 1)).is_none());
+}
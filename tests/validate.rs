@@ -0,0 +1,134 @@
+extern crate kiddo;
+extern crate rand;
+
+use kiddo::{KdTree, SplitStrategy};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const STRATEGIES: [SplitStrategy; 4] = [
+    SplitStrategy::Midpoint,
+    SplitStrategy::Median,
+    SplitStrategy::SlidingMidpoint,
+    SplitStrategy::MaxVariance,
+];
+
+/// A point on a coarse grid, so that some points are repeated often enough to form
+/// `Duplicates` nodes
+fn random_point(rng: &mut StdRng) -> [f64; 2] {
+    [
+        rng.gen_range(0, 20) as f64 / 4.0,
+        rng.gen_range(0, 5) as f64,
+    ]
+}
+
+#[test]
+fn it_stays_valid_through_adds_removes_and_moves() {
+    for (seed, split_strategy) in STRATEGIES.iter().enumerate() {
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let mut kdtree = KdTree::with_split_strategy(4, *split_strategy).unwrap();
+        let mut points: Vec<([f64; 2], usize)> = vec![];
+
+        for item in 0..2000 {
+            match rng.gen_range(0, 10) {
+                0..=5 => {
+                    let point = random_point(&mut rng);
+                    kdtree.add(&point, item).unwrap();
+                    points.push((point, item));
+                }
+                6 | 7 if !points.is_empty() => {
+                    let (point, item) = points.swap_remove(rng.gen_range(0, points.len()));
+                    assert_eq!(kdtree.remove(&point, &item).unwrap(), 1);
+                }
+                8 if !points.is_empty() => {
+                    let idx = rng.gen_range(0, points.len());
+                    let new_point = random_point(&mut rng);
+                    let (point, item) = &mut points[idx];
+                    assert!(kdtree.update_position(point, &new_point, item).unwrap());
+                    *point = new_point;
+                }
+                _ => {
+                    kdtree.retain(|_, item| item % 13 != 0);
+                    points.retain(|(_, item)| item % 13 != 0);
+                    kdtree.rebalance();
+                }
+            }
+
+            assert_eq!(kdtree.validate(), Ok(()), "{:?}", split_strategy);
+            assert_eq!(kdtree.size(), points.len());
+        }
+
+        assert_eq!(kdtree.stats().size, points.len());
+    }
+}
+
+#[test]
+fn it_is_valid_when_built_from_points() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let points: Vec<([f64; 2], usize)> = (0..5000).map(|i| (random_point(&mut rng), i)).collect();
+
+    for split_strategy in STRATEGIES.iter() {
+        let kdtree =
+            KdTree::from_points_with_split_strategy(points.clone(), 8, *split_strategy).unwrap();
+
+        assert_eq!(kdtree.validate(), Ok(()));
+    }
+}
+
+#[test]
+fn it_reports_the_shape_of_a_balanced_tree() {
+    let points: Vec<([f64; 2], usize)> = (0..1024).map(|i| ([i as f64, 0.0], i)).collect();
+    let kdtree = KdTree::from_points_with_capacity(points, 8).unwrap();
+    let stats = kdtree.stats();
+
+    assert_eq!(stats.size, 1024);
+    assert_eq!(stats.leaves, 128);
+    assert_eq!(stats.stems, 127);
+    assert_eq!(stats.duplicates, 0);
+    assert_eq!(stats.depth_histogram.len(), 8);
+    assert_eq!(stats.depth_histogram[7], 128);
+    assert_eq!(stats.min_fill_ratio, 1.0);
+    assert_eq!(stats.mean_fill_ratio, 1.0);
+    assert_eq!(stats.max_fill_ratio, 1.0);
+    assert_eq!(stats.oversized_leaves, 0);
+}
+
+#[test]
+fn it_reports_duplicates_as_oversized_leaves() {
+    let mut kdtree = KdTree::with_capacity(4).unwrap();
+    for item in 0..10 {
+        kdtree.add(&[1.0, 2.0], item).unwrap();
+    }
+    kdtree.add(&[3.0, 4.0], 10).unwrap();
+    let stats = kdtree.stats();
+
+    assert_eq!(kdtree.validate(), Ok(()));
+    assert_eq!(stats.leaves, 2);
+    assert_eq!(stats.stems, 1);
+    assert_eq!(stats.duplicates, 1);
+    assert_eq!(stats.depth_histogram, vec![0, 2]);
+    assert_eq!(stats.min_fill_ratio, 0.25);
+    assert_eq!(stats.max_fill_ratio, 2.5);
+    assert_eq!(stats.oversized_leaves, 1);
+}
+
+#[test]
+fn it_counts_the_memory_of_every_leaf() {
+    let small: KdTree<f64, [u8; 64], 2> =
+        KdTree::from_points_with_capacity(vec![([0.0, 0.0], [0; 64]); 1], 16).unwrap();
+    let points = (0..1000).map(|i| ([i as f64, 0.0], [0; 64])).collect();
+    let large: KdTree<f64, [u8; 64], 2> = KdTree::from_points_with_capacity(points, 16).unwrap();
+
+    assert!(small.stats().memory >= 64 + 16);
+    assert!(large.stats().memory >= 1000 * (64 + 16));
+}
+
+#[test]
+fn it_validates_an_empty_tree() {
+    let kdtree: KdTree<f64, usize, 2> = KdTree::new();
+    let stats = kdtree.stats();
+
+    assert_eq!(kdtree.validate(), Ok(()));
+    assert_eq!(stats.leaves, 1);
+    assert_eq!(stats.depth_histogram, vec![1]);
+    assert_eq!(stats.max_fill_ratio, 0.0);
+}
@@ -0,0 +1,83 @@
+extern crate kiddo;
+extern crate rand;
+
+mod common;
+
+use common::{brute_force_within_box, random_box, random_points, random_tree};
+use kiddo::ErrorKind;
+use kiddo::KdTree;
+
+#[test]
+fn it_finds_the_same_points_as_a_brute_force_search() {
+    let points = random_points(2000);
+    let kdtree: KdTree<f64, usize, 3> = random_tree(&points);
+
+    for _ in 0..100 {
+        let (min, max) = random_box();
+
+        let mut result: Vec<_> = kdtree
+            .within_box(&min, &max)
+            .unwrap()
+            .into_iter()
+            .map(|(p, i)| {
+                assert_eq!(p, points[*i].0);
+                *i
+            })
+            .collect();
+        result.sort();
+
+        assert_eq!(result, brute_force_within_box(&points, &min, &max));
+        assert_eq!(
+            kdtree.iter_within_box(&min, &max).unwrap().count(),
+            result.len()
+        );
+    }
+}
+
+#[test]
+fn it_includes_points_on_the_edges_of_the_box() {
+    let mut kdtree: KdTree<f64, usize, 2> = KdTree::with_capacity(2).unwrap();
+    for x in 0..10 {
+        for y in 0..10 {
+            kdtree.add(&[x as f64, y as f64], x * 10 + y).unwrap();
+        }
+    }
+
+    assert_eq!(
+        kdtree.within_box(&[2.0, 3.0], &[4.0, 3.0]).unwrap().len(),
+        3
+    );
+    assert_eq!(
+        kdtree.within_box(&[0.0, 0.0], &[9.0, 9.0]).unwrap().len(),
+        100
+    );
+    assert!(kdtree
+        .within_box(&[4.0, 3.0], &[2.0, 3.0])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn it_handles_an_empty_tree() {
+    let kdtree: KdTree<f64, usize, 3> = KdTree::new();
+
+    assert!(kdtree
+        .within_box(&[0.0, 0.0, 0.0], &[1.0, 1.0, 1.0])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn it_rejects_non_finite_corners() {
+    let kdtree: KdTree<f64, usize, 3> = KdTree::new();
+
+    assert_eq!(
+        kdtree
+            .within_box(&[f64::NAN, 0.0, 0.0], &[1.0, 1.0, 1.0])
+            .err(),
+        Some(ErrorKind::NonFiniteCoordinate)
+    );
+    assert!(kdtree
+        .iter_within_box(&[0.0, 0.0, 0.0], &[f64::INFINITY, 1.0, 1.0])
+        .is_err());
+}